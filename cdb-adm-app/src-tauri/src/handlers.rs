@@ -0,0 +1,313 @@
+use std::collections::BTreeMap;
+
+use cdb_adm::cli::ActionRow;
+use cdb_adm::{
+    agents_and_daemons_path_map, boot_up_agents_and_daemons, diff_preferences,
+    diff_service_snapshots, export_domain, launchctl_blame, list_all_agents_and_daemons,
+    read_exported_domains, restore_domains, service_statuses, split_service_target,
+    take_service_snapshot, turn_off_agents_and_daemons, write_backup, CDBSettings, Error,
+    PreferenceChange, Result, ServiceCatalog, ServiceChange, ServiceSnapshot, ServiceStatus,
+    Settings, SettingsEnvPath, Uid,
+};
+use serde::{Deserialize, Serialize};
+
+pub const PROGRESS_EVENT: &str = "progress";
+
+/// Progress of a long-running command, emitted once per item as the
+/// `progress` event.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProgressEvent {
+    pub operation: String,
+    pub current: usize,
+    pub total: usize,
+    pub target: String,
+    pub ok: bool,
+    pub error: Option<Error>,
+}
+
+/// Destination of progress events: the app handle in the app and a
+/// recorder in tests.
+pub trait Progress {
+    fn report(&self, event: ProgressEvent);
+}
+impl<R: tauri::Runtime> Progress for tauri::AppHandle<R> {
+    fn report(&self, event: ProgressEvent) {
+        use tauri::Emitter;
+        if let Err(error) = self.emit(PROGRESS_EVENT, event) {
+            eprintln!("[warning] emitting progress: {}", error);
+        }
+    }
+}
+
+fn report_each<T>(
+    progress: &dyn Progress,
+    operation: &str,
+    items: &[T],
+    mut run: impl FnMut(&T) -> (String, Result<()>),
+) -> Vec<(String, Result<()>)> {
+    let mut results = Vec::<(String, Result<()>)>::new();
+    for (index, item) in items.iter().enumerate() {
+        let (target, result) = run(item);
+        progress.report(ProgressEvent {
+            operation: operation.to_string(),
+            current: index + 1,
+            total: items.len(),
+            target: target.to_string(),
+            ok: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        });
+        results.push((target, result));
+    }
+    results
+}
+
+pub fn current_uid() -> Result<Uid> {
+    Ok(Uid::from(iocore::User::id()?.uid))
+}
+
+pub fn list_services(uid: &Uid) -> Result<Vec<ServiceStatus>> {
+    let mut services =
+        service_statuses(&list_all_agents_and_daemons(uid)?, &ServiceCatalog::builtin());
+    services.sort_by(|a, b| a.service.cmp(&b.service).then(a.domain.cmp(&b.domain)));
+    Ok(services)
+}
+
+/// Turns off each `domain/label` target like `adm turn-off`.
+pub fn turn_off(uid: &Uid, targets: &[String], progress: &dyn Progress) -> Result<Vec<ActionRow>> {
+    let targets = targets
+        .iter()
+        .map(|target| split_service_target(target))
+        .collect::<Result<Vec<(String, String)>>>()?;
+    Ok(report_each(progress, "turn-off", &targets, |(domain, service)| {
+        let results = turn_off_agents_and_daemons(
+            uid,
+            true,
+            vec![(domain.to_string(), service.to_string(), 0)],
+            false,
+        );
+        action_result(domain, service, results)
+    })
+    .iter()
+    .map(action_row)
+    .collect())
+}
+
+/// Bootstraps, enables and kickstarts each `domain/label` target like
+/// `adm boot-up`.
+pub fn boot_up(targets: &[String], progress: &dyn Progress) -> Result<Vec<ActionRow>> {
+    let targets = targets
+        .iter()
+        .map(|target| split_service_target(target))
+        .collect::<Result<Vec<(String, String)>>>()?;
+    let paths = agents_and_daemons_path_map(true, true, true)?;
+    Ok(report_each(progress, "boot-up", &targets, |(domain, service)| match paths.get(service) {
+        Some(info) => {
+            let results = boot_up_agents_and_daemons(
+                true,
+                vec![(domain.to_string(), service.to_string(), 0, info.clone())],
+            );
+            action_result(domain, service, results)
+        },
+        None => (
+            format!("{}/{}", domain, service),
+            Err(Error::LaunchdError(format!("path not found for {:#?}", service))),
+        ),
+    })
+    .iter()
+    .map(action_row)
+    .collect())
+}
+
+fn action_result(
+    domain: &str,
+    service: &str,
+    results: Vec<(String, String, i64, Result<()>)>,
+) -> (String, Result<()>) {
+    let target = format!("{}/{}", domain, service);
+    match results.into_iter().next() {
+        Some((_, _, _, result)) => (target, result),
+        None => (target, Ok(())),
+    }
+}
+
+fn action_row((target, result): &(String, Result<()>)) -> ActionRow {
+    ActionRow {
+        target: target.to_string(),
+        pid: 0,
+        ok: result.is_ok(),
+        error: result.as_ref().err().cloned(),
+    }
+}
+
+/// Live status, parsed plist and, for running services, the reason
+/// launchd started it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ServiceDetails {
+    pub status: ServiceStatus,
+    pub plist: Option<plist::Dictionary>,
+    pub reason: Option<String>,
+}
+
+pub fn inspect_service(uid: &Uid, target: &str) -> Result<ServiceDetails> {
+    let (domain, service) = split_service_target(target)?;
+    let all = list_all_agents_and_daemons(uid)?;
+    let plist = all
+        .iter()
+        .find(|(d, s, _, _, _, _)| *d == domain && *s == service)
+        .and_then(|(_, _, _, _, _, info)| info.as_ref().map(|(_, dictionary)| dictionary.clone()));
+    let status = service_statuses(&all, &ServiceCatalog::builtin())
+        .into_iter()
+        .find(|status| status.domain == domain && status.service == service)
+        .ok_or_else(|| Error::LaunchdError(format!("service {:#?} not found", target)))?;
+    let reason = if status.running() {
+        launchctl_blame(target).map(|blame| blame.to_string()).ok()
+    } else {
+        None
+    };
+    Ok(ServiceDetails { status, plist, reason })
+}
+
+pub fn snapshot(uid: &Uid, path: Option<&str>) -> Result<ServiceSnapshot> {
+    let snapshot = take_service_snapshot(uid)?;
+    if let Some(path) = path {
+        if CDBSettings::cli(true).encrypt_backups() {
+            snapshot.save_encrypted(&iocore::Path::new(path))?;
+        } else {
+            snapshot.save(&iocore::Path::new(path))?;
+        }
+    }
+    Ok(snapshot)
+}
+
+/// Changes from the snapshot at `before` to the one at `after` or,
+/// without it, to the services running now.
+pub fn diff_snapshot(uid: &Uid, before: &str, after: Option<&str>) -> Result<Vec<ServiceChange>> {
+    let before = ServiceSnapshot::load(&iocore::Path::new(before))?;
+    let after = match after {
+        Some(path) => ServiceSnapshot::load(&iocore::Path::new(path))?,
+        None => take_service_snapshot(uid)?,
+    };
+    Ok(diff_service_snapshots(&before, &after))
+}
+
+/// Exports `domains` like `cdb export`, writing the JSON to
+/// `output_path` when given, encrypted if `cdb.encrypt_backups` is set.
+pub fn export_preferences(
+    domains: &[String],
+    output_path: Option<&str>,
+    progress: &dyn Progress,
+) -> Result<BTreeMap<String, (plist::Value, Option<iocore::Path>)>> {
+    let mut exported = BTreeMap::<String, (plist::Value, Option<iocore::Path>)>::new();
+    let mut failed = Vec::<Error>::new();
+    report_each(progress, "export", domains, |domain| {
+        let result = export_domain(domain).map(|value| {
+            let path = iocore::Path::raw(domain).try_canonicalize();
+            let path = if path.is_file() { Some(path) } else { None };
+            exported.insert(domain.to_string(), (value, path));
+        });
+        if let Err(error) = &result {
+            failed.push(error.clone());
+        }
+        (domain.to_string(), result)
+    });
+    if let Some(error) = failed.into_iter().next() {
+        return Err(error);
+    }
+    if let Some(path) = output_path {
+        write_backup(
+            &iocore::Path::new(path),
+            &serde_json::to_string_pretty(&exported)?,
+            CDBSettings::cli(true).encrypt_backups(),
+        )?;
+    }
+    Ok(exported)
+}
+
+/// Imports the domains of a `cdb export` or `cdb delete` output file,
+/// only those in `domains` unless it is empty.
+pub fn restore_preferences(
+    path: &str,
+    domains: &[String],
+    progress: &dyn Progress,
+) -> Result<Vec<ActionRow>> {
+    let exported = read_exported_domains(&iocore::Path::new(path))?;
+    let names = exported
+        .keys()
+        .filter(|domain| domains.is_empty() || domains.contains(domain))
+        .cloned()
+        .collect::<Vec<String>>();
+    Ok(report_each(progress, "restore", &names, |domain| {
+        let mut results = restore_domains(&exported, &[domain.as_str()]);
+        results.pop().unwrap_or_else(|| (domain.to_string(), Ok(())))
+    })
+    .iter()
+    .map(action_row)
+    .collect())
+}
+
+/// Differences between the domains of an export file and their
+/// current values.
+pub fn diff_exported_preferences(
+    path: &str,
+    progress: &dyn Progress,
+) -> Result<Vec<PreferenceChange>> {
+    let before = read_exported_domains(&iocore::Path::new(path))?;
+    let domains = before.keys().cloned().collect::<Vec<String>>();
+    let after = export_preferences(&domains, None, progress)?;
+    Ok(diff_preferences(&before, &after))
+}
+
+pub fn read_settings() -> Result<Settings> {
+    Ok(Settings::cli(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::sync::Arc;
+
+    use cdb_adm::{override_command_runner, FakeCommandRunner, Uid};
+
+    use super::{boot_up, turn_off, Progress, ProgressEvent};
+
+    #[derive(Default)]
+    struct RecordedProgress {
+        events: RefCell<Vec<ProgressEvent>>,
+    }
+    impl Progress for RecordedProgress {
+        fn report(&self, event: ProgressEvent) {
+            self.events.borrow_mut().push(event);
+        }
+    }
+
+    #[test]
+    fn test_turn_off_reports_progress() {
+        let runner = Arc::new(
+            FakeCommandRunner::new()
+                .respond("launchctl bootout system/com.example.a", 0, "")
+                .respond("launchctl disable system/com.example.a", 0, "")
+                .respond("launchctl kill 9 system/com.example.a", 0, ""),
+        );
+        let _guard = override_command_runner(runner.clone());
+        let progress = RecordedProgress::default();
+        let targets = vec!["system/com.example.a".to_string(), "system/com.example.b".to_string()];
+        let rows = turn_off(&Uid(501), &targets, &progress).unwrap();
+        assert_eq!(rows.iter().map(|row| row.ok).collect::<Vec<bool>>(), vec![true, false]);
+        let events = progress.events.borrow();
+        assert_eq!(
+            events.iter().map(|event| (event.current, event.total)).collect::<Vec<_>>(),
+            vec![(1, 2), (2, 2)]
+        );
+        assert_eq!(events[1].target, "system/com.example.b");
+        assert!(events[1].error.is_some());
+        assert!(runner.calls().iter().any(|call| call.ends_with("bootout system/com.example.a")));
+    }
+
+    #[test]
+    fn test_invalid_targets_are_errors() {
+        let progress = RecordedProgress::default();
+        assert!(turn_off(&Uid(501), &["com.example.a".to_string()], &progress).is_err());
+        assert!(boot_up(&["gui/me/com.example.a".to_string()], &progress).is_err());
+        assert!(progress.events.borrow().is_empty());
+    }
+}
@@ -1,35 +1,102 @@
-use cdb_adm::{list_all_agents_and_daemons, Uid};
+pub mod handlers;
+
+use std::collections::BTreeMap;
+
+use cdb_adm::cli::ActionRow;
+use cdb_adm::{Error, PreferenceChange, ServiceChange, ServiceSnapshot, ServiceStatus, Settings};
+use handlers::{current_uid, ServiceDetails};
 
 #[tauri::command]
 fn greet(name: &str) -> String {
     format!("Hello, {}! You've been greeted from Rust!", name)
 }
-#[tauri::command]
-fn list_agents_and_daemons() -> Vec<Vec<String>> {
-    let uid = Uid::from(iocore::User::id().unwrap().uid);
-    let mut ads = list_all_agents_and_daemons(&uid)
-        .unwrap()
+#[tauri::command(async)]
+fn list_agents_and_daemons() -> Result<Vec<Vec<String>>, Error> {
+    Ok(handlers::list_services(&current_uid()?)?
         .iter()
-        .map(|(domain, service, pid, status, enabled, info)| {
+        .map(|status| {
             vec![
-                service.to_string(),
-                pid.to_string(),
-                domain.to_string(),
-                status.map(|h| h.to_string()).unwrap_or_else(|| "-".to_string()),
-                if *enabled { "enabled" } else { "disabled" }.to_string(),
-                info.clone().map(|(path, _)| path.to_string()).unwrap_or_default(),
+                status.service.to_string(),
+                status.pid.to_string(),
+                status.domain.to_string(),
+                status.status.map(|h| h.to_string()).unwrap_or_else(|| "-".to_string()),
+                if status.enabled { "enabled" } else { "disabled" }.to_string(),
+                status.path.clone().unwrap_or_default(),
             ]
         })
-        .collect::<Vec<Vec<String>>>();
-    ads.sort_by_key(|service| service[0].to_string());
-    ads
+        .collect())
+}
+#[tauri::command(async)]
+fn list_services() -> Result<Vec<ServiceStatus>, Error> {
+    handlers::list_services(&current_uid()?)
+}
+#[tauri::command(async)]
+fn turn_off(app: tauri::AppHandle, targets: Vec<String>) -> Result<Vec<ActionRow>, Error> {
+    handlers::turn_off(&current_uid()?, &targets, &app)
+}
+#[tauri::command(async)]
+fn boot_up(app: tauri::AppHandle, targets: Vec<String>) -> Result<Vec<ActionRow>, Error> {
+    handlers::boot_up(&targets, &app)
+}
+#[tauri::command(async)]
+fn inspect_service(target: String) -> Result<ServiceDetails, Error> {
+    handlers::inspect_service(&current_uid()?, &target)
+}
+#[tauri::command(async)]
+fn snapshot(path: Option<String>) -> Result<ServiceSnapshot, Error> {
+    handlers::snapshot(&current_uid()?, path.as_deref())
+}
+#[tauri::command(async)]
+fn diff_snapshot(before: String, after: Option<String>) -> Result<Vec<ServiceChange>, Error> {
+    handlers::diff_snapshot(&current_uid()?, &before, after.as_deref())
+}
+#[tauri::command(async)]
+fn export_preferences(
+    app: tauri::AppHandle,
+    domains: Vec<String>,
+    output_path: Option<String>,
+) -> Result<BTreeMap<String, (plist::Value, Option<iocore::Path>)>, Error> {
+    handlers::export_preferences(&domains, output_path.as_deref(), &app)
+}
+#[tauri::command(async)]
+fn restore_preferences(
+    app: tauri::AppHandle,
+    path: String,
+    domains: Vec<String>,
+) -> Result<Vec<ActionRow>, Error> {
+    handlers::restore_preferences(&path, &domains, &app)
+}
+#[tauri::command(async)]
+fn diff_preferences(app: tauri::AppHandle, path: String) -> Result<Vec<PreferenceChange>, Error> {
+    handlers::diff_exported_preferences(&path, &app)
+}
+#[tauri::command]
+fn read_settings() -> Result<Settings, Error> {
+    handlers::read_settings()
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    if let Ok(config) = cdb_adm::resolve_adm_config(&[]) {
+        cdb_adm::set_adm_config(config);
+    }
+    cdb_adm::set_execution_settings(cdb_adm::adm_settings().execution());
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
-        .invoke_handler(tauri::generate_handler![greet, list_agents_and_daemons])
+        .invoke_handler(tauri::generate_handler![
+            greet,
+            list_agents_and_daemons,
+            list_services,
+            turn_off,
+            boot_up,
+            inspect_service,
+            snapshot,
+            diff_snapshot,
+            export_preferences,
+            restore_preferences,
+            diff_preferences,
+            read_settings
+        ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
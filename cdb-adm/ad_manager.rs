@@ -1,17 +1,59 @@
 mod adm;
+mod catalog;
+mod impact;
 mod launchctl;
+mod launchd_log;
+mod outcome;
 mod parser;
+mod plist_spec;
+mod policy;
+mod resources;
+mod snapshot;
+mod status;
 
 pub use adm::{
-    agents_and_daemons_path_map, list_agents_and_daemons, list_agents_and_daemons_paths,
-    salient_system_uids, system_uids, Uid,
+    agents_and_daemons_path_map, included_system_uids, launchd_system_uids,
+    list_agents_and_daemons, list_agents_and_daemons_paths, salient_system_uids, system_uids, Uid,
 };
+pub use catalog::{
+    category_name, parse_catalog, ServiceCatalog, BOOTOUT_CATALOG, NON_NEEDED_CATALOG,
+};
+pub use impact::{analyse_impact, mach_services_from_plist, ImpactHint};
 pub use launchctl::{
-    agent_or_daemon, agent_or_daemon_prefix, bootout_agent_or_daemon, launchctl, launchctl_ok,
-    list_active_agents_and_daemons, list_all_agents_and_daemons, list_disabled_agents_and_daemons,
-    turn_off_agent_or_daemon,
+    agent_or_daemon, agent_or_daemon_prefix, bootout_agent_or_daemon, launchctl, launchctl_blame,
+    launchctl_list, launchctl_ok, list_active_agents_and_daemons, list_all_agents_and_daemons,
+    list_disabled_agents_and_daemons, split_service_target, turn_off_agent_or_daemon,
+    LAUNCHCTL_PATH,
+};
+pub use launchd_log::{
+    launchd_log_findings, parse_launchd_log, LaunchdLogCapture, LaunchdLogCursor, LaunchdLogEntry,
+    LaunchdLogFinding, LaunchdLogReport, CRASH_LOOP_THRESHOLD, LAUNCHD_LOG_PATH,
+};
+pub use outcome::LaunchctlOutcome;
+pub use parser::{
+    extract_service_info_opt, extract_service_name, parse_endpoints, parse_launchctl_blame,
+    parse_launchctl_list, parse_services, parse_user_subdomains, BlameReason, LaunchctlBlame,
+    LaunchctlListEntry, LaunchdEndpoint,
+};
+pub use plist_spec::{CalendarInterval, LaunchdKind, LaunchdPlistSpec, PlistFormat};
+pub use policy::{
+    enforce_launch_agent_plist, evaluate_policy, service_states, Deviation, Policy,
+    PolicyDeviation, PolicyRule, ServiceState, DEFAULT_ENFORCE_INTERVAL_SECS,
+    ENFORCE_LAUNCH_AGENT_LABEL,
+};
+pub use resources::{
+    format_epoch, format_kib, parse_proc_boot_time, parse_proc_resources, parse_ps_duration_ms,
+    parse_ps_resources, parse_ps_threads, proc_resources, process_resources, ps_resources,
+    rank_by_resources, ProcessResources, ResourceSort, PROC_CLOCK_TICKS, PROC_PATH, PS_PATH,
 };
-pub use parser::{extract_service_info_opt, extract_service_name, parse_services};
+pub use snapshot::{diff_service_snapshots, ServiceChange, ServiceDifference, ServiceSnapshot};
+pub use status::{
+    filter_and_sort_statuses, label_matches, service_statuses, DomainKind, ServiceStatus,
+    StatusFilter, StatusSort,
+};
+
+use crate::{turn_off_in_batches, BatchOutcome, BisectState, HealthCheck};
+use policy::enforce_launch_agent_spec;
 
 pub const NON_NEEDED_SERVICES: [&'static str; 297] = include!("agents-and-daemons.noon");
 pub const BOOTOUT_SERVICES: [&'static str; 56] = include!("bootout.noon");
@@ -22,7 +64,7 @@ pub fn turn_off_smart(
     services: Vec<String>,
     include_non_needed: bool,
     log: bool,
-) {
+) -> Vec<(String, String, i64, crate::Result<()>)> {
     let all_agents_and_daemons = list_all_agents_and_daemons(uid)
         .expect("active agents and daemons list")
         .iter()
@@ -32,7 +74,304 @@ pub fn turn_off_smart(
         .collect();
     let ads_to_turn_off =
         agents_and_daemons_to_turn_off(quiet, services, include_non_needed, all_agents_and_daemons);
-    turn_off_agents_and_daemons(uid, quiet, ads_to_turn_off, log);
+    turn_off_agents_and_daemons(uid, quiet, ads_to_turn_off, log)
+}
+pub fn explain_turn_off_smart(
+    uid: &Uid,
+    quiet: bool,
+    services: Vec<String>,
+    include_non_needed: bool,
+) -> crate::Result<Vec<ImpactHint>> {
+    use std::collections::BTreeMap;
+    let all_agents_and_daemons = list_all_agents_and_daemons(uid)?;
+    let ads_to_turn_off = agents_and_daemons_to_turn_off(
+        quiet,
+        services,
+        include_non_needed,
+        all_agents_and_daemons
+            .iter()
+            .map(|(domain, service, pid, status, _, info)| {
+                (domain.clone(), service.clone(), *pid, *status, info.clone())
+            })
+            .collect(),
+    );
+    let dictionaries = all_agents_and_daemons
+        .iter()
+        .filter_map(|(domain, service, _, _, _, info)| {
+            info.clone().map(|(_, dictionary)| (format!("{}/{}", domain, service), dictionary))
+        })
+        .collect::<BTreeMap<String, plist::Dictionary>>();
+    let targets = ads_to_turn_off
+        .iter()
+        .map(|(domain, service, _)| {
+            (
+                domain.to_string(),
+                service.to_string(),
+                dictionaries.get(&format!("{}/{}", domain, service)).cloned(),
+            )
+        })
+        .collect::<Vec<(String, String, Option<plist::Dictionary>)>>();
+
+    let mut endpoints = BTreeMap::<String, Vec<LaunchdEndpoint>>::new();
+    for (domain, _, _) in &targets {
+        if endpoints.contains_key(domain) {
+            continue;
+        }
+        match launchctl::launchctl_print(domain, false).and_then(|data| parse_endpoints(&data)) {
+            Ok(domain_endpoints) => {
+                endpoints.insert(domain.to_string(), domain_endpoints);
+            },
+            Err(error) =>
+                if !quiet {
+                    eprintln!("[warning] reading endpoints of {}: {}", domain, error);
+                },
+        }
+    }
+    let findings = match iocore::Path::raw(LAUNCHD_LOG_PATH).read() {
+        Ok(data) => launchd_log_findings(&parse_launchd_log(&data)),
+        Err(error) => {
+            if !quiet {
+                eprintln!("[warning] reading {}: {}", LAUNCHD_LOG_PATH, error);
+            }
+            Vec::new()
+        },
+    };
+    Ok(analyse_impact(&targets, &endpoints, &findings))
+}
+pub fn turn_off_smart_verified(
+    uid: &Uid,
+    quiet: bool,
+    services: Vec<String>,
+    include_non_needed: bool,
+    log: bool,
+    batch_size: usize,
+    checks: &[Box<dyn HealthCheck>],
+) -> crate::Result<Vec<BatchOutcome<(String, String, i64)>>> {
+    use std::collections::BTreeMap;
+    let all_agents_and_daemons = list_all_agents_and_daemons(uid)?;
+    let paths = all_agents_and_daemons
+        .iter()
+        .filter_map(|(domain, service, _, _, _, info)| {
+            info.clone().map(|(path, _)| (format!("{}/{}", domain, service), path))
+        })
+        .collect::<BTreeMap<String, iocore::Path>>();
+    let ads_to_turn_off = agents_and_daemons_to_turn_off(
+        quiet,
+        services,
+        include_non_needed,
+        all_agents_and_daemons
+            .iter()
+            .map(|(domain, service, pid, status, _, info)| {
+                (domain.clone(), service.clone(), *pid, *status, info.clone())
+            })
+            .collect(),
+    );
+    Ok(turn_off_in_batches(
+        &ads_to_turn_off,
+        batch_size,
+        checks,
+        |batch| {
+            turn_off_agents_and_daemons(uid, quiet, batch.to_vec(), log);
+        },
+        |batch| roll_back_agents_and_daemons(quiet, batch, &paths),
+    ))
+}
+/// Bisects the agents and daemons to turn off until the ones that
+/// break `checks` are found, saving progress to `state_path` after
+/// every step and resuming from it when it already exists.
+pub fn bisect_smart(
+    uid: &Uid,
+    quiet: bool,
+    services: Vec<String>,
+    include_non_needed: bool,
+    log: bool,
+    state_path: &iocore::Path,
+    checks: &[Box<dyn HealthCheck>],
+) -> crate::Result<BisectState<(String, String, i64)>> {
+    use std::collections::BTreeMap;
+    let all_agents_and_daemons = list_all_agents_and_daemons(uid)?;
+    let paths = all_agents_and_daemons
+        .iter()
+        .filter_map(|(domain, service, _, _, _, info)| {
+            info.clone().map(|(path, _)| (format!("{}/{}", domain, service), path))
+        })
+        .collect::<BTreeMap<String, iocore::Path>>();
+    let mut state = match BisectState::load(state_path)? {
+        Some(state) => {
+            if !quiet {
+                println!("resuming bisect from {:#?}", state_path.to_string());
+            }
+            state
+        },
+        None => {
+            let failures = crate::run_health_checks(checks);
+            if let Some((name, error)) = failures.first() {
+                return Err(crate::Error::SystemError(format!(
+                    "health check {:#?} fails before turning anything off: {}",
+                    name, error
+                )));
+            }
+            BisectState::new(agents_and_daemons_to_turn_off(
+                quiet,
+                services,
+                include_non_needed,
+                all_agents_and_daemons
+                    .iter()
+                    .map(|(domain, service, pid, status, _, info)| {
+                        (domain.clone(), service.clone(), *pid, *status, info.clone())
+                    })
+                    .collect(),
+            ))
+        },
+    };
+    while !state.is_done() {
+        for (name, error) in state.step(
+            checks,
+            |set| {
+                turn_off_agents_and_daemons(uid, quiet, set.to_vec(), log);
+            },
+            |set| roll_back_agents_and_daemons(quiet, set, &paths),
+        ) {
+            if !quiet {
+                eprintln!("[info] health check {:#?} failed: {}", name, error);
+            }
+        }
+        state.save(state_path)?;
+    }
+    Ok(state)
+}
+fn roll_back_agents_and_daemons(
+    quiet: bool,
+    agents_and_daemons: &[(String, String, i64)],
+    paths: &std::collections::BTreeMap<String, iocore::Path>,
+) {
+    for (domain, service, _) in agents_and_daemons {
+        match paths.get(&format!("{}/{}", domain, service)) {
+            Some(path) => match enable_and_kickstart_smart(domain, service, path) {
+                (commands, Ok(_)) =>
+                    if !quiet {
+                        for command in &commands {
+                            eprintln!("{} -", command);
+                        }
+                        println!("{}/{} rolled back", domain, service);
+                    },
+                (_, Err(error)) =>
+                    eprintln!("[error] rolling back {}/{}: {}", domain, service, error),
+            },
+            None => eprintln!("[warning] cannot roll back {}/{}: path not found", domain, service),
+        }
+    }
+}
+pub fn live_service_states(uid: &Uid) -> crate::Result<Vec<ServiceState>> {
+    let system_uids = crate::included_system_uids();
+    let loaded = list_active_agents_and_daemons(uid, &system_uids)?
+        .iter()
+        .map(|(domain, service, pid, _, _)| (domain.to_string(), service.to_string(), *pid))
+        .collect::<Vec<(String, String, i64)>>();
+    let overrides = list_disabled_agents_and_daemons(uid, &system_uids)?
+        .iter()
+        .map(|(domain, service, _, _, enabled, _)| {
+            (domain.to_string(), service.to_string(), *enabled)
+        })
+        .collect::<Vec<(String, String, bool)>>();
+    Ok(service_states(&loaded, &overrides))
+}
+pub fn take_service_snapshot(uid: &Uid) -> crate::Result<ServiceSnapshot> {
+    Ok(ServiceSnapshot::new(service_statuses(
+        &list_all_agents_and_daemons(uid)?,
+        &ServiceCatalog::builtin(),
+    )))
+}
+/// Re-applies the turn-offs of every service deviating from `policy`,
+/// returning each deviation along with the result of its correction.
+pub fn enforce_policy(
+    uid: &Uid,
+    quiet: bool,
+    policy: &Policy,
+    dry_run: bool,
+) -> crate::Result<Vec<(PolicyDeviation, crate::Result<()>)>> {
+    let mut corrections = Vec::<(PolicyDeviation, crate::Result<()>)>::new();
+    for deviation in evaluate_policy(policy, &live_service_states(uid)?) {
+        if dry_run {
+            corrections.push((deviation, Ok(())));
+            continue;
+        }
+        let (domain, service) = (deviation.domain.as_str(), deviation.service.as_str());
+        let result = match deviation.rule {
+            PolicyRule::Bootout => bootout_disable_and_kill_smart(uid, domain, service)
+                .or_else(|_| disable_and_kill_smart(uid, domain, service)),
+            PolicyRule::Disabled => disable_and_kill_smart(uid, domain, service),
+        };
+        match &result {
+            Ok(_) =>
+                if !quiet {
+                    println!("[corrected] {}", deviation);
+                },
+            Err(error) => eprintln!("[error] correcting {}: {}", deviation, error),
+        }
+        corrections.push((deviation, result));
+    }
+    Ok(corrections)
+}
+/// Writes the LaunchAgent that runs `adm enforce --once` periodically
+/// into ~/Library/LaunchAgents and returns its path.
+pub fn install_enforce_launch_agent(uid: &Uid, interval: u64) -> crate::Result<iocore::Path> {
+    let program = std::env::current_exe()?;
+    let log_path = iocore::Path::new("~/Library/Logs").join("adm-enforce.log");
+    let spec = enforce_launch_agent_spec(
+        &program.to_string_lossy(),
+        uid,
+        crate::settings_environment(),
+        interval,
+        &log_path.to_string(),
+    );
+    install_launchd_plist(uid, &spec, PlistFormat::Xml, false, false)
+}
+/// Writes the plist of `spec` into its search root, owned by
+/// root:wheel when launchd requires it, and optionally bootstraps it.
+pub fn install_launchd_plist(
+    uid: &Uid,
+    spec: &LaunchdPlistSpec,
+    format: PlistFormat,
+    system: bool,
+    bootstrap: bool,
+) -> crate::Result<iocore::Path> {
+    let bytes = spec.to_bytes(format)?;
+    let mut path = spec.install_path(system);
+    let as_root = spec.owned_by_root(system);
+    if as_root {
+        let staging = iocore::Path::raw(std::env::temp_dir().to_string_lossy())
+            .join(format!("{}.plist", spec.label));
+        staging.write(&bytes)?;
+        let args = [
+            "/usr/bin/install",
+            "-o",
+            "root",
+            "-g",
+            "wheel",
+            "-m",
+            "644",
+            &staging.to_string(),
+            &path.to_string(),
+        ];
+        let (exit_code, _, err) = crate::command_runner().run("/usr/bin/sudo", &args)?;
+        staging.delete()?;
+        if exit_code != 0 {
+            return Err(crate::Error::IOError(format!(
+                "installing {:#?} failed with {}: {}",
+                path.to_string(),
+                exit_code,
+                err.trim()
+            )));
+        }
+    } else {
+        path.write(&bytes)?;
+        path.set_mode(0o644)?;
+    }
+    if bootstrap {
+        launchctl_subcommand(&["bootstrap", &spec.domain(uid), &path.to_string()], as_root)?;
+    }
+    Ok(path)
 }
 pub fn agents_and_daemons_to_turn_off(
     quiet: bool,
@@ -82,7 +421,25 @@ pub fn turn_off_agents_and_daemons(
     quiet: bool,
     agents_and_daemons_to_turn_off: Vec<(String, String, i64)>,
     log: bool,
-) {
+) -> Vec<(String, String, i64, crate::Result<()>)> {
+    turn_off_capturing_launchd_log(
+        uid,
+        quiet,
+        agents_and_daemons_to_turn_off,
+        log.then(LaunchdLogCapture::default).as_ref(),
+    )
+}
+
+/// Turns off each service, writing the launchd.log lines appended
+/// meanwhile to its report when `capture` is given. Services are then
+/// turned off one at a time whatever the configured concurrency, as
+/// the lines of concurrent operations could not be told apart.
+pub fn turn_off_capturing_launchd_log(
+    uid: &Uid,
+    quiet: bool,
+    agents_and_daemons_to_turn_off: Vec<(String, String, i64)>,
+    capture: Option<&LaunchdLogCapture>,
+) -> Vec<(String, String, i64, crate::Result<()>)> {
     if !agents_and_daemons_to_turn_off.is_empty() {
         if !quiet {
             println!("turning off services");
@@ -92,88 +449,87 @@ pub fn turn_off_agents_and_daemons(
             println!("ok");
         }
     }
-    use iocore::Path;
-    for (domain, service, pid) in agents_and_daemons_to_turn_off {
-        let log_base_path =
-            Path::cwd().join("logs").join(service.as_str()).join(domain.replace("/", "-"));
-        if log {
-            log_base_path
-                .join("launchd.0.log")
-                .write(
-                    &Path::raw("/private/var/log/com.apple.xpc.launchd/launchd.log")
-                        .read_bytes()
-                        .unwrap(),
-                )
-                .unwrap();
-        }
-        match bootout_disable_and_kill_smart(uid, &domain, &service) {
-            Ok(_) => {
-                if !quiet {
-                    println!("{}/{} ({}) turned off", &domain, &service, pid);
-                }
-                if log {
-                    let launchd_log =
-                        Path::raw("/private/var/log/com.apple.xpc.launchd/launchd.log")
-                            .read()
-                            .unwrap();
-                    log_base_path.join("launchd.1.log").write(launchd_log.as_bytes()).unwrap();
+    let concurrency = if capture.is_some() { 1 } else { crate::execution_settings().concurrency };
+    let outcomes = crate::parallel_map(
+        &agents_and_daemons_to_turn_off,
+        concurrency,
+        |(domain, service, _)| {
+            let log_cursor = capture.and_then(|capture| {
+                LaunchdLogCursor::open(&capture.log_path)
+                    .map_err(|error| eprintln!("[warning] not capturing launchd.log: {}", error))
+                    .ok()
+            });
+            let outcome = match bootout_disable_and_kill_smart(uid, domain, service) {
+                Ok(_) => Ok("turned off"),
+                Err(error) => match disable_and_kill_smart(uid, domain, service) {
+                    Ok(_) => Ok("disabled"),
+                    Err(_) => Err(error),
+                },
+            };
+            if let (Some(capture), Some(cursor)) = (capture, log_cursor) {
+                if let Err(error) = write_launchd_log_report(
+                    &cursor,
+                    &capture.report_path(domain, service),
+                    domain,
+                    service,
+                    outcome.as_ref().err(),
+                    quiet,
+                ) {
+                    eprintln!(
+                        "[warning] writing launchd.log report of {}/{}: {}",
+                        domain, service, error
+                    );
                 }
+            }
+            outcome
+        },
+    );
+    // reported in the order of the input whatever order the workers
+    // finished in
+    let mut results = Vec::<(String, String, i64, crate::Result<()>)>::new();
+    for ((domain, service, pid), outcome) in
+        agents_and_daemons_to_turn_off.into_iter().zip(outcomes)
+    {
+        if !quiet {
+            match &outcome {
+                Ok(action) => println!("{}/{} ({}) {}", &domain, &service, pid, action),
+                Err(error) => eprintln!("{}", error),
+            }
+        }
+        results.push((domain, service, pid, outcome.map(|_| ())));
+    }
+    results
+}
 
-                // if launchd_log.contains("failed lookup: name = com.apple.modelmanager") {
-                //     eprintln!("\n------------------------------------------------------------------------------------------------");
-                //     eprintln!("launchd error detected, check {:#?}", log_base_path.to_string());
-                //     eprintln!("\n------------------------------------------------------------------------------------------------");
-                //     std::process::exit(1);
-                // }
-                // <Notice>: Last log repeated 1 times
-                // (system) <Warning>: failed lookup: name = com.apple.modelmanager, requestor = coreaudiod[418], error = 3: No such process
-                // (system) <Warning>: failed lookup: name = com.apple.BTAudioHALPlugin.xpc, requestor = coreaudiod[418], error = 3: No such process
-                // println!("[enter]");
-                // std::io::stdin().read_line(&mut String::new()).unwrap();
-
-                // match test_osx_app_opens() {
-                //     Ok(_) => {},
-                //     Err(error) => {
-
-                //         eprintln!("\n----------------------------------------------------------");
-                //         eprintln!("app does not open: {}", error);
-                //         eprintln!("----------------------------------------------------------\n");
-                //         std::process::exit(3);
-                //     },
-                // }
-            },
-            Err(error) => match disable_and_kill_smart(uid, &domain, &service) {
-                Ok(_) =>
-                    if !quiet {
-                        println!("{}/{} ({}) disabled", &domain, &service, pid);
-                    },
-                Err(_) =>
-                    if !quiet {
-                        eprintln!("{}", error)
-                    },
-            },
+fn write_launchd_log_report(
+    cursor: &LaunchdLogCursor,
+    log_base_path: &iocore::Path,
+    domain: &str,
+    service: &str,
+    error: Option<&crate::Error>,
+    quiet: bool,
+) -> crate::Result<()> {
+    let (data, mut report) = cursor.report(domain, service)?;
+    report.error = error.cloned();
+    log_base_path.join("launchd.log").write(data.as_bytes())?;
+    log_base_path.join("report.json").write(serde_json::to_string_pretty(&report)?.as_bytes())?;
+    if !quiet {
+        for finding in &report.findings {
+            eprintln!("[warning] {}/{}: {}", domain, service, finding);
+        }
+        if !report.findings.is_empty() {
+            eprintln!("[warning] check {:#?}", log_base_path.to_string());
         }
     }
+    Ok(())
 }
 
 fn launchctl_subcommand(args: &[&str], as_root: bool) -> crate::Result<i64> {
-    match launchctl_ok(args, as_root) {
-        Ok((exit_code, _, err)) => match exit_code {
-            0 | 64 | 113 => Ok(exit_code),
-            3 | 125 => Err(crate::Error::LaunchdServiceNotRunning(format!(
-                "`launchctl {}' failed with {}: {}",
-                args.join(" "),
-                exit_code,
-                err
-            ))),
-            _ => Err(crate::Error::LaunchdError(format!(
-                "`launchctl {}' failed with {}: {}",
-                args.join(" "),
-                exit_code,
-                err
-            ))),
-        },
-        Err(err) => Err(err),
+    let (exit_code, _, err) = launchctl_ok(args, as_root)?;
+    if crate::LaunchctlOutcome::classify(exit_code, &err).tolerated() {
+        Ok(exit_code)
+    } else {
+        Err(crate::Error::launchctl(args, exit_code, &err))
     }
 }
 
@@ -211,11 +567,13 @@ fn disable_and_kill_smart(uid: &Uid, domain: &str, service: &str) -> crate::Resu
     Ok(())
 }
 
+/// Bootstraps, enables and kickstarts `domain/service`, returning the
+/// `launchctl` commands it ran.
 fn enable_and_kickstart_smart(
     domain: &str,
     service: &str,
     path: &iocore::Path,
-) -> crate::Result<()> {
+) -> (Vec<String>, crate::Result<()>) {
     let as_root = true;
     let services_target = format!("{}/{}", domain, service);
     let commands = vec![
@@ -223,25 +581,34 @@ fn enable_and_kickstart_smart(
         vec!["enable".to_string(), services_target.to_string()],
         vec!["kickstart".to_string(), "-k".to_string(), services_target.to_string()],
     ];
+    let mut ran = Vec::<String>::new();
     for command in commands {
-        eprintln!("{} -", command.join(" "));
-        launchctl_subcommand(crate::to_slice_str!(command), as_root)?;
+        ran.push(command.join(" "));
+        if let Err(error) = launchctl_subcommand(crate::to_slice_str!(command), as_root) {
+            return (ran, Err(error));
+        }
     }
-    Ok(())
+    (ran, Ok(()))
 }
 
-pub fn boot_up_smart(uid: &Uid, quiet: bool, services: Vec<String>, include_non_needed: bool) {
+pub fn boot_up_smart(
+    uid: &Uid,
+    quiet: bool,
+    services: Vec<String>,
+    include_non_needed: bool,
+) -> Vec<(String, String, i64, crate::Result<()>)> {
     let mut services_set = Vec::<String>::new();
     if include_non_needed {
         services_set.extend(crate::to_vec_string!(NON_NEEDED_SERVICES));
     }
     services_set.extend(services);
+    let display_warnings = !quiet && crate::adm_settings().display_warnings();
     let services_to_boot_up = list_all_agents_and_daemons(uid)
         .unwrap()
         .iter()
         .filter(|(_, service, _, _, _, info)| {
             if info.is_none() {
-                if !quiet {
+                if display_warnings {
                     eprintln!("[warning] path not found for {:#?}", &service);
                 }
                 return false;
@@ -265,7 +632,14 @@ pub fn boot_up_smart(uid: &Uid, quiet: bool, services: Vec<String>, include_non_
         })
         .collect::<Vec<(String, String, i64, (iocore::Path, plist::Dictionary))>>();
 
-    if !services_to_boot_up.is_empty() {
+    boot_up_agents_and_daemons(quiet, services_to_boot_up)
+}
+
+pub fn boot_up_agents_and_daemons(
+    quiet: bool,
+    agents_and_daemons_to_boot_up: Vec<(String, String, i64, (iocore::Path, plist::Dictionary))>,
+) -> Vec<(String, String, i64, crate::Result<()>)> {
+    if !agents_and_daemons_to_boot_up.is_empty() {
         if !quiet {
             println!("booting-up services");
         }
@@ -274,87 +648,26 @@ pub fn boot_up_smart(uid: &Uid, quiet: bool, services: Vec<String>, include_non_
             println!("ok");
         }
     }
-    for (domain, service, pid, (path, _)) in services_to_boot_up {
-        match enable_and_kickstart_smart(&domain, &service, &path) {
-            Ok(_) =>
-                if !quiet {
-                    println!("{}/{} ({}) booted-up", &domain, &service, pid);
-                },
-            Err(error) =>
-                if !quiet {
-                    eprintln!("{}", error)
-                },
-        }
-    }
-}
-
-pub fn test_osx_app_opens() -> crate::Result<bool> {
-    let app_name = "Firefox.app";
-    let cli_name = "firefox";
-    use std::process::{Command, Stdio};
-    let mut cmd = Command::new("/usr/bin/open");
-    let cmd = cmd.current_dir(".");
-    let cmd = cmd.args(crate::to_slice_str!(vec![format!("/Applications/{}", app_name)]));
-    let cmd = cmd.stdin(Stdio::null());
-    let cmd = cmd.stdout(Stdio::piped());
-    let cmd = cmd.stderr(Stdio::piped());
-    let child = cmd.spawn()?;
-    let output = child.wait_with_output()?;
-
-    let exit_code: i64 = output.status.code().unwrap_or_default().into();
-    if exit_code == 0 {
-        let timeout = std::time::Duration::from_secs(10);
-        wait_for_subprocess_with_status(format!("g p -qr {}", cli_name).as_str(), true, &timeout)
-            .map_err(|msg| {
-            crate::Error::IOError(format!("waiting for {} to be up: {}", &app_name, msg))
-        })?;
-        std::thread::sleep(std::time::Duration::from_millis(33));
-
-        wait_for_subprocess_with_status(
-            format!("g p -qr {} -k", cli_name).as_str(),
-            true,
-            &timeout,
-        )
-        .map_err(|msg| {
-            crate::Error::IOError(format!("waiting for {} to die: {}", &app_name, msg))
-        })?;
-        std::thread::sleep(std::time::Duration::from_millis(33));
-        wait_for_subprocess_with_status(format!("g p -qr {}", cli_name).as_str(), false, &timeout)
-            .map_err(|msg| {
-                crate::Error::IOError(format!("waiting for {} NOT be up: {}", &app_name, msg))
-            })?;
-        Ok(true)
-    } else {
-        let out = String::from_utf8(output.stdout).unwrap();
-        let err = String::from_utf8(output.stderr).unwrap();
-        Err(crate::Error::IOError(format!(
-            "{} does not open({}):\n<stdout>{}</stdout>\n<stderr>{}</stderr>",
-            &app_name, exit_code, out, err
-        )))
-    }
-}
-
-pub fn wait_for_subprocess_with_status(
-    command: &str,
-    ok: bool,
-    timeout: &std::time::Duration,
-) -> Result<(), String> {
-    let now = std::time::Instant::now();
-    while !subprocess_match(command, ok) {
-        std::thread::sleep(std::time::Duration::from_millis(33));
-        if now.elapsed() > *timeout {
-            return Err(format!(
-                "timed out waiting for command {:#?} within {}s",
-                command,
-                timeout.as_secs()
-            ));
+    let concurrency = crate::execution_settings().concurrency;
+    let outcomes = crate::parallel_map(
+        &agents_and_daemons_to_boot_up,
+        concurrency,
+        |(domain, service, _, (path, _))| enable_and_kickstart_smart(domain, service, path),
+    );
+    let mut results = Vec::<(String, String, i64, crate::Result<()>)>::new();
+    for ((domain, service, pid, _), (commands, result)) in
+        agents_and_daemons_to_boot_up.into_iter().zip(outcomes)
+    {
+        if !quiet {
+            for command in &commands {
+                eprintln!("{} -", command);
+            }
+            match &result {
+                Ok(_) => println!("{}/{} ({}) booted-up", &domain, &service, pid),
+                Err(error) => eprintln!("{}", error),
+            }
         }
+        results.push((domain, service, pid, result));
     }
-    Ok(())
-}
-
-pub fn subprocess_match(command: &str, ok: bool) -> bool {
-    iocore::shell_command(command, ".")
-        .map(|status| if ok { status == 0 } else { status != 0 })
-        .unwrap_or_else(|_| !ok)
+    results
 }
@@ -1,7 +1,11 @@
+#[derive(PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
 pub struct Uid(pub u64);
-impl Default for Uid {
-    fn default() -> Uid {
-        Uid(501)
+impl Uid {
+    /// Uid of the user logged in at the console according to the
+    /// [`UserDirectory`](crate::UserDirectory) of this system.
+    pub fn console() -> crate::Result<Uid> {
+        crate::UserDirectory::detect()?.resolve(None, None)
     }
 }
 impl Into<u64> for Uid {
@@ -14,14 +18,6 @@ impl From<u64> for Uid {
         Uid(u)
     }
 }
-impl From<Option<u64>> for Uid {
-    fn from(u: Option<u64>) -> Uid {
-        match u {
-            Some(u) => Uid(u),
-            None => Uid::default(),
-        }
-    }
-}
 impl From<i32> for Uid {
     fn from(u: i32) -> Uid {
         Uid(u as u64)
@@ -129,18 +125,35 @@ pub fn system_uids() -> Vec<Option<Uid>> {
         .map(|uid| Some(uid.clone()))
         .collect::<Vec<Option<Uid>>>()
 }
+/// Uids of the system accounts that launchd has a domain for, only
+/// root when the users or the domains cannot be read.
 pub fn salient_system_uids() -> Vec<Uid> {
-    vec![
-        Uid(0),   // root
-        Uid(55),  // _appleevents - Apple Events
-        Uid(88),  // _windowserver - WindowServer
-        Uid(89),  // _spotlight - Spotlight
-        Uid(200), // _softwareupdate - Software Update Service
-        Uid(202), // _coreaudiod - Core Audio Daemon
-        Uid(205), // _locationd - Location Daemon
-        Uid(242), // _nsurlsessiond - NSURLSession Daemon
-        Uid(262), // _cmiodalassistants - CoreMedia IO Assistants User
-    ]
+    match (
+        crate::UserDirectory::detect(),
+        super::launchctl::launchctl_print("system", false),
+    ) {
+        (Ok(directory), Ok(system)) => launchd_system_uids(&directory, &system),
+        _ => vec![Uid(0)],
+    }
+}
+/// [`salient_system_uids`] when the `include_system_uids` setting is
+/// on, none otherwise.
+pub fn included_system_uids() -> Vec<Uid> {
+    if crate::adm_settings().include_system_uids() {
+        salient_system_uids()
+    } else {
+        Vec::new()
+    }
+}
+/// Uids of the system accounts of `directory` among the user domains
+/// of `system`, the output of `launchctl print system`.
+pub fn launchd_system_uids(directory: &crate::UserDirectory, system: &str) -> Vec<Uid> {
+    let domains = crate::parse_user_subdomains(system);
+    directory
+        .system_uids()
+        .into_iter()
+        .filter(|uid| domains.contains(uid))
+        .collect()
 }
 
 pub fn agents_and_daemons_path_map(
@@ -158,7 +171,8 @@ pub fn agents_and_daemons_path_map(
     Ok(map)
 }
 
-#[cfg(test)]
+// reads the launchd plists and preferences of the running system
+#[cfg(all(test, feature = "macos", target_os = "macos"))]
 mod tests {
     use crate::{agents_and_daemons_path_map, Result};
     #[test]
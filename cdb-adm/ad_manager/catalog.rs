@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+pub const NON_NEEDED_CATALOG: &str = include_str!("../agents-and-daemons.noon");
+pub const BOOTOUT_CATALOG: &str = include_str!("../bootout.noon");
+
+/// Maps each label listed in a `.noon` file to the `// <section>`
+/// tags enclosing it, e.g.: `bluetooth`, `accessibility` or `ai`.
+/// Commented-out labels are not part of the catalog.
+pub fn parse_catalog(data: &str) -> BTreeMap<String, Vec<String>> {
+    let mut catalog = BTreeMap::<String, Vec<String>>::new();
+    let mut sections = Vec::<String>::new();
+    for line in data.lines() {
+        let line = line.trim();
+        if line.starts_with("//") {
+            let tag = line.trim_start_matches(|c: char| c == '/' || c.is_whitespace());
+            if let Some(tag) = tag.strip_prefix("</").and_then(|tag| tag.strip_suffix('>')) {
+                let tag = category_name(tag);
+                if let Some(index) = sections.iter().rposition(|section| *section == tag) {
+                    sections.remove(index);
+                }
+            } else if let Some(tag) = tag.strip_prefix('<').and_then(|tag| tag.strip_suffix('>')) {
+                sections.push(category_name(tag));
+            }
+            continue;
+        }
+        let label = match line.split("//").next().map(|h| h.trim().trim_end_matches(',').trim()) {
+            Some(label) if label.len() > 2 && label.starts_with('"') && label.ends_with('"') =>
+                label.trim_matches('"').to_string(),
+            _ => continue,
+        };
+        let categories = catalog.entry(label).or_default();
+        for section in &sections {
+            if !categories.contains(section) {
+                categories.push(section.to_string());
+            }
+        }
+    }
+    catalog
+}
+
+/// Normalizes a section tag into a category name: `A.I.` becomes
+/// `ai`, `social&games` becomes `social-games`.
+pub fn category_name(tag: &str) -> String {
+    tag.to_lowercase()
+        .replace('.', "")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<&str>>()
+        .join("-")
+}
+
+/// Categories of the services listed in `agents-and-daemons.noon`
+/// and `bootout.noon`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServiceCatalog {
+    non_needed: BTreeMap<String, Vec<String>>,
+    bootout: BTreeMap<String, Vec<String>>,
+}
+impl ServiceCatalog {
+    pub fn builtin() -> ServiceCatalog {
+        ServiceCatalog::new(NON_NEEDED_CATALOG, BOOTOUT_CATALOG)
+    }
+
+    pub fn new(non_needed: &str, bootout: &str) -> ServiceCatalog {
+        ServiceCatalog { non_needed: parse_catalog(non_needed), bootout: parse_catalog(bootout) }
+    }
+
+    /// Categories of a service: `apple` or `third-party` by label,
+    /// `non-needed` and `bootout` when listed in the respective
+    /// catalog plus the sections it appears in.
+    pub fn categories(&self, service: &str) -> Vec<String> {
+        let mut categories = vec![
+            if service.starts_with("com.apple.") { "apple" } else { "third-party" }.to_string(),
+        ];
+        for (category, catalog) in [("non-needed", &self.non_needed), ("bootout", &self.bootout)] {
+            if let Some(sections) = catalog.get(service) {
+                categories.push(category.to_string());
+                for section in sections {
+                    if !categories.contains(section) {
+                        categories.push(section.to_string());
+                    }
+                }
+            }
+        }
+        categories
+    }
+
+    pub fn known_categories(&self) -> Vec<String> {
+        let mut categories =
+            crate::to_vec_string!(["apple", "third-party", "non-needed", "bootout"]);
+        for sections in self.non_needed.values().chain(self.bootout.values()) {
+            for section in sections {
+                if !categories.contains(section) {
+                    categories.push(section.to_string());
+                }
+            }
+        }
+        categories
+    }
+}
@@ -0,0 +1,87 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{LaunchdEndpoint, LaunchdLogFinding};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImpactHint {
+    pub domain: String,
+    pub service: String,
+    pub endpoint: String,
+    pub active: bool,
+    pub consumers: Vec<String>,
+}
+impl std::fmt::Display for ImpactHint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.consumers.is_empty() {
+            write!(
+                f,
+                "service {}/{} provides endpoint {} ({})",
+                self.domain,
+                self.service,
+                self.endpoint,
+                if self.active { "active" } else { "on demand" }
+            )
+        } else {
+            write!(
+                f,
+                "service {}/{} provides endpoint {} used by {}",
+                self.domain,
+                self.service,
+                self.endpoint,
+                self.consumers.join(", ")
+            )
+        }
+    }
+}
+
+pub fn mach_services_from_plist(dictionary: &plist::Dictionary) -> Vec<String> {
+    match dictionary.get("MachServices") {
+        Some(plist::Value::Dictionary(services)) =>
+            services.keys().map(|name| name.to_string()).collect::<Vec<String>>(),
+        _ => Vec::new(),
+    }
+}
+
+/// Cross-references the `MachServices` of each target with the
+/// endpoints registered in its domain and with the requestors of
+/// failed lookups found in launchd.log.
+pub fn analyse_impact(
+    targets: &[(String, String, Option<plist::Dictionary>)],
+    endpoints: &BTreeMap<String, Vec<LaunchdEndpoint>>,
+    findings: &[LaunchdLogFinding],
+) -> Vec<ImpactHint> {
+    let mut consumers = BTreeMap::<String, BTreeSet<String>>::new();
+    for finding in findings {
+        if let LaunchdLogFinding::FailedLookup { name, requestor, .. } = finding {
+            consumers.entry(name.to_string()).or_default().insert(requestor.to_string());
+        }
+    }
+    let mut hints = Vec::<ImpactHint>::new();
+    for (domain, service, dictionary) in targets {
+        let mach_services = match dictionary {
+            Some(dictionary) => mach_services_from_plist(dictionary),
+            None => continue,
+        };
+        let domain_endpoints = endpoints.get(domain).cloned().unwrap_or_default();
+        for name in mach_services {
+            let endpoint = domain_endpoints.iter().find(|endpoint| endpoint.name == name);
+            let endpoint_consumers = consumers
+                .get(&name)
+                .map(|consumers| consumers.iter().map(|h| h.to_string()).collect::<Vec<String>>())
+                .unwrap_or_default();
+            if endpoint.is_none() && endpoint_consumers.is_empty() {
+                continue;
+            }
+            hints.push(ImpactHint {
+                domain: domain.to_string(),
+                service: service.to_string(),
+                endpoint: name,
+                active: endpoint.map(|endpoint| endpoint.active).unwrap_or_default(),
+                consumers: endpoint_consumers,
+            });
+        }
+    }
+    hints
+}
@@ -1,6 +1,9 @@
-use std::process::{Command, Stdio};
+use crate::{
+    parse_launchctl_blame, parse_launchctl_list, parse_services, to_slice_str, Error,
+    LaunchctlBlame, LaunchctlListEntry, Result, Uid,
+};
 
-use crate::{parse_services, to_slice_str, Error, Result, Uid};
+pub const LAUNCHCTL_PATH: &str = "/bin/launchctl";
 
 pub fn turn_off_agent_or_daemon(
     ad: impl std::fmt::Display,
@@ -13,17 +16,17 @@ pub fn turn_off_agent_or_daemon(
     // std::io::stdin().read_line(&mut line).unwrap();
     match bootout_agent_or_daemon(&ad, uid.clone(), gui) {
         Ok(_) => {},
-        Err(Error::LaunchdServiceNotRunning(e)) =>
+        Err(error) if error.launchctl_outcome().is_some_and(|outcome| outcome.not_running()) =>
             if !silent_warnings {
-                eprintln!("bootout {}[warning] {}", &ad, e);
+                eprintln!("bootout {}[warning] {}", &ad, error);
             },
         Err(e) => return Err(e),
     };
     match disable_agent_or_daemon(&ad, uid, gui) {
         Ok(_) => {},
-        Err(Error::LaunchdServiceNotRunning(e)) =>
+        Err(error) if error.launchctl_outcome().is_some_and(|outcome| outcome.not_running()) =>
             if !silent_warnings {
-                eprintln!("disable {}[warning] {}", &ad, e);
+                eprintln!("disable {}[warning] {}", &ad, error);
             },
         Err(e) => return Err(e),
     };
@@ -40,6 +43,20 @@ pub fn agent_or_daemon(ad: impl std::fmt::Display, uid: Option<Uid>, gui: bool)
     format!("{}/{}", agent_or_daemon_prefix(uid, gui), ad)
 }
 
+/// Splits a service target such as `system/com.apple.x` or
+/// `gui/501/com.apple.x` into its domain and label.
+pub fn split_service_target(target: &str) -> Result<(String, String)> {
+    let parts = target.splitn(3, '/').collect::<Vec<&str>>();
+    match parts.as_slice() {
+        ["system", service] if !service.is_empty() =>
+            Ok(("system".to_string(), service.to_string())),
+        [kind @ ("gui" | "user"), uid, service]
+            if !service.is_empty() && uid.parse::<u64>().is_ok() =>
+            Ok((format!("{}/{}", kind, uid), service.to_string())),
+        _ => Err(Error::ParseError(format!("invalid service target {:#?}", target))),
+    }
+}
+
 pub fn launchctl_act(
     subcommand: impl std::fmt::Display,
     ad: impl std::fmt::Display,
@@ -66,30 +83,12 @@ pub fn launchctl_act(
         err.trim()
     );
 
-    match exit_code {
-        3 | 125 => Err(Error::LaunchdServiceNotRunning(agent_or_daemon(&ad, uid, gui).to_string())),
-        exit_code => Err(Error::LaunchdError(format!(
-            "`launchctl {}' failed with exit code {:#?}: {}",
-            args.join(" "),
-            exit_code,
-            err
-        ))),
-    }
+    Err(Error::launchctl(to_slice_str!(args), exit_code, &err))
 }
 pub fn launchctl(args: &[&str], as_root: bool) -> Result<(i64, String, String)> {
     match launchctl_ok(args, as_root)? {
         (0, out, err) => Ok((0, out, err)),
-        (3 | 125, _, err) => Err(Error::LaunchdServiceNotRunning(format!(
-            "`launchctl {}' failed: {}",
-            args.join(" "),
-            err
-        ))),
-        (exit_code, _, err) => Err(Error::LaunchdError(format!(
-            "`launchctl {}' failed with exit code {:#?}: {}",
-            args.join(" "),
-            exit_code,
-            err
-        ))),
+        (exit_code, _, err) => Err(Error::launchctl(args, exit_code, &err)),
     }
 }
 
@@ -108,37 +107,21 @@ pub fn disable_agent_or_daemon(
     Ok(launchctl_act("disable", ad, uid, gui)?)
 }
 pub fn launchctl_ok(args: &[&str], as_root: bool) -> Result<(i64, String, String)> {
-    let username = if as_root {
-        "root".to_string()
-    } else {
+    if let Some(broker) = crate::broker_client().filter(|_| as_root) {
+        let command = crate::PrivilegedCommand::Launchctl { args: crate::to_vec_string!(args) };
+        return crate::retry(|_| broker.request(&command));
+    }
+    let mut argv = Vec::<String>::new();
+    if !as_root {
         let user = iocore::User::id()?;
         if user.uid == 0 {
             return Err(Error::IOError(format!("cannot run launchctl as non-root as root")));
-        } else {
-            user.name.to_string()
         }
-    };
-    let args = vec![
-        "su".to_string(),
-        "-l".to_string(),
-        username,
-        "-c".to_string(),
-        format!("launchctl {}", args.join(" ")),
-    ];
-    let mut cmd = Command::new("sudo");
-    let cmd = cmd.current_dir("/System");
-    let cmd = cmd.args(to_slice_str!(args));
-    let cmd = cmd.stdin(Stdio::null());
-    let cmd = cmd.stdout(Stdio::piped());
-    let cmd = cmd.stderr(Stdio::piped());
-    let child = cmd.spawn()?;
-    let output = child.wait_with_output()?;
-    let exit_code: i64 = output.status.code().unwrap_or_default().into();
-    Ok((
-        exit_code,
-        String::from_utf8(output.stdout).unwrap_or_default(),
-        String::from_utf8(output.stderr).unwrap_or_default(),
-    ))
+        argv.extend(["-u".to_string(), user.name.to_string()]);
+    }
+    argv.push(LAUNCHCTL_PATH.to_string());
+    argv.extend(crate::to_vec_string!(args));
+    crate::run_sudo_with_retries(to_slice_str!(argv))
 }
 pub fn launchctl_print(domain: &str, disabled: bool) -> Result<String> {
     let args = vec![
@@ -149,19 +132,20 @@ pub fn launchctl_print(domain: &str, disabled: bool) -> Result<String> {
     if exit_code == 0 {
         return Ok(out);
     }
-    match exit_code {
-        exit_code => Err(Error::LaunchdError(format!(
-            "`launchctl {}' failed with exit code {:#?}: {}\n{}",
-            args.join(" "),
-            exit_code,
-            err,
-            out
-        ))),
-    }
+    let stderr = if err.trim().is_empty() { out } else { err };
+    Err(Error::launchctl(to_slice_str!(args), exit_code, &stderr))
+}
+pub fn launchctl_list() -> Result<Vec<LaunchctlListEntry>> {
+    let (_, out, _) = launchctl(&["list"], false)?;
+    parse_launchctl_list(&out)
+}
+pub fn launchctl_blame(service_target: &str) -> Result<LaunchctlBlame> {
+    let (_, out, _) = launchctl(&["blame", service_target], service_target.starts_with("system/"))?;
+    parse_launchctl_blame(&out)
 }
 pub fn list_active_agents_and_daemons(
     uid: &Uid,
-    include_system_uids: bool,
+    system_uids: &[Uid],
 ) -> crate::Result<Vec<(String, String, i64, Option<i64>, Option<(iocore::Path, plist::Dictionary)>)>>
 {
     let map = crate::agents_and_daemons_path_map(true, true, true)?;
@@ -172,11 +156,9 @@ pub fn list_active_agents_and_daemons(
         agent_or_daemon_prefix(Some(uid.clone()), false),
         agent_or_daemon_prefix(Some(uid.clone()), true),
     ];
-    if include_system_uids {
-        for suid in crate::salient_system_uids() {
-            domains.push(agent_or_daemon_prefix(Some(suid.clone()), false));
-            domains.push(agent_or_daemon_prefix(Some(suid.clone()), true));
-        }
+    for suid in system_uids {
+        domains.push(agent_or_daemon_prefix(Some(*suid), false));
+        domains.push(agent_or_daemon_prefix(Some(*suid), true));
     }
     for domain in domains {
         for (pid, status, service, _enabled) in parse_services(
@@ -194,7 +176,7 @@ pub fn list_active_agents_and_daemons(
 }
 pub fn list_disabled_agents_and_daemons(
     uid: &Uid,
-    include_system_uids: bool,
+    system_uids: &[Uid],
 ) -> crate::Result<
     Vec<(
         String,
@@ -212,11 +194,9 @@ pub fn list_disabled_agents_and_daemons(
         agent_or_daemon_prefix(Some(uid.clone()), false),
         agent_or_daemon_prefix(Some(uid.clone()), true),
     ];
-    if include_system_uids {
-        for suid in crate::salient_system_uids() {
-            domains.push(agent_or_daemon_prefix(Some(suid.clone()), false));
-            domains.push(agent_or_daemon_prefix(Some(suid.clone()), true));
-        }
+    for suid in system_uids {
+        domains.push(agent_or_daemon_prefix(Some(*suid), false));
+        domains.push(agent_or_daemon_prefix(Some(*suid), true));
     }
     for domain in domains {
         for (pid, status, service, enabled) in parse_services(
@@ -256,7 +236,8 @@ pub fn list_all_agents_and_daemons(
             Option<(iocore::Path, plist::Dictionary)>,
         ),
     >::new();
-    for (domain, service, pid, status, info) in list_active_agents_and_daemons(uid, true).expect("active agents and daemons") {
+    let system_uids = crate::included_system_uids();
+    for (domain, service, pid, status, info) in list_active_agents_and_daemons(uid, &system_uids).expect("active agents and daemons") {
         result.insert(
             format!("{}/{}", &domain, &service),
             (
@@ -270,7 +251,7 @@ pub fn list_all_agents_and_daemons(
         );
     }
     for (domain, service, pid, status, enabled, info) in
-        list_disabled_agents_and_daemons(uid, true)?
+        list_disabled_agents_and_daemons(uid, &system_uids)?
     {
         result.insert(
             format!("{}/{}", &domain, &service),
@@ -295,7 +276,7 @@ pub fn list_all_agents_and_daemons(
 }
 #[cfg(test)]
 mod tests {
-    use crate::{agent_or_daemon, agent_or_daemon_prefix, Uid};
+    use crate::{agent_or_daemon, agent_or_daemon_prefix, split_service_target, Uid};
 
     #[test]
     fn test_agent_or_daemon_prefix() {
@@ -318,4 +299,18 @@ mod tests {
             "gui/202/com.apple.calaccessd"
         );
     }
+    #[test]
+    fn test_split_service_target() {
+        assert_eq!(
+            split_service_target("system/com.apple.calaccessd"),
+            Ok(("system".to_string(), "com.apple.calaccessd".to_string()))
+        );
+        assert_eq!(
+            split_service_target("gui/501/com.apple.Siri.agent"),
+            Ok(("gui/501".to_string(), "com.apple.Siri.agent".to_string()))
+        );
+        assert!(split_service_target("com.apple.calaccessd").is_err());
+        assert!(split_service_target("gui/me/com.apple.Siri.agent").is_err());
+        assert!(split_service_target("system/").is_err());
+    }
 }
@@ -0,0 +1,240 @@
+use std::collections::BTreeMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+pub const LAUNCHD_LOG_PATH: &str = "/private/var/log/com.apple.xpc.launchd/launchd.log";
+pub const CRASH_LOOP_THRESHOLD: usize = 3;
+static REQUESTOR_REGEX: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new("requestor = (?<requestor>[^,\\[]+)(?:\\[(?<pid>\\d+)\\])?").unwrap()
+});
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchdLogEntry {
+    pub timestamp: String,
+    pub domain: Option<String>,
+    pub service: Option<String>,
+    pub pid: Option<i64>,
+    pub level: String,
+    pub message: String,
+    pub requestor_pid: Option<i64>,
+}
+impl LaunchdLogEntry {
+    pub fn target(&self) -> Option<String> {
+        match (&self.domain, &self.service) {
+            (Some(domain), Some(service)) => Some(format!("{}/{}", domain, service)),
+            (Some(domain), None) => Some(domain.to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LaunchdLogFinding {
+    FailedLookup {
+        name: String,
+        requestor: String,
+        requestor_pid: Option<i64>,
+        error: String,
+    },
+    CrashLoop {
+        target: String,
+        exits: usize,
+    },
+}
+impl std::fmt::Display for LaunchdLogFinding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LaunchdLogFinding::FailedLookup { name, requestor, requestor_pid, error } => write!(
+                f,
+                "failed lookup of {} by {}[{}]: {}",
+                name,
+                requestor,
+                requestor_pid.map(|pid| pid.to_string()).unwrap_or_else(|| "-".to_string()),
+                error
+            ),
+            LaunchdLogFinding::CrashLoop { target, exits } =>
+                write!(f, "crash loop of {} ({} exits)", target, exits),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchdLogReport {
+    pub domain: String,
+    pub service: String,
+    pub entries: Vec<LaunchdLogEntry>,
+    pub findings: Vec<LaunchdLogFinding>,
+    pub error: Option<Error>,
+}
+
+pub fn parse_launchd_log(data: &str) -> Vec<LaunchdLogEntry> {
+    let entry_regex = regex::Regex::new(
+        "^(?<timestamp>\\d{4}-\\d{2}-\\d{2} \\d{2}:\\d{2}:\\d{2}(?:[.]\\d+)?(?: [+-]\\d{4})?)\\s+(?:[(](?<subject>[^)]*)[)]\\s+)?<(?<level>\\w+)>:\\s?(?<message>.*)$",
+    )
+    .unwrap();
+    let mut entries = Vec::<LaunchdLogEntry>::new();
+    for line in data.lines() {
+        let caps = match entry_regex.captures(line) {
+            Some(caps) => caps,
+            None => {
+                if let Some(entry) = entries.last_mut().filter(|_| !line.trim().is_empty()) {
+                    entry.message.push('\n');
+                    entry.message.push_str(line);
+                }
+                continue;
+            },
+        };
+        let (domain, service, pid) =
+            parse_launchd_log_subject(caps.name("subject").map(|h| h.as_str()).unwrap_or_default());
+        let message = caps.name("message").expect("message").as_str().to_string();
+        let requestor_pid = extract_requestor(&message).and_then(|(_, pid)| pid);
+        entries.push(LaunchdLogEntry {
+            timestamp: caps.name("timestamp").expect("timestamp").as_str().to_string(),
+            domain,
+            service,
+            pid,
+            level: caps.name("level").expect("level").as_str().to_string(),
+            message,
+            requestor_pid,
+        });
+    }
+    entries
+}
+
+fn parse_launchd_log_subject(subject: &str) -> (Option<String>, Option<String>, Option<i64>) {
+    let subject = subject.trim();
+    if subject.is_empty() {
+        return (None, None, None);
+    }
+    let (target, pid) = match subject.rsplit_once(" [") {
+        Some((target, pid)) => (target.trim(), pid.trim_end_matches(']').parse::<i64>().ok()),
+        None => (subject, None),
+    };
+    let parts = target.splitn(3, '/').collect::<Vec<&str>>();
+    let (domain, service) = match parts.as_slice() {
+        [domain] => (domain.to_string(), None),
+        [kind, id] if id.parse::<u64>().is_ok() => (format!("{}/{}", kind, id), None),
+        [kind, id, service] if id.parse::<u64>().is_ok() =>
+            (format!("{}/{}", kind, id), Some(service.to_string())),
+        [domain, service] => (domain.to_string(), Some(service.to_string())),
+        [domain, first, rest] => (domain.to_string(), Some(format!("{}/{}", first, rest))),
+        _ => (target.to_string(), None),
+    };
+    (Some(domain), service, pid)
+}
+
+fn extract_requestor(message: &str) -> Option<(String, Option<i64>)> {
+    let caps = REQUESTOR_REGEX.captures(message)?;
+    let requestor = caps.name("requestor")?.as_str().trim().to_string();
+    let pid = caps.name("pid").and_then(|pid| pid.as_str().parse::<i64>().ok());
+    Some((requestor, pid))
+}
+
+pub fn launchd_log_findings(entries: &[LaunchdLogEntry]) -> Vec<LaunchdLogFinding> {
+    let lookup_regex = regex::Regex::new(
+        "failed lookup: name = (?<name>[^,]+), requestor = [^,]+, error = (?<error>.+)$",
+    )
+    .unwrap();
+    let mut findings = Vec::<LaunchdLogFinding>::new();
+    let mut exits = BTreeMap::<String, usize>::new();
+    for entry in entries {
+        if let Some(caps) = lookup_regex.captures(&entry.message) {
+            let (requestor, requestor_pid) =
+                extract_requestor(&entry.message).unwrap_or_else(|| ("-".to_string(), None));
+            let finding = LaunchdLogFinding::FailedLookup {
+                name: caps.name("name").expect("name").as_str().trim().to_string(),
+                requestor,
+                requestor_pid,
+                error: caps.name("error").expect("error").as_str().trim().to_string(),
+            };
+            if !findings.contains(&finding) {
+                findings.push(finding);
+            }
+        } else if let Some(target) = entry.target().filter(|_| is_abnormal_exit(&entry.message)) {
+            *exits.entry(target).or_default() += 1;
+        }
+    }
+    for (target, exits) in exits {
+        if exits >= CRASH_LOOP_THRESHOLD {
+            findings.push(LaunchdLogFinding::CrashLoop { target, exits });
+        }
+    }
+    findings
+}
+
+fn is_abnormal_exit(message: &str) -> bool {
+    message.contains("exited due to")
+        || message.contains("Service only ran for")
+        || (message.contains("exited with exit code") && !message.contains("exit code: 0"))
+}
+
+/// Where the launchd.log lines of each turned off service are read
+/// from and where its report is written, `logs/<service>/<domain>` of
+/// the working directory by default.
+#[derive(Debug, Clone)]
+pub struct LaunchdLogCapture {
+    pub log_path: iocore::Path,
+    pub reports_path: iocore::Path,
+}
+impl Default for LaunchdLogCapture {
+    fn default() -> LaunchdLogCapture {
+        LaunchdLogCapture {
+            log_path: iocore::Path::raw(LAUNCHD_LOG_PATH),
+            reports_path: iocore::Path::cwd().join("logs"),
+        }
+    }
+}
+impl LaunchdLogCapture {
+    pub fn report_path(&self, domain: &str, service: &str) -> iocore::Path {
+        self.reports_path.join(service).join(domain.replace("/", "-"))
+    }
+}
+
+/// Remembers the size of a launchd.log so that only the lines
+/// appended after [`LaunchdLogCursor::open`] are read back.
+#[derive(Debug, Clone)]
+pub struct LaunchdLogCursor {
+    path: iocore::Path,
+    offset: u64,
+}
+impl LaunchdLogCursor {
+    pub fn open(path: &iocore::Path) -> Result<LaunchdLogCursor> {
+        let offset = std::fs::metadata(path.to_path_buf())
+            .map_err(|error| {
+                Error::IOError(format!("reading metadata of {:#?}: {}", path.to_string(), error))
+            })?
+            .len();
+        Ok(LaunchdLogCursor { path: path.clone(), offset })
+    }
+
+    pub fn read_appended(&self) -> Result<String> {
+        let mut file = std::fs::File::open(self.path.to_path_buf())?;
+        let len = file.metadata()?.len();
+        // the log was rotated in the meantime, everything in it is new
+        let offset = if len < self.offset { 0 } else { self.offset };
+        file.seek(SeekFrom::Start(offset))?;
+        let mut bytes = Vec::<u8>::new();
+        file.read_to_end(&mut bytes)?;
+        Ok(String::from_utf8_lossy(&bytes).to_string())
+    }
+
+    pub fn report(&self, domain: &str, service: &str) -> Result<(String, LaunchdLogReport)> {
+        let data = self.read_appended()?;
+        let entries = parse_launchd_log(&data);
+        let findings = launchd_log_findings(&entries);
+        Ok((
+            data,
+            LaunchdLogReport {
+                domain: domain.to_string(),
+                service: service.to_string(),
+                entries,
+                findings,
+                error: None,
+            },
+        ))
+    }
+}
@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+
+/// Meaning of a `launchctl` exit, derived from its exit code and, as
+/// the codes are reused across subcommands, from its stderr first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaunchctlOutcome {
+    Success,
+    NotPermitted,
+    NoSuchProcess,
+    IOError,
+    AlreadyInProgress,
+    AlreadyLoaded,
+    Usage,
+    ServiceNotFound,
+    ServiceDisabled,
+    UnsupportedAction,
+    SipProtected,
+    Other(i64),
+}
+impl LaunchctlOutcome {
+    pub fn classify(exit_code: i64, stderr: &str) -> LaunchctlOutcome {
+        if exit_code == 0 {
+            return LaunchctlOutcome::Success;
+        }
+        let stderr = stderr.to_lowercase();
+        if stderr.contains("system integrity protection") {
+            LaunchctlOutcome::SipProtected
+        } else if stderr.contains("already loaded") || stderr.contains("already bootstrapped") {
+            LaunchctlOutcome::AlreadyLoaded
+        } else if stderr.contains("operation not permitted") {
+            LaunchctlOutcome::NotPermitted
+        } else if stderr.contains("could not find") {
+            LaunchctlOutcome::ServiceNotFound
+        } else if stderr.contains("no such process") {
+            LaunchctlOutcome::NoSuchProcess
+        } else if stderr.contains("service is disabled") {
+            LaunchctlOutcome::ServiceDisabled
+        } else if stderr.contains("does not support specified action") {
+            LaunchctlOutcome::UnsupportedAction
+        } else if stderr.contains("operation already in progress") {
+            LaunchctlOutcome::AlreadyInProgress
+        } else if stderr.contains("input/output error") {
+            LaunchctlOutcome::IOError
+        } else {
+            LaunchctlOutcome::from_exit_code(exit_code)
+        }
+    }
+
+    pub fn from_exit_code(exit_code: i64) -> LaunchctlOutcome {
+        match exit_code {
+            0 => LaunchctlOutcome::Success,
+            1 => LaunchctlOutcome::NotPermitted,
+            3 => LaunchctlOutcome::NoSuchProcess,
+            5 => LaunchctlOutcome::IOError,
+            37 => LaunchctlOutcome::AlreadyInProgress,
+            64 => LaunchctlOutcome::Usage,
+            113 => LaunchctlOutcome::ServiceNotFound,
+            119 => LaunchctlOutcome::ServiceDisabled,
+            125 => LaunchctlOutcome::UnsupportedAction,
+            150 => LaunchctlOutcome::SipProtected,
+            exit_code => LaunchctlOutcome::Other(exit_code),
+        }
+    }
+
+    /// Whether the service was not running, which turning it off
+    /// treats as already done.
+    pub fn not_running(&self) -> bool {
+        matches!(self, LaunchctlOutcome::NoSuchProcess | LaunchctlOutcome::UnsupportedAction)
+    }
+
+    /// Whether a `bootstrap`, `enable`, `kickstart`, `bootout`,
+    /// `disable` or `kill` left the service as requested.
+    pub fn tolerated(&self) -> bool {
+        matches!(
+            self,
+            LaunchctlOutcome::Success
+                | LaunchctlOutcome::Usage
+                | LaunchctlOutcome::ServiceNotFound
+                | LaunchctlOutcome::AlreadyLoaded
+        )
+    }
+}
+impl std::fmt::Display for LaunchctlOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            LaunchctlOutcome::Success => write!(f, "success"),
+            LaunchctlOutcome::NotPermitted => write!(f, "operation not permitted"),
+            LaunchctlOutcome::NoSuchProcess => write!(f, "no such process"),
+            LaunchctlOutcome::IOError => write!(f, "input/output error"),
+            LaunchctlOutcome::AlreadyInProgress => write!(f, "operation already in progress"),
+            LaunchctlOutcome::AlreadyLoaded => write!(f, "service already loaded"),
+            LaunchctlOutcome::Usage => write!(f, "usage error"),
+            LaunchctlOutcome::ServiceNotFound => write!(f, "could not find specified service"),
+            LaunchctlOutcome::ServiceDisabled => write!(f, "service is disabled"),
+            LaunchctlOutcome::UnsupportedAction =>
+                write!(f, "domain does not support specified action"),
+            LaunchctlOutcome::SipProtected =>
+                write!(f, "operation not permitted while System Integrity Protection is engaged"),
+            LaunchctlOutcome::Other(exit_code) => write!(f, "exit code {}", exit_code),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LaunchctlOutcome;
+
+    #[test]
+    fn test_classify() {
+        for (exit_code, stderr, outcome) in [
+            (0, "", LaunchctlOutcome::Success),
+            (3, "", LaunchctlOutcome::NoSuchProcess),
+            (113, "", LaunchctlOutcome::ServiceNotFound),
+            (125, "", LaunchctlOutcome::UnsupportedAction),
+            (42, "", LaunchctlOutcome::Other(42)),
+            (5, "Bootstrap failed: 5: Input/output error", LaunchctlOutcome::IOError),
+            (
+                5,
+                "Load failed: 5: Input/output error\nservice already loaded",
+                LaunchctlOutcome::AlreadyLoaded,
+            ),
+            (
+                150,
+                "Boot-out failed: 150: Operation not permitted while System Integrity \
+                 Protection is engaged",
+                LaunchctlOutcome::SipProtected,
+            ),
+            (1, "Warning: Operation not permitted", LaunchctlOutcome::NotPermitted),
+            (
+                113,
+                "Could not find service \"com.example.x\" in domain for port",
+                LaunchctlOutcome::ServiceNotFound,
+            ),
+        ] {
+            assert_eq!(LaunchctlOutcome::classify(exit_code, stderr), outcome, "{:#?}", stderr);
+        }
+        assert!(LaunchctlOutcome::NoSuchProcess.not_running());
+        assert!(!LaunchctlOutcome::SipProtected.tolerated());
+    }
+}
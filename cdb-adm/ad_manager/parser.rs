@@ -1,4 +1,6 @@
-use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Uid};
 
 pub fn parse_services(data: &str, disabled: bool) -> Result<Vec<(i64, Option<i64>, String, bool)>> {
     let mut services = Vec::new();
@@ -40,6 +42,21 @@ pub fn parse_services(data: &str, disabled: bool) -> Result<Vec<(i64, Option<i64
     Ok(services)
 }
 
+/// Uids of the `user/<uid>` subdomains in the output of `launchctl
+/// print system`, the users that launchd has a domain for.
+pub fn parse_user_subdomains(data: &str) -> Vec<Uid> {
+    let mut uids = data
+        .lines()
+        .skip_while(|line| line.trim() != "subdomains = {")
+        .skip(1)
+        .take_while(|line| line.trim() != "}")
+        .filter_map(|line| line.trim().strip_prefix("user/")?.parse::<Uid>().ok())
+        .collect::<Vec<Uid>>();
+    uids.sort();
+    uids.dedup();
+    uids
+}
+
 pub fn extract_service_name(line: &str) -> Result<String> {
     let (_, _, service) = extract_service_info_opt(line)
         .map_err(|error| Error::ParseError(format!("service name not found in {:#?}: {}", line, error.to_string())))?;
@@ -74,3 +91,135 @@ pub fn extract_disabled_service_info(line: &str) -> Option<(String, bool)> {
     let enabled = caps.name("enabled").map(|h|h.as_str().to_string())?.trim() == "enabled";
     Some((service, enabled))
 }
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchctlListEntry {
+    pub pid: Option<i64>,
+    pub status: i64,
+    pub label: String,
+}
+
+pub fn parse_launchctl_list(data: &str) -> Result<Vec<LaunchctlListEntry>> {
+    let entry_regex =
+        regex::Regex::new("^(?<pid>-|\\d+)\\t(?<status>-?\\d+)\\t(?<label>.+)$").unwrap();
+    let mut entries = Vec::new();
+    for line in data.lines() {
+        if line.trim().is_empty() || line.starts_with("PID\t") {
+            continue;
+        }
+        let caps = entry_regex.captures(line).ok_or_else(|| {
+            Error::ParseError(format!("launchctl list entry not found in ```{}```", line))
+        })?;
+        let pid = caps.name("pid").expect("pid").as_str().parse::<i64>().ok();
+        let status = caps.name("status").expect("status").as_str().parse::<i64>()?;
+        let label = caps.name("label").expect("label").as_str().trim().to_string();
+        entries.push(LaunchctlListEntry { pid, status, label });
+    }
+    Ok(entries)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BlameReason {
+    Speculative,
+    Demand,
+    NonIpcDemand,
+    Ipc,
+    Event,
+    KeepAlive,
+    Other(String),
+}
+impl std::fmt::Display for BlameReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BlameReason::Speculative => write!(f, "speculative"),
+            BlameReason::Demand => write!(f, "demand"),
+            BlameReason::NonIpcDemand => write!(f, "non-ipc demand"),
+            BlameReason::Ipc => write!(f, "ipc"),
+            BlameReason::Event => write!(f, "event"),
+            BlameReason::KeepAlive => write!(f, "keepalive"),
+            BlameReason::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+impl From<&str> for BlameReason {
+    fn from(reason: &str) -> BlameReason {
+        match reason.trim() {
+            "speculative" => BlameReason::Speculative,
+            "demand" => BlameReason::Demand,
+            "non-ipc demand" => BlameReason::NonIpcDemand,
+            "ipc" => BlameReason::Ipc,
+            "event" => BlameReason::Event,
+            "keepalive" => BlameReason::KeepAlive,
+            reason => BlameReason::Other(reason.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchctlBlame {
+    pub reason: BlameReason,
+    pub detail: Option<String>,
+}
+impl std::fmt::Display for LaunchctlBlame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{} ({})", self.reason, detail),
+            None => write!(f, "{}", self.reason),
+        }
+    }
+}
+
+pub fn parse_launchctl_blame(data: &str) -> Result<LaunchctlBlame> {
+    let blame_regex =
+        regex::Regex::new("^(?<reason>[^(]+?)\\s*(?:[(](?<detail>[^)]*)[)])?$").unwrap();
+    let line = data
+        .lines()
+        .map(|line| line.trim())
+        .find(|line| !line.is_empty())
+        .ok_or_else(|| Error::ParseError("launchctl blame output is empty".to_string()))?;
+    let caps = blame_regex.captures(line).ok_or_else(|| {
+        Error::ParseError(format!("launchctl blame reason not found in ```{}```", line))
+    })?;
+    let reason = BlameReason::from(caps.name("reason").expect("reason").as_str());
+    let detail = caps.name("detail").map(|detail| detail.as_str().trim().to_string());
+    Ok(LaunchctlBlame { reason, detail })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchdEndpoint {
+    pub port: String,
+    pub kind: String,
+    pub active: bool,
+    pub name: String,
+}
+
+pub fn parse_endpoints(data: &str) -> Result<Vec<LaunchdEndpoint>> {
+    let endpoint_regex = regex::Regex::new(
+        "^\\s*(?<port>0x[0-9a-fA-F]+|\\d+)\\s+(?<kind>\\w)\\s+(?<state>\\w)\\s+(?<name>.+?)\\s*$",
+    )
+    .unwrap();
+    let mut endpoints = Vec::new();
+    let mut in_endpoints = false;
+    for line in data.lines() {
+        if !in_endpoints {
+            in_endpoints = ["endpoints = {", "externally-hosted endpoints = {"]
+                .contains(&line.trim())
+                && !line.starts_with("\t\t");
+        } else {
+            if line.trim() == "}" {
+                in_endpoints = false;
+                continue;
+            }
+            let caps = endpoint_regex.captures(line).ok_or_else(|| {
+                Error::ParseError(format!("endpoint not found in ```{}```", line.trim()))
+            })?;
+            endpoints.push(LaunchdEndpoint {
+                port: caps.name("port").expect("port").as_str().to_string(),
+                kind: caps.name("kind").expect("kind").as_str().to_string(),
+                active: caps.name("state").expect("state").as_str() == "A",
+                name: caps.name("name").expect("name").as_str().to_string(),
+            });
+        }
+    }
+    Ok(endpoints)
+}
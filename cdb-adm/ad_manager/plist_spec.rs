@@ -0,0 +1,220 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum LaunchdKind {
+    #[default]
+    Agent,
+    Daemon,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum PlistFormat {
+    #[default]
+    Xml,
+    Binary,
+}
+
+/// One entry of `StartCalendarInterval`, unset fields match any value.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CalendarInterval {
+    pub minute: Option<u64>,
+    pub hour: Option<u64>,
+    pub day: Option<u64>,
+    pub weekday: Option<u64>,
+    pub month: Option<u64>,
+}
+impl CalendarInterval {
+    pub fn to_dictionary(&self) -> plist::Dictionary {
+        let mut dictionary = plist::Dictionary::new();
+        for (key, value) in [
+            ("Minute", self.minute),
+            ("Hour", self.hour),
+            ("Day", self.day),
+            ("Weekday", self.weekday),
+            ("Month", self.month),
+        ] {
+            if let Some(value) = value {
+                dictionary.insert(key.to_string(), plist::Value::Integer(value.into()));
+            }
+        }
+        dictionary
+    }
+}
+impl std::str::FromStr for CalendarInterval {
+    type Err = Error;
+
+    /// Parses comma-separated `key=value` pairs, e.g.: `hour=3,minute=30`
+    fn from_str(s: &str) -> Result<CalendarInterval> {
+        let mut interval = CalendarInterval::default();
+        for pair in s.split(',').map(|pair| pair.trim()).filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').ok_or_else(|| {
+                Error::ParseError(format!("calendar interval {:#?} is not key=value", pair))
+            })?;
+            let value = Some(value.trim().parse::<u64>()?);
+            match key.trim().to_lowercase().as_str() {
+                "minute" => interval.minute = value,
+                "hour" => interval.hour = value,
+                "day" => interval.day = value,
+                "weekday" => interval.weekday = value,
+                "month" => interval.month = value,
+                key => {
+                    return Err(Error::ParseError(format!(
+                        "unknown calendar interval key {:#?}",
+                        key
+                    )));
+                },
+            }
+        }
+        if interval == CalendarInterval::default() {
+            return Err(Error::ParseError(format!("empty calendar interval {:#?}", s)));
+        }
+        Ok(interval)
+    }
+}
+
+/// Describes a launchd agent or daemon from which `adm create` authors
+/// its plist, either from a TOML spec or from command-line flags.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LaunchdPlistSpec {
+    pub label: String,
+    #[serde(default)]
+    pub kind: LaunchdKind,
+    #[serde(default)]
+    pub program_arguments: Vec<String>,
+    pub start_interval: Option<u64>,
+    #[serde(default)]
+    pub start_calendar_interval: Vec<CalendarInterval>,
+    pub run_at_load: Option<bool>,
+    pub keep_alive: Option<bool>,
+    pub standard_out_path: Option<String>,
+    pub standard_error_path: Option<String>,
+    #[serde(default)]
+    pub environment_variables: BTreeMap<String, String>,
+}
+impl LaunchdPlistSpec {
+    pub fn from_toml(data: &str) -> Result<LaunchdPlistSpec> {
+        Ok(toml::from_str::<LaunchdPlistSpec>(data)?)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.label.trim().is_empty() {
+            return Err(Error::ConfigurationError("launchd plist requires a label".to_string()));
+        }
+        if self.label.contains('/') {
+            return Err(Error::ConfigurationError(format!(
+                "launchd label {:#?} cannot contain slashes",
+                self.label
+            )));
+        }
+        if self.program_arguments.is_empty() {
+            return Err(Error::ConfigurationError(format!(
+                "launchd plist {:#?} requires program arguments",
+                self.label
+            )));
+        }
+        if self.start_interval == Some(0) {
+            return Err(Error::ConfigurationError(format!(
+                "start interval of {:#?} must be greater than zero",
+                self.label
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn to_dictionary(&self) -> plist::Dictionary {
+        let mut dictionary = plist::Dictionary::new();
+        dictionary.insert("Label".to_string(), self.label.clone().into());
+        dictionary.insert(
+            "ProgramArguments".to_string(),
+            plist::Value::Array(
+                self.program_arguments.iter().map(|arg| arg.clone().into()).collect(),
+            ),
+        );
+        if let Some(interval) = self.start_interval {
+            dictionary.insert("StartInterval".to_string(), plist::Value::Integer(interval.into()));
+        }
+        match self.start_calendar_interval.as_slice() {
+            [] => {},
+            [interval] => {
+                dictionary.insert(
+                    "StartCalendarInterval".to_string(),
+                    interval.to_dictionary().into(),
+                );
+            },
+            intervals => {
+                dictionary.insert(
+                    "StartCalendarInterval".to_string(),
+                    plist::Value::Array(
+                        intervals.iter().map(|interval| interval.to_dictionary().into()).collect(),
+                    ),
+                );
+            },
+        }
+        if let Some(run_at_load) = self.run_at_load {
+            dictionary.insert("RunAtLoad".to_string(), run_at_load.into());
+        }
+        if let Some(keep_alive) = self.keep_alive {
+            dictionary.insert("KeepAlive".to_string(), keep_alive.into());
+        }
+        if let Some(path) = &self.standard_out_path {
+            dictionary.insert("StandardOutPath".to_string(), path.clone().into());
+        }
+        if let Some(path) = &self.standard_error_path {
+            dictionary.insert("StandardErrorPath".to_string(), path.clone().into());
+        }
+        if !self.environment_variables.is_empty() {
+            let mut environment = plist::Dictionary::new();
+            for (key, value) in &self.environment_variables {
+                environment.insert(key.to_string(), value.clone().into());
+            }
+            dictionary.insert("EnvironmentVariables".to_string(), environment.into());
+        }
+        dictionary
+    }
+
+    pub fn to_bytes(&self, format: PlistFormat) -> Result<Vec<u8>> {
+        self.validate()?;
+        let value = plist::Value::Dictionary(self.to_dictionary());
+        let mut bytes = Vec::<u8>::new();
+        match format {
+            PlistFormat::Xml => plist::to_writer_xml(&mut bytes, &value)?,
+            PlistFormat::Binary => plist::to_writer_binary(&mut bytes, &value)?,
+        }
+        Ok(bytes)
+    }
+
+    /// Search root the plist goes into: daemons always live in
+    /// /Library/LaunchDaemons while agents go into the user's
+    /// ~/Library/LaunchAgents unless `system` is set.
+    pub fn install_root(&self, system: bool) -> iocore::Path {
+        match (self.kind, system) {
+            (LaunchdKind::Daemon, _) => iocore::Path::raw("/Library/LaunchDaemons"),
+            (LaunchdKind::Agent, true) => iocore::Path::raw("/Library/LaunchAgents"),
+            (LaunchdKind::Agent, false) => iocore::Path::new("~/Library/LaunchAgents"),
+        }
+    }
+
+    pub fn install_path(&self, system: bool) -> iocore::Path {
+        self.install_root(system).join(format!("{}.plist", self.label))
+    }
+
+    /// Whether the installed plist must be owned by root:wheel for
+    /// launchd to accept it.
+    pub fn owned_by_root(&self, system: bool) -> bool {
+        self.kind == LaunchdKind::Daemon || system
+    }
+
+    /// Domain the plist gets bootstrapped into.
+    pub fn domain(&self, uid: &crate::Uid) -> String {
+        match self.kind {
+            LaunchdKind::Daemon => crate::agent_or_daemon_prefix(None, false),
+            LaunchdKind::Agent => crate::agent_or_daemon_prefix(Some(*uid), true),
+        }
+    }
+}
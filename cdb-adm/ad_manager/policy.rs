@@ -0,0 +1,222 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{LaunchdKind, LaunchdPlistSpec, Uid};
+
+pub const DEFAULT_ENFORCE_INTERVAL_SECS: u64 = 3600;
+pub const ENFORCE_LAUNCH_AGENT_LABEL: &str = "com.github.gabrielfalcao.cdb-adm.enforce";
+
+/// Declares which agents and daemons must stay disabled, which must
+/// stay booted out and which ones `adm enforce` must leave alone.
+///
+/// Entries are either labels or `domain/label` targets and may end in
+/// `*` to match every label with that prefix. `ignore` takes
+/// precedence over `bootout` which takes precedence over `disabled`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Policy {
+    #[serde(default)]
+    pub disabled: Vec<String>,
+    #[serde(default)]
+    pub bootout: Vec<String>,
+    #[serde(default)]
+    pub ignore: Vec<String>,
+    pub interval: Option<u64>,
+}
+impl Policy {
+    pub fn interval(&self) -> u64 {
+        self.interval.unwrap_or(DEFAULT_ENFORCE_INTERVAL_SECS)
+    }
+
+    pub fn rule(&self, domain: &str, service: &str) -> Option<PolicyRule> {
+        let matches =
+            |patterns: &[String]| patterns.iter().any(|h| policy_matches(h, domain, service));
+        if matches(&self.ignore) {
+            None
+        } else if matches(&self.bootout) {
+            Some(PolicyRule::Bootout)
+        } else if matches(&self.disabled) {
+            Some(PolicyRule::Disabled)
+        } else {
+            None
+        }
+    }
+}
+
+fn policy_matches(pattern: &str, domain: &str, service: &str) -> bool {
+    let target = format!("{}/{}", domain, service);
+    match pattern.strip_suffix('*') {
+        Some(prefix) => service.starts_with(prefix) || target.starts_with(prefix),
+        None => pattern == service || pattern == target,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyRule {
+    Disabled,
+    Bootout,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Deviation {
+    Enabled,
+    Loaded,
+    Running,
+}
+impl std::fmt::Display for Deviation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                Deviation::Enabled => "enabled",
+                Deviation::Loaded => "loaded",
+                Deviation::Running => "running",
+            }
+        )
+    }
+}
+
+/// Live state of a service as seen by `launchctl print` (loaded) and
+/// `launchctl print-disabled` (enabled) in a given domain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceState {
+    pub domain: String,
+    pub service: String,
+    pub pid: i64,
+    pub loaded: bool,
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyDeviation {
+    pub domain: String,
+    pub service: String,
+    pub pid: i64,
+    pub rule: PolicyRule,
+    pub deviations: Vec<Deviation>,
+}
+impl PolicyDeviation {
+    pub fn target(&self) -> String {
+        format!("{}/{}", self.domain, self.service)
+    }
+}
+impl std::fmt::Display for PolicyDeviation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} should be {} but is {}",
+            self.target(),
+            match self.rule {
+                PolicyRule::Disabled => "disabled",
+                PolicyRule::Bootout => "booted out",
+            },
+            self.deviations.iter().map(|h| h.to_string()).collect::<Vec<String>>().join(", ")
+        )
+    }
+}
+
+/// Merges the services of `launchctl print` (`loaded`) with those of
+/// `launchctl print-disabled` (`overrides`) of the same domains.
+pub fn service_states(
+    loaded: &[(String, String, i64)],
+    overrides: &[(String, String, bool)],
+) -> Vec<ServiceState> {
+    let mut states = BTreeMap::<(String, String), ServiceState>::new();
+    for (domain, service, pid) in loaded {
+        states.insert(
+            (domain.to_string(), service.to_string()),
+            ServiceState {
+                domain: domain.to_string(),
+                service: service.to_string(),
+                pid: *pid,
+                loaded: true,
+                enabled: true,
+            },
+        );
+    }
+    for (domain, service, enabled) in overrides {
+        states
+            .entry((domain.to_string(), service.to_string()))
+            .or_insert_with(|| ServiceState {
+                domain: domain.to_string(),
+                service: service.to_string(),
+                pid: 0,
+                loaded: false,
+                enabled: *enabled,
+            })
+            .enabled = *enabled;
+    }
+    states.into_values().collect()
+}
+
+pub fn evaluate_policy(policy: &Policy, states: &[ServiceState]) -> Vec<PolicyDeviation> {
+    let mut deviations = Vec::<PolicyDeviation>::new();
+    for state in states {
+        let rule = match policy.rule(&state.domain, &state.service) {
+            Some(rule) => rule,
+            None => continue,
+        };
+        let mut found = Vec::<Deviation>::new();
+        if state.enabled {
+            found.push(Deviation::Enabled);
+        }
+        if rule == PolicyRule::Bootout && state.loaded {
+            found.push(Deviation::Loaded);
+        }
+        if state.pid != 0 {
+            found.push(Deviation::Running);
+        }
+        if !found.is_empty() {
+            deviations.push(PolicyDeviation {
+                domain: state.domain.to_string(),
+                service: state.service.to_string(),
+                pid: state.pid,
+                rule,
+                deviations: found,
+            });
+        }
+    }
+    deviations
+}
+
+/// LaunchAgent that runs `adm enforce --once` for `uid` every
+/// `interval` seconds, logging the corrections to `log_path`. The
+/// settings files holding the policy are passed in `environment`, as
+/// launchd starts agents with an environment of its own.
+pub fn enforce_launch_agent_plist(
+    program: &str,
+    uid: &Uid,
+    environment: BTreeMap<String, String>,
+    interval: u64,
+    log_path: &str,
+) -> plist::Dictionary {
+    enforce_launch_agent_spec(program, uid, environment, interval, log_path).to_dictionary()
+}
+pub(crate) fn enforce_launch_agent_spec(
+    program: &str,
+    uid: &Uid,
+    environment: BTreeMap<String, String>,
+    interval: u64,
+    log_path: &str,
+) -> LaunchdPlistSpec {
+    LaunchdPlistSpec {
+        label: ENFORCE_LAUNCH_AGENT_LABEL.to_string(),
+        kind: LaunchdKind::Agent,
+        program_arguments: vec![
+            program.to_string(),
+            "enforce".to_string(),
+            "--once".to_string(),
+            "--uid".to_string(),
+            uid.to_string(),
+        ],
+        start_interval: Some(interval),
+        run_at_load: Some(true),
+        standard_out_path: Some(log_path.to_string()),
+        standard_error_path: Some(log_path.to_string()),
+        environment_variables: environment,
+        ..LaunchdPlistSpec::default()
+    }
+}
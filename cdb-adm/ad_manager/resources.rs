@@ -0,0 +1,296 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, ServiceStatus};
+
+pub const PS_PATH: &str = "/bin/ps";
+pub const PROC_PATH: &str = "/proc";
+/// `USER_HZ` of Linux, the unit of the times in `/proc/<pid>/stat`.
+pub const PROC_CLOCK_TICKS: u64 = 100;
+
+/// What a running service costs: resident memory, cpu time consumed
+/// so far, thread count and when its process started.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessResources {
+    pub pid: i64,
+    pub rss_kib: u64,
+    pub cpu_time_ms: u64,
+    pub threads: u64,
+    /// Seconds since the unix epoch.
+    pub started: u64,
+}
+impl ProcessResources {
+    pub fn rss(&self) -> String {
+        format_kib(self.rss_kib)
+    }
+
+    pub fn cpu_time(&self) -> String {
+        let seconds = self.cpu_time_ms / 1000;
+        format!(
+            "{}:{:02}:{:02}.{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60,
+            self.cpu_time_ms % 1000 / 10
+        )
+    }
+
+    pub fn start_time(&self) -> String {
+        format_epoch(self.started)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResourceSort {
+    #[default]
+    Memory,
+    Cpu,
+}
+
+/// Resources of each of `pids` still running, read with `ps` where
+/// launchd runs and from `/proc` elsewhere.
+pub fn process_resources(pids: &[i64]) -> Result<BTreeMap<i64, ProcessResources>> {
+    if crate::macos_execution() {
+        ps_resources(pids, now())
+    } else {
+        proc_resources(&iocore::Path::raw(PROC_PATH), pids)
+    }
+}
+
+/// Runs `ps` through the [command runner](crate::command_runner) for
+/// memory and cpu time and `ps -M` to count threads, as the `ps` of
+/// macOS has no keyword for them.
+pub fn ps_resources(pids: &[i64], now: u64) -> Result<BTreeMap<i64, ProcessResources>> {
+    if pids.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let pids = pids.iter().map(|pid| pid.to_string()).collect::<Vec<String>>().join(",");
+    let runner = crate::command_runner();
+    // ps exits 1 when some pid is gone, the output of the others is
+    // still good.
+    let (_, stdout, stderr) =
+        runner.run(PS_PATH, &["-o", "pid=,rss=,time=,etime=", "-p", &pids])?;
+    if stdout.trim().is_empty() && !stderr.trim().is_empty() {
+        return Err(Error::SystemError(format!("`ps -p {}' failed: {}", pids, stderr.trim())));
+    }
+    let mut resources = parse_ps_resources(&stdout, now)?;
+    let (_, stdout, _) = runner.run(PS_PATH, &["-M", "-p", &pids])?;
+    for (pid, threads) in parse_ps_threads(&stdout) {
+        if let Some(resources) = resources.get_mut(&pid) {
+            resources.threads = threads;
+        }
+    }
+    Ok(resources)
+}
+
+/// Parses the output of `ps -o pid=,rss=,time=,etime=`, computing
+/// start times from the elapsed times and `now`.
+pub fn parse_ps_resources(data: &str, now: u64) -> Result<BTreeMap<i64, ProcessResources>> {
+    let mut resources = BTreeMap::<i64, ProcessResources>::new();
+    for line in data.lines().filter(|line| !line.trim().is_empty()) {
+        let fields = line.split_whitespace().collect::<Vec<&str>>();
+        let [pid, rss, time, etime] = fields[..] else {
+            return Err(Error::ParseError(format!(
+                "expected pid, rss, time and etime in ps line {:#?}",
+                line
+            )));
+        };
+        let pid = pid
+            .parse::<i64>()
+            .map_err(|error| Error::ParseError(format!("pid {:#?}: {}", pid, error)))?;
+        resources.insert(
+            pid,
+            ProcessResources {
+                pid,
+                rss_kib: rss
+                    .parse::<u64>()
+                    .map_err(|error| Error::ParseError(format!("rss {:#?}: {}", rss, error)))?,
+                cpu_time_ms: parse_ps_duration_ms(time)?,
+                threads: 0,
+                started: now.saturating_sub(parse_ps_duration_ms(etime)? / 1000),
+            },
+        );
+    }
+    Ok(resources)
+}
+
+/// Counts the threads of each pid in the output of `ps -M`, where
+/// every thread has a line of its own and only the first one of each
+/// process carries its user.
+pub fn parse_ps_threads(data: &str) -> BTreeMap<i64, u64> {
+    let mut threads = BTreeMap::<i64, u64>::new();
+    for line in data.lines().skip(1) {
+        let mut fields = line.split_whitespace();
+        let pid = match fields.next().map(|field| field.parse::<i64>()) {
+            Some(Ok(pid)) => Some(pid),
+            Some(Err(_)) => fields.next().and_then(|field| field.parse::<i64>().ok()),
+            None => None,
+        };
+        if let Some(pid) = pid {
+            *threads.entry(pid).or_default() += 1;
+        }
+    }
+    threads
+}
+
+/// Parses the `[[dd-]hh:]mm:ss[.cc]` durations of the `time` and
+/// `etime` keywords of `ps` into milliseconds.
+pub fn parse_ps_duration_ms(duration: &str) -> Result<u64> {
+    let invalid = || Error::ParseError(format!("invalid ps duration {:#?}", duration));
+    let (days, clock) = match duration.split_once('-') {
+        Some((days, clock)) => (days.parse::<u64>().map_err(|_| invalid())?, clock),
+        None => (0, duration),
+    };
+    let parts = clock.split(':').collect::<Vec<&str>>();
+    if parts.len() < 2 || parts.len() > 3 {
+        return Err(invalid());
+    }
+    let (seconds, fraction) = match parts[parts.len() - 1].split_once('.') {
+        Some((seconds, fraction)) => (seconds, fraction),
+        None => (parts[parts.len() - 1], ""),
+    };
+    let mut total = 0;
+    for part in parts[..parts.len() - 1].iter().chain([&seconds]) {
+        total = total * 60 + part.parse::<u64>().map_err(|_| invalid())?;
+    }
+    total += days * 86_400;
+    let millis = format!("{:0<3}", fraction)
+        .get(..3)
+        .and_then(|millis| millis.parse::<u64>().ok())
+        .ok_or_else(invalid)?;
+    Ok(total * 1000 + millis)
+}
+
+/// Reads the resources of each of `pids` from `<root>/<pid>/stat` and
+/// `<root>/<pid>/status`, with the boot time from `<root>/stat`.
+/// Processes gone in the meantime are left out.
+pub fn proc_resources(
+    root: &iocore::Path,
+    pids: &[i64],
+) -> Result<BTreeMap<i64, ProcessResources>> {
+    let mut resources = BTreeMap::<i64, ProcessResources>::new();
+    if pids.is_empty() {
+        return Ok(resources);
+    }
+    let boot_time = parse_proc_boot_time(&root.join("stat").read()?)?;
+    for pid in pids {
+        let directory = root.join(pid.to_string());
+        let (Ok(stat), Ok(status)) =
+            (directory.join("stat").read(), directory.join("status").read())
+        else {
+            continue;
+        };
+        resources.insert(*pid, parse_proc_resources(*pid, &stat, &status, boot_time)?);
+    }
+    Ok(resources)
+}
+
+/// Resources of `pid` from the contents of its `/proc` `stat` and
+/// `status` files, `boot_time` being the `btime` of `/proc/stat`.
+pub fn parse_proc_resources(
+    pid: i64,
+    stat: &str,
+    status: &str,
+    boot_time: u64,
+) -> Result<ProcessResources> {
+    // the command name may contain spaces and parentheses, the fields
+    // after it start with the state, field 3 of proc_pid_stat(5)
+    let fields = stat
+        .rsplit_once(')')
+        .map(|(_, fields)| fields.split_whitespace().collect::<Vec<&str>>())
+        .unwrap_or_default();
+    let field = |number: usize| -> Result<u64> {
+        fields.get(number - 3).and_then(|field| field.parse::<u64>().ok()).ok_or_else(|| {
+            Error::ParseError(format!("field {} missing from /proc/{}/stat", number, pid))
+        })
+    };
+    let (utime, stime, threads, start_ticks) = (field(14)?, field(15)?, field(20)?, field(22)?);
+    Ok(ProcessResources {
+        pid,
+        rss_kib: proc_status_value(status, "VmRSS").unwrap_or_default(),
+        cpu_time_ms: (utime + stime) * 1000 / PROC_CLOCK_TICKS,
+        threads: proc_status_value(status, "Threads").unwrap_or(threads),
+        started: boot_time + start_ticks / PROC_CLOCK_TICKS,
+    })
+}
+
+pub fn parse_proc_boot_time(stat: &str) -> Result<u64> {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|btime| btime.trim().parse::<u64>().ok())
+        .ok_or_else(|| Error::ParseError("btime missing from /proc/stat".to_string()))
+}
+
+fn proc_status_value(status: &str, key: &str) -> Option<u64> {
+    status.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        if name != key {
+            return None;
+        }
+        value.split_whitespace().next()?.parse::<u64>().ok()
+    })
+}
+
+/// Running services along with their resources, most expensive first
+/// according to `by`. Services whose process is gone are left out.
+pub fn rank_by_resources(
+    statuses: Vec<ServiceStatus>,
+    resources: &BTreeMap<i64, ProcessResources>,
+    by: ResourceSort,
+) -> Vec<(ServiceStatus, ProcessResources)> {
+    let mut ranked = statuses
+        .into_iter()
+        .filter_map(|status| {
+            let resources = resources.get(&status.pid)?.clone();
+            Some((status, resources))
+        })
+        .collect::<Vec<(ServiceStatus, ProcessResources)>>();
+    ranked.sort_by(|(a, a_resources), (b, b_resources)| {
+        let label = a.service.cmp(&b.service);
+        match by {
+            ResourceSort::Memory => b_resources.rss_kib.cmp(&a_resources.rss_kib).then(label),
+            ResourceSort::Cpu => b_resources.cpu_time_ms.cmp(&a_resources.cpu_time_ms).then(label),
+        }
+    });
+    ranked
+}
+
+pub fn format_kib(kib: u64) -> String {
+    match kib {
+        0..1024 => format!("{}K", kib),
+        1024..1048576 => format!("{:.1}M", kib as f64 / 1024.0),
+        _ => format!("{:.1}G", kib as f64 / 1048576.0),
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC.
+pub fn format_epoch(epoch: u64) -> String {
+    let (days, seconds) = (epoch / 86400, epoch % 86400);
+    // civil_from_days of Howard Hinnant's date algorithms
+    let days = days as i64 + 719468;
+    let era = days / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
@@ -0,0 +1,120 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{read_backup, write_backup, Result, ServiceStatus};
+
+/// Services of every domain at a point in time, saved as JSON so
+/// that later listings can be compared against it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceSnapshot {
+    pub timestamp: u64,
+    pub services: Vec<ServiceStatus>,
+}
+impl ServiceSnapshot {
+    pub fn new(services: Vec<ServiceStatus>) -> ServiceSnapshot {
+        ServiceSnapshot {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            services,
+        }
+    }
+
+    /// Reads a snapshot saved with [`save`](Self::save) or, decrypting
+    /// it, with [`save_encrypted`](Self::save_encrypted).
+    pub fn load(path: &iocore::Path) -> Result<ServiceSnapshot> {
+        Ok(serde_json::from_str(&read_backup(path)?)?)
+    }
+
+    pub fn save(&self, path: &iocore::Path) -> Result<()> {
+        write_backup(path, &serde_json::to_string_pretty(self)?, false)
+    }
+
+    pub fn save_encrypted(&self, path: &iocore::Path) -> Result<()> {
+        write_backup(path, &serde_json::to_string_pretty(self)?, true)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "kind")]
+pub enum ServiceDifference {
+    Added,
+    Removed,
+    Started { pid: i64 },
+    Stopped { pid: i64 },
+    Restarted { before: i64, after: i64 },
+    Enabled,
+    Disabled,
+    ExitStatus { before: Option<i64>, after: Option<i64> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceChange {
+    pub domain: String,
+    pub service: String,
+    pub differences: Vec<ServiceDifference>,
+}
+impl ServiceChange {
+    pub fn target(&self) -> String {
+        format!("{}/{}", self.domain, self.service)
+    }
+}
+
+/// Changes of each service from `before` to `after`, ordered by
+/// target. Services present in both snapshots without differences are
+/// left out.
+pub fn diff_service_snapshots(
+    before: &ServiceSnapshot,
+    after: &ServiceSnapshot,
+) -> Vec<ServiceChange> {
+    let index = |snapshot: &ServiceSnapshot| {
+        snapshot
+            .services
+            .iter()
+            .map(|status| (status.target(), status.clone()))
+            .collect::<BTreeMap<String, ServiceStatus>>()
+    };
+    let (before, after) = (index(before), index(after));
+    let mut targets = before.keys().chain(after.keys()).collect::<Vec<&String>>();
+    targets.sort();
+    targets.dedup();
+    let mut changes = Vec::<ServiceChange>::new();
+    for target in targets {
+        let (status, differences) = match (before.get(target), after.get(target)) {
+            (None, Some(after)) => (after, vec![ServiceDifference::Added]),
+            (Some(before), None) => (before, vec![ServiceDifference::Removed]),
+            (Some(before), Some(after)) => (after, service_differences(before, after)),
+            (None, None) => continue,
+        };
+        if !differences.is_empty() {
+            changes.push(ServiceChange {
+                domain: status.domain.to_string(),
+                service: status.service.to_string(),
+                differences,
+            });
+        }
+    }
+    changes
+}
+
+fn service_differences(before: &ServiceStatus, after: &ServiceStatus) -> Vec<ServiceDifference> {
+    let mut differences = Vec::<ServiceDifference>::new();
+    match (before.pid, after.pid) {
+        (0, 0) => {},
+        (0, pid) => differences.push(ServiceDifference::Started { pid }),
+        (pid, 0) => differences.push(ServiceDifference::Stopped { pid }),
+        (before, after) if before != after =>
+            differences.push(ServiceDifference::Restarted { before, after }),
+        _ => {},
+    }
+    match (before.enabled, after.enabled) {
+        (false, true) => differences.push(ServiceDifference::Enabled),
+        (true, false) => differences.push(ServiceDifference::Disabled),
+        _ => {},
+    }
+    if before.status != after.status {
+        differences
+            .push(ServiceDifference::ExitStatus { before: before.status, after: after.status });
+    }
+    differences
+}
@@ -0,0 +1,147 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ServiceCatalog, Uid};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum DomainKind {
+    System,
+    User,
+    Gui,
+}
+impl DomainKind {
+    pub fn of(domain: &str) -> Option<DomainKind> {
+        match domain.split('/').next() {
+            Some("system") => Some(DomainKind::System),
+            Some("user") => Some(DomainKind::User),
+            Some("gui") => Some(DomainKind::Gui),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum StatusSort {
+    #[default]
+    Label,
+    Pid,
+    Status,
+    Domain,
+}
+
+/// One service of `adm status` along with its catalog categories.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub domain: String,
+    pub service: String,
+    pub pid: i64,
+    pub status: Option<i64>,
+    pub enabled: bool,
+    pub path: Option<String>,
+    pub categories: Vec<String>,
+}
+impl ServiceStatus {
+    pub fn target(&self) -> String {
+        format!("{}/{}", self.domain, self.service)
+    }
+
+    pub fn running(&self) -> bool {
+        self.pid != 0
+    }
+
+    /// Last exit status is neither zero nor unknown.
+    pub fn crashed(&self) -> bool {
+        self.status.map(|status| status != 0).unwrap_or_default()
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn service_statuses(
+    agents_and_daemons: &[(
+        String,
+        String,
+        i64,
+        Option<i64>,
+        bool,
+        Option<(iocore::Path, plist::Dictionary)>,
+    )],
+    catalog: &ServiceCatalog,
+) -> Vec<ServiceStatus> {
+    agents_and_daemons
+        .iter()
+        .map(|(domain, service, pid, status, enabled, info)| ServiceStatus {
+            domain: domain.to_string(),
+            service: service.to_string(),
+            pid: *pid,
+            status: *status,
+            enabled: *enabled,
+            path: info.as_ref().map(|(path, _)| path.to_string()),
+            categories: catalog.categories(service),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatusFilter {
+    pub domain: Option<DomainKind>,
+    pub uid: Option<Uid>,
+    pub running: Option<bool>,
+    pub enabled: Option<bool>,
+    pub crashed: bool,
+    pub label: Option<String>,
+    pub category: Option<String>,
+}
+impl StatusFilter {
+    pub fn matches(&self, status: &ServiceStatus) -> bool {
+        self.domain.is_none_or(|kind| DomainKind::of(&status.domain) == Some(kind))
+            && self.uid.is_none_or(|uid| status.domain.ends_with(&format!("/{}", uid)))
+            && self.running.is_none_or(|running| status.running() == running)
+            && self.enabled.is_none_or(|enabled| status.enabled == enabled)
+            && (!self.crashed || status.crashed())
+            && self.label.as_ref().is_none_or(|selector| label_matches(selector, &status.service))
+            && self.category.as_ref().is_none_or(|category| status.categories.contains(category))
+    }
+}
+
+/// Selectors containing `*` are globs matched against the whole
+/// label, any other selector matches labels that contain it.
+pub fn label_matches(selector: &str, label: &str) -> bool {
+    if !selector.contains('*') {
+        return label.contains(selector);
+    }
+    let parts = selector.split('*').collect::<Vec<&str>>();
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !label.starts_with(first) || label.len() < first.len() + last.len() {
+        return false;
+    }
+    let mut rest = &label[first.len()..];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+pub fn filter_and_sort_statuses(
+    statuses: Vec<ServiceStatus>,
+    filter: &StatusFilter,
+    sort: StatusSort,
+) -> Vec<ServiceStatus> {
+    let mut statuses = statuses
+        .into_iter()
+        .filter(|status| filter.matches(status))
+        .collect::<Vec<ServiceStatus>>();
+    statuses.sort_by(|a, b| {
+        let label = a.service.cmp(&b.service);
+        match sort {
+            StatusSort::Label => label,
+            StatusSort::Pid => a.pid.cmp(&b.pid).then(label),
+            StatusSort::Status => a.status.cmp(&b.status).then(label),
+            StatusSort::Domain => a.domain.cmp(&b.domain).then(label),
+        }
+    });
+    statuses
+}
@@ -0,0 +1,181 @@
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{run_health_checks, Error, HealthCheck, Result};
+
+pub const DEFAULT_BISECT_STATE_PATH: &str = "adm-bisect.json";
+
+/// Progress of a bisection, written to disk after every step so that
+/// an interrupted `adm bisect` can pick up where it stopped.
+///
+/// `pending` holds the sets still suspected of breaking the checks,
+/// `cleared` the targets left turned off without breaking anything and
+/// `culprits` the targets that break the checks on their own.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BisectState<T> {
+    pub pending: Vec<Vec<T>>,
+    pub cleared: Vec<T>,
+    pub culprits: Vec<T>,
+    pub steps: usize,
+}
+impl<T: Clone + Serialize + DeserializeOwned> BisectState<T> {
+    pub fn new(targets: Vec<T>) -> BisectState<T> {
+        BisectState {
+            pending: if targets.is_empty() { Vec::new() } else { vec![targets] },
+            cleared: Vec::new(),
+            culprits: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Turns off the first half of the next pending set and runs the
+    /// checks: a half that passes stays off, a half that fails is
+    /// booted back up and bisected further until single culprits remain.
+    pub fn step(
+        &mut self,
+        checks: &[Box<dyn HealthCheck>],
+        mut turn_off: impl FnMut(&[T]),
+        mut boot_up: impl FnMut(&[T]),
+    ) -> Vec<(String, Error)> {
+        let mut set = match self.pending.pop() {
+            Some(set) => set,
+            None => return Vec::new(),
+        };
+        let rest = set.split_off(set.len().div_ceil(2));
+        turn_off(&set);
+        let failures = run_health_checks(checks);
+        self.steps += 1;
+        if !rest.is_empty() {
+            self.pending.push(rest);
+        }
+        if failures.is_empty() {
+            self.cleared.extend(set);
+        } else {
+            boot_up(&set);
+            if set.len() == 1 {
+                self.culprits.extend(set);
+            } else {
+                self.pending.push(set);
+            }
+        }
+        failures
+    }
+
+    pub fn load(path: &iocore::Path) -> Result<Option<BisectState<T>>> {
+        if !path.is_file() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str::<BisectState<T>>(&path.read()?).map_err(|error| {
+            Error::JsonError(format!("reading bisect state {:#?}: {}", path.to_string(), error))
+        })?))
+    }
+
+    pub fn save(&self, path: &iocore::Path) -> Result<()> {
+        path.write(serde_json::to_string_pretty(self)?.as_bytes())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::BisectState;
+    use crate::{Error, HealthCheck, Result};
+
+    struct FakeCheck {
+        turned_off: Rc<RefCell<Vec<String>>>,
+        culprits: Vec<String>,
+    }
+    impl HealthCheck for FakeCheck {
+        fn name(&self) -> String {
+            "fake".to_string()
+        }
+
+        fn check(&self) -> Result<()> {
+            match self.culprits.iter().find(|culprit| self.turned_off.borrow().contains(culprit)) {
+                Some(culprit) => Err(Error::SystemError(format!("{} is off", culprit))),
+                None => Ok(()),
+            }
+        }
+    }
+
+    fn targets() -> Vec<String> {
+        ["a", "b", "c", "d", "e", "f", "g", "h"].iter().map(|h| h.to_string()).collect()
+    }
+
+    fn run(
+        state: &mut BisectState<String>,
+        turned_off: &Rc<RefCell<Vec<String>>>,
+        checks: &[Box<dyn HealthCheck>],
+        max_steps: usize,
+    ) {
+        for _ in 0..max_steps {
+            if state.is_done() {
+                break;
+            }
+            state.step(
+                checks,
+                |set| turned_off.borrow_mut().extend(set.to_vec()),
+                |set| turned_off.borrow_mut().retain(|target| !set.contains(target)),
+            );
+        }
+    }
+
+    #[test]
+    fn test_bisect_finds_culprits() {
+        let turned_off = Rc::new(RefCell::new(Vec::<String>::new()));
+        let checks: Vec<Box<dyn HealthCheck>> = vec![Box::new(FakeCheck {
+            turned_off: turned_off.clone(),
+            culprits: vec!["c".to_string(), "f".to_string()],
+        })];
+        let mut state = BisectState::new(targets());
+        run(&mut state, &turned_off, &checks, 100);
+
+        assert!(state.is_done());
+        assert_eq!(state.culprits, vec!["c".to_string(), "f".to_string()]);
+        let mut cleared = state.cleared.clone();
+        cleared.sort();
+        assert_eq!(cleared, vec!["a", "b", "d", "e", "g", "h"]);
+        assert_eq!(turned_off.borrow().len(), 6);
+    }
+
+    #[test]
+    fn test_bisect_resumes_from_saved_state() {
+        let turned_off = Rc::new(RefCell::new(Vec::<String>::new()));
+        let checks: Vec<Box<dyn HealthCheck>> = vec![Box::new(FakeCheck {
+            turned_off: turned_off.clone(),
+            culprits: vec!["h".to_string()],
+        })];
+        let mut state = BisectState::new(targets());
+        run(&mut state, &turned_off, &checks, 2);
+        assert!(!state.is_done());
+
+        let mut state =
+            serde_json::from_str::<BisectState<String>>(&serde_json::to_string(&state).unwrap())
+                .unwrap();
+        run(&mut state, &turned_off, &checks, 100);
+        assert!(state.is_done());
+        assert_eq!(state.culprits, vec!["h".to_string()]);
+        assert_eq!(state.cleared.len(), 7);
+    }
+
+    #[test]
+    fn test_bisect_without_culprits_clears_everything() {
+        let turned_off = Rc::new(RefCell::new(Vec::<String>::new()));
+        let checks: Vec<Box<dyn HealthCheck>> = vec![Box::new(FakeCheck {
+            turned_off: turned_off.clone(),
+            culprits: Vec::new(),
+        })];
+        let mut state = BisectState::new(targets());
+        run(&mut state, &turned_off, &checks, 100);
+        assert_eq!(state.steps, 4);
+        assert!(state.culprits.is_empty());
+        assert_eq!(state.cleared, targets());
+    }
+}
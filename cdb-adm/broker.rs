@@ -0,0 +1,330 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{wait_until, CommandRunner, Error, Result, LAUNCHCTL_PATH, SPCTL_PATH};
+
+pub const DEFAULT_BROKER_AUDIT_LOG: &str = "/var/log/cdb-adm-broker.jsonl";
+pub const BROKER_LAUNCHCTL_SUBCOMMANDS: [&str; 10] = [
+    "blame",
+    "bootout",
+    "bootstrap",
+    "disable",
+    "enable",
+    "kickstart",
+    "kill",
+    "list",
+    "print",
+    "print-disabled",
+];
+pub const BROKER_SPCTL_OPTIONS: [&str; 3] = ["--global-disable", "--master-disable", "--status"];
+
+static BROKER_CLIENT: RwLock<Option<Arc<BrokerClient>>> = RwLock::new(None);
+
+/// Command the privileged helper runs as root, always as an argv
+/// vector and never through a shell.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "program")]
+pub enum PrivilegedCommand {
+    Launchctl { args: Vec<String> },
+    Spctl { args: Vec<String> },
+}
+impl PrivilegedCommand {
+    pub fn program(&self) -> &'static str {
+        match self {
+            PrivilegedCommand::Launchctl { .. } => LAUNCHCTL_PATH,
+            PrivilegedCommand::Spctl { .. } => SPCTL_PATH,
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        match self {
+            PrivilegedCommand::Launchctl { args } | PrivilegedCommand::Spctl { args } => args,
+        }
+    }
+
+    /// Only the `launchctl` subcommands and `spctl` options used by
+    /// `adm` are allowed.
+    pub fn allowed(&self) -> bool {
+        match self {
+            PrivilegedCommand::Launchctl { args } => args
+                .first()
+                .map(|subcommand| BROKER_LAUNCHCTL_SUBCOMMANDS.contains(&subcommand.as_str()))
+                .unwrap_or_default(),
+            PrivilegedCommand::Spctl { args } =>
+                args.len() == 1 && BROKER_SPCTL_OPTIONS.contains(&args[0].as_str()),
+        }
+    }
+}
+impl std::fmt::Display for PrivilegedCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.program())?;
+        for arg in self.args() {
+            write!(f, " {:?}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokerRequest {
+    pub id: u64,
+    pub token: String,
+    pub command: PrivilegedCommand,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokerResponse {
+    pub id: u64,
+    pub exit_code: i64,
+    pub stdout: String,
+    pub stderr: String,
+    pub error: Option<Error>,
+}
+
+/// Line of the broker audit log. Tokens are never logged.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokerAuditEntry {
+    pub timestamp: u64,
+    pub id: u64,
+    pub command: PrivilegedCommand,
+    pub authorized: bool,
+    pub exit_code: Option<i64>,
+    pub error: Option<Error>,
+}
+
+/// Privileged side of `adm --broker`: authorizes each request against
+/// the session token and the command allowlist, runs it through
+/// `runner` and records it in the audit log.
+pub struct Broker {
+    token: String,
+    runner: Arc<dyn CommandRunner>,
+    audit_log: Option<iocore::Path>,
+}
+impl Broker {
+    pub fn new(
+        token: &str,
+        runner: Arc<dyn CommandRunner>,
+        audit_log: Option<iocore::Path>,
+    ) -> Broker {
+        Broker { token: token.to_string(), runner, audit_log }
+    }
+
+    pub fn authorize(&self, request: &BrokerRequest) -> Result<()> {
+        if !same_token(&self.token, &request.token) {
+            return Err(Error::AuthorizationError(format!(
+                "request {} denied: invalid token",
+                request.id
+            )));
+        }
+        if !request.command.allowed() {
+            return Err(Error::AuthorizationError(format!(
+                "request {} denied: `{}' is not allowed",
+                request.id, request.command
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn handle(&self, request: &BrokerRequest) -> BrokerResponse {
+        let authorization = self.authorize(request);
+        let result = authorization.clone().and_then(|_| {
+            let args = request.command.args().iter().map(|h| h.as_str()).collect::<Vec<&str>>();
+            let timeout = crate::execution_settings().timeout();
+            self.runner.run_with_timeout(request.command.program(), &args, timeout)
+        });
+        let response = match result {
+            Ok((exit_code, stdout, stderr)) =>
+                BrokerResponse { id: request.id, exit_code, stdout, stderr, error: None },
+            Err(error) => BrokerResponse {
+                id: request.id,
+                exit_code: -1,
+                stdout: String::new(),
+                stderr: String::new(),
+                error: Some(error),
+            },
+        };
+        let entry = BrokerAuditEntry {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            id: request.id,
+            command: request.command.clone(),
+            authorized: authorization.is_ok(),
+            exit_code: response.error.is_none().then_some(response.exit_code),
+            error: response.error.clone(),
+        };
+        if let Err(error) = self.audit(&entry) {
+            eprintln!("[warning] writing broker audit log: {}", error);
+        }
+        response
+    }
+
+    fn audit(&self, entry: &BrokerAuditEntry) -> Result<()> {
+        if let Some(path) = &self.audit_log {
+            if !path.is_file() {
+                path.write(b"")?;
+                path.clone().set_mode(0o600)?;
+            }
+            path.append(format!("{}\n", serde_json::to_string(entry)?).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Answers each JSON line request of `reader` with a JSON line
+    /// response on `writer` as soon as its command exits.
+    pub fn serve_connection(&self, reader: impl Read, mut writer: impl Write) -> Result<()> {
+        for line in BufReader::new(reader).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<BrokerRequest>(&line) {
+                Ok(request) => self.handle(&request),
+                Err(error) => BrokerResponse {
+                    id: 0,
+                    exit_code: -1,
+                    stdout: String::new(),
+                    stderr: String::new(),
+                    error: Some(Error::from(error)),
+                },
+            };
+            writer.write_all(format!("{}\n", serde_json::to_string(&response)?).as_bytes())?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Binds `socket_path` readable only by `client_uid`, serves its
+    /// first connection until the client hangs up and removes it.
+    pub fn serve(&self, socket_path: &iocore::Path, client_uid: Option<u32>) -> Result<()> {
+        if socket_path.exists() {
+            socket_path.delete()?;
+        }
+        let listener = UnixListener::bind(socket_path.to_path_buf())?;
+        socket_path.clone().set_mode(0o600)?;
+        if let Some(uid) = client_uid {
+            std::os::unix::fs::chown(socket_path.to_path_buf(), Some(uid), None)?;
+        }
+        let result = listener
+            .accept()
+            .map_err(Error::from)
+            .and_then(|(stream, _)| self.serve_connection(stream.try_clone()?, stream));
+        socket_path.delete()?;
+        result
+    }
+}
+
+fn same_token(expected: &str, given: &str) -> bool {
+    expected.len() == given.len()
+        && expected.bytes().zip(given.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+pub fn random_token() -> Result<String> {
+    let mut bytes = [0u8; 32];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(hex::encode(bytes))
+}
+
+/// Unprivileged side of `adm --broker`: sends requests to the helper
+/// over its Unix socket, one at a time.
+pub struct BrokerClient {
+    token: String,
+    connection: Mutex<(BufReader<UnixStream>, UnixStream)>,
+    next_id: AtomicU64,
+}
+impl BrokerClient {
+    pub fn connect(socket_path: &iocore::Path, token: &str) -> Result<BrokerClient> {
+        let stream = UnixStream::connect(socket_path.to_path_buf())?;
+        Ok(BrokerClient {
+            token: token.to_string(),
+            connection: Mutex::new((BufReader::new(stream.try_clone()?), stream)),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    pub fn request(&self, command: &PrivilegedCommand) -> Result<(i64, String, String)> {
+        let request = BrokerRequest {
+            id: self.next_id.fetch_add(1, Ordering::SeqCst),
+            token: self.token.to_string(),
+            command: command.clone(),
+        };
+        let mut connection = self
+            .connection
+            .lock()
+            .map_err(|error| Error::SystemError(format!("broker connection: {}", error)))?;
+        let (reader, writer) = &mut *connection;
+        writer.write_all(format!("{}\n", serde_json::to_string(&request)?).as_bytes())?;
+        writer.flush()?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(Error::SystemError("broker hung up".to_string()));
+        }
+        let response = serde_json::from_str::<BrokerResponse>(&line)?;
+        if response.id != request.id {
+            return Err(Error::SystemError(format!(
+                "broker answered request {} instead of {}",
+                response.id, request.id
+            )));
+        }
+        match response.error {
+            Some(error) => Err(error),
+            None => Ok((response.exit_code, response.stdout, response.stderr)),
+        }
+    }
+}
+
+/// Starts `adm broker` once through `sudo`, handing it the session
+/// token through stdin, and routes every privileged command of this
+/// process through it from then on.
+pub fn start_broker(audit_log: Option<&str>) -> Result<Arc<BrokerClient>> {
+    let token = random_token()?;
+    let socket_path = iocore::Path::raw(std::env::temp_dir().display().to_string())
+        .join(format!("cdb-adm-broker-{}.sock", std::process::id()));
+    let mut args = vec![
+        std::env::current_exe()?.display().to_string(),
+        "broker".to_string(),
+        "--socket".to_string(),
+        socket_path.to_string(),
+        "--client-uid".to_string(),
+        iocore::User::id()?.uid.to_string(),
+    ];
+    if let Some(path) = audit_log {
+        args.extend(["--audit-log".to_string(), path.to_string()]);
+    }
+    let mut child = Command::new("sudo")
+        .args(&args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(format!("{}\n", token).as_bytes())?;
+    }
+    let child = Mutex::new(child);
+    wait_until(&Duration::from_secs(120), || {
+        socket_path.exists()
+            || child
+                .lock()
+                .map(|mut child| child.try_wait().map(|status| status.is_some()).unwrap_or(true))
+                .unwrap_or(true)
+    })?;
+    if !socket_path.exists() {
+        return Err(Error::SystemError("privileged broker exited before listening".to_string()));
+    }
+    let client = Arc::new(BrokerClient::connect(&socket_path, &token)?);
+    set_broker_client(client.clone());
+    Ok(client)
+}
+
+pub fn set_broker_client(client: Arc<BrokerClient>) {
+    if let Ok(mut current) = BROKER_CLIENT.write() {
+        *current = Some(client);
+    }
+}
+pub fn broker_client() -> Option<Arc<BrokerClient>> {
+    BROKER_CLIENT.read().ok().and_then(|client| client.clone())
+}
@@ -1,6 +1,15 @@
 pub mod adb;
+pub mod args;
 pub mod cdb;
+pub mod output;
 pub mod traits;
-pub use adb::{BootUp, Cli as ADM, List as ADMList, Path, Status, TurnOff};
-pub use cdb::{Cli as CDB, Delete, Export, Fix, List as CDBList};
+pub use adb::{
+    Bisect, BootUp, Cli as ADM, Create, Enforce, List as ADMList, Path, Status, Tui, TurnOff, Why,
+};
+pub use args::UserArgs;
+pub use cdb::{Cli as CDB, Decrypt, Delete, Export, Fix, List as CDBList, Restore};
+pub use output::{
+    output_format, print_columns, print_diagnostics, print_output, print_rows, render_output,
+    render_rows, report_error, set_output_format, ActionRow, OutputFormat, PathRow, Row,
+};
 pub use traits::{ArgsDispatcher, ParserDispatcher, SubcommandDispatcher};
@@ -1,12 +1,27 @@
-use std::fmt::Alignment::{Left, Right};
+use std::collections::BTreeMap;
+use std::fmt::Alignment::{self, Left, Right};
+use std::time::Duration;
 
 use clap::{Args, Parser, Subcommand};
-use verynicetable::Table;
+use serde::{Deserialize, Serialize};
 
-use crate::cli::{ArgsDispatcher, ParserDispatcher, SubcommandDispatcher};
+use crate::cli::{
+    output_format, print_columns, print_diagnostics, print_output, print_rows, report_error,
+    set_output_format, ActionRow, ArgsDispatcher, OutputFormat, ParserDispatcher, PathRow, Row,
+    SubcommandDispatcher, UserArgs,
+};
 use crate::{
-    boot_up_smart, list_agents_and_daemons, list_all_agents_and_daemons, spctl_global_disable,
-    turn_off_mdutil, turn_off_smart, Error, Result, Uid,
+    adm_config, adm_settings, agent_or_daemon_prefix, bisect_smart, boot_up_smart, check_settings,
+    command_runner, enforce_policy, explain_turn_off_smart, filter_and_sort_statuses,
+    install_enforce_launch_agent, install_launchd_plist, launchctl_blame, launchctl_list,
+    list_agents_and_daemons, list_all_agents_and_daemons, process_resources, rank_by_resources,
+    resolve_adm_config, run_tui, service_statuses, set_adm_config, set_execution_settings,
+    spctl_global_disable, start_broker, turn_off_mdutil, turn_off_smart, turn_off_smart_verified,
+    AuditReport, CalendarInterval, CommandSucceeds, ConfigEntry, DomainKind, Error, HealthCheck,
+    ImpactHint, LaunchdKind, LaunchdPlistSpec, PlistFormat, PolicyDeviation, ProcessResources,
+    ReportFormat, ResourceSort, Result, ServiceCatalog, ServiceStatus, StatusFilter, StatusSort,
+    SystemFacts, Uid, DEFAULT_BISECT_STATE_PATH, DEFAULT_BROKER_AUDIT_LOG,
+    DEFAULT_TUI_REFRESH_SECS, TUI_JOURNAL_FILENAME,
 };
 
 #[derive(Parser, Debug)]
@@ -14,11 +29,36 @@ use crate::{
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+
+    #[arg(long, global = true, value_enum, default_value = "table")]
+    pub output: OutputFormat,
+
+    #[arg(
+        long,
+        global = true,
+        help = "authenticate once and run privileged commands through a broker"
+    )]
+    pub broker: bool,
+
+    #[arg(long, global = true, help = "audit log of the broker", requires = "broker")]
+    pub broker_audit_log: Option<String>,
 }
 impl ParserDispatcher<Error> for Cli {
     fn dispatch(&self) -> Result<()> {
-        self.command.dispatch()?;
-        Ok(())
+        set_output_format(self.output);
+        let config = match resolve_adm_config(&self.command.config_flags()) {
+            Ok(config) => config,
+            Err(error) => {
+                eprintln!("[warning] loading settings: {}", error);
+                adm_config()
+            },
+        };
+        set_execution_settings(config.adm_settings()?.execution());
+        set_adm_config(config);
+        if self.broker && !matches!(self.command, Command::Broker(_)) {
+            report_error(start_broker(self.broker_audit_log.as_deref()).map(|_| ()))?;
+        }
+        report_error(self.command.dispatch())
     }
 }
 #[derive(Subcommand, Debug)]
@@ -28,6 +68,41 @@ pub enum Command {
     TurnOff(TurnOff),
     BootUp(BootUp),
     Status(Status),
+    Top(Top),
+    Report(Report),
+    Why(Why),
+    Bisect(Bisect),
+    Enforce(Enforce),
+    Create(Create),
+    Tui(Tui),
+    #[command(subcommand)]
+    Config(Config),
+    #[command(hide = true)]
+    Broker(Broker),
+}
+impl Command {
+    /// Settings given as flags, by settings key, which take precedence
+    /// over the environment and the settings files.
+    pub fn config_flags(&self) -> Vec<(&'static str, &'static str, toml::Value)> {
+        let flags = match self {
+            Command::TurnOff(op) => vec![
+                ("display_warnings", "--display-warnings", op.display_warnings),
+                ("include_non_needed", "--include-non-needed", op.include_non_needed),
+                ("include_system_uids", "--include-system-uids", op.include_system_uids),
+            ],
+            Command::BootUp(op) => vec![
+                ("display_warnings", "--display-warnings", op.display_warnings),
+                ("include_non_needed", "--include-non-needed", op.include_non_needed),
+            ],
+            Command::Bisect(op) =>
+                vec![("include_non_needed", "--include-non-needed", op.include_non_needed)],
+            _ => Vec::new(),
+        };
+        flags
+            .into_iter()
+            .filter_map(|(key, flag, value)| value.map(|h| (key, flag, toml::Value::Boolean(h))))
+            .collect()
+    }
 }
 impl SubcommandDispatcher<Error> for Command {
     fn dispatch(&self) -> Result<()> {
@@ -35,8 +110,17 @@ impl SubcommandDispatcher<Error> for Command {
             Command::List(op) => op.dispatch()?,
             Command::Path(op) => op.dispatch()?,
             Command::Status(op) => op.dispatch()?,
+            Command::Top(op) => op.dispatch()?,
+            Command::Report(op) => op.dispatch()?,
             Command::TurnOff(op) => op.dispatch()?,
             Command::BootUp(op) => op.dispatch()?,
+            Command::Why(op) => op.dispatch()?,
+            Command::Bisect(op) => op.dispatch()?,
+            Command::Enforce(op) => op.dispatch()?,
+            Command::Create(op) => op.dispatch()?,
+            Command::Tui(op) => op.dispatch()?,
+            Command::Config(op) => op.dispatch()?,
+            Command::Broker(op) => op.dispatch()?,
         }
         Ok(())
     }
@@ -47,67 +131,583 @@ pub struct TurnOff {
     #[arg()]
     pub services: Vec<String>,
 
-    #[arg(long, default_value = "501")]
-    pub uid: Uid,
+    #[command(flatten)]
+    pub user: UserArgs,
 
     #[arg(short, long)]
     pub verbose: bool,
 
-    #[arg(short, long)]
-    pub display_warnings: bool,
+    #[arg(short, long, num_args = 0..=1, default_missing_value = "true")]
+    pub display_warnings: Option<bool>,
 
-    #[arg(short, long)]
-    pub include_non_needed: bool,
+    #[arg(short, long, num_args = 0..=1, default_missing_value = "true")]
+    pub include_non_needed: Option<bool>,
 
-    #[arg(short = 'u', long)]
-    pub include_system_uids: bool,
+    #[arg(short = 'u', long, num_args = 0..=1, default_missing_value = "true")]
+    pub include_system_uids: Option<bool>,
 
     #[arg(long)]
     pub logs: bool,
+
+    #[arg(long, help = "explain what depends on the services instead of turning them off")]
+    pub explain: bool,
+
+    #[arg(long, help = "run health checks after each batch and roll it back if any fails")]
+    pub verify: bool,
+
+    #[arg(long, default_value = "10")]
+    pub batch_size: usize,
 }
 impl ArgsDispatcher<Error> for TurnOff {
     fn dispatch(&self) -> Result<()> {
+        let quiet = !self.verbose || output_format().is_structured();
+        if self.explain {
+            let hints = explain_turn_off_smart(
+                &self.user.resolve()?,
+                quiet,
+                self.services.clone(),
+                adm_settings().include_non_needed(),
+            )?;
+            print_output(&hints, |hints| {
+                hints.iter().map(|hint| format!("{}\n", hint)).collect::<String>()
+            })?;
+            return Ok(());
+        }
         spctl_global_disable()?;
         turn_off_mdutil()?;
-        turn_off_smart(
-            &self.uid,
-            !self.verbose,
+        if self.verify {
+            let checks = adm_settings().health_checks();
+            if checks.is_empty() {
+                return Err(Error::ConfigurationError(
+                    "--verify requires health checks configured in the adm settings".to_string(),
+                ));
+            }
+            let outcomes = turn_off_smart_verified(
+                &self.user.resolve()?,
+                quiet,
+                self.services.clone(),
+                adm_settings().include_non_needed(),
+                self.logs,
+                self.batch_size,
+                &checks,
+            )?;
+            let rows = outcomes.iter().map(BatchRow::from).collect::<Vec<BatchRow>>();
+            print_output(&rows, |rows| {
+                let mut text = String::new();
+                for row in rows {
+                    for (name, error) in &row.failures {
+                        text.push_str(&format!(
+                            "[error] health check {:#?} failed: {}\n",
+                            name, error
+                        ));
+                    }
+                    if row.rolled_back {
+                        text.push_str(&format!(
+                            "[warning] rolled back {}\n",
+                            row.targets.join(", ")
+                        ));
+                    }
+                }
+                text
+            })?;
+            return Ok(());
+        }
+        let results = turn_off_smart(
+            &self.user.resolve()?,
+            quiet,
             self.services.clone(),
-            self.include_non_needed,
-            self.logs
+            adm_settings().include_non_needed(),
+            self.logs,
         );
+        print_output(&results.iter().map(ActionRow::from).collect::<Vec<ActionRow>>(), |_| {
+            String::new()
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchRow {
+    pub targets: Vec<String>,
+    pub rolled_back: bool,
+    pub failures: Vec<(String, Error)>,
+}
+impl From<&crate::BatchOutcome<(String, String, i64)>> for BatchRow {
+    fn from(outcome: &crate::BatchOutcome<(String, String, i64)>) -> BatchRow {
+        BatchRow {
+            targets: outcome
+                .targets
+                .iter()
+                .map(|(domain, service, _)| format!("{}/{}", domain, service))
+                .collect(),
+            rolled_back: outcome.rolled_back,
+            failures: outcome.failures.clone(),
+        }
+    }
+}
+impl Row for BatchRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("TARGETS", Left), ("ROLLED BACK", Left), ("FAILURES", Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.targets.join(" "),
+            self.rolled_back.to_string(),
+            self.failures
+                .iter()
+                .map(|(name, error)| format!("{}: {}", name, error))
+                .collect::<Vec<String>>()
+                .join("; "),
+        ]
+    }
+}
+impl Row for ImpactHint {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![
+            ("TARGET", Left),
+            ("ENDPOINT", Left),
+            ("ACTIVE", Left),
+            ("CONSUMERS", Left),
+        ]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            format!("{}/{}", self.domain, self.service),
+            self.endpoint.to_string(),
+            self.active.to_string(),
+            self.consumers.join(" "),
+        ]
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Bisect {
+    #[arg()]
+    pub services: Vec<String>,
+
+    #[command(flatten)]
+    pub user: UserArgs,
+
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[arg(short, long, num_args = 0..=1, default_missing_value = "true")]
+    pub include_non_needed: Option<bool>,
+
+    #[arg(long)]
+    pub logs: bool,
+
+    #[arg(short, long, default_value = DEFAULT_BISECT_STATE_PATH, help = "resumable state path")]
+    pub state: String,
+
+    #[arg(long, help = "discard the bisect state and start over")]
+    pub reset: bool,
+
+    #[arg(last = true, help = "check command, defaults to the health checks in the adm settings")]
+    pub check: Vec<String>,
+}
+impl ArgsDispatcher<Error> for Bisect {
+    fn dispatch(&self) -> Result<()> {
+        let checks: Vec<Box<dyn HealthCheck>> = if self.check.is_empty() {
+            adm_settings().health_checks()
+        } else {
+            vec![Box::new(CommandSucceeds { command: self.check.clone() })]
+        };
+        if checks.is_empty() {
+            return Err(Error::ConfigurationError(
+                "bisect requires a check command or health checks in the adm settings".to_string(),
+            ));
+        }
+        let state_path = iocore::Path::raw(&self.state);
+        if self.reset && state_path.is_file() {
+            state_path.delete()?;
+        }
+        let state = bisect_smart(
+            &self.user.resolve()?,
+            !self.verbose || output_format().is_structured(),
+            self.services.clone(),
+            adm_settings().include_non_needed(),
+            self.logs,
+            &state_path,
+            &checks,
+        )?;
+        let mut rows = Vec::<BisectRow>::new();
+        for ((domain, service, _), culprit) in state
+            .culprits
+            .iter()
+            .map(|target| (target, true))
+            .chain(state.cleared.iter().map(|target| (target, false)))
+        {
+            rows.push(BisectRow { target: format!("{}/{}", domain, service), culprit });
+        }
+        print_output(&rows, |rows| {
+            let mut text = format!(
+                "{} services left turned off after {} steps\n",
+                state.cleared.len(),
+                state.steps
+            );
+            if state.culprits.is_empty() {
+                text.push_str("no culprits found\n");
+            }
+            for row in rows.iter().filter(|row| row.culprit) {
+                text.push_str(&format!("culprit: {}\n", row.target));
+            }
+            text
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BisectRow {
+    pub target: String,
+    pub culprit: bool,
+}
+impl Row for BisectRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("TARGET", Left), ("CULPRIT", Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.target.to_string(), self.culprit.to_string()]
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Enforce {
+    #[command(flatten)]
+    pub user: UserArgs,
+
+    #[arg(short, long)]
+    pub verbose: bool,
+
+    #[arg(long, help = "enforce the policy once and exit")]
+    pub once: bool,
+
+    #[arg(short, long, help = "seconds between runs, defaults to the policy interval")]
+    pub interval: Option<u64>,
+
+    #[arg(short = 'n', long, help = "only report the deviations from the policy")]
+    pub dry_run: bool,
+
+    #[arg(long, help = "install a LaunchAgent that enforces the policy periodically")]
+    pub install: bool,
+}
+impl ArgsDispatcher<Error> for Enforce {
+    fn dispatch(&self) -> Result<()> {
+        let policy = adm_settings().policy();
+        let interval = self.interval.unwrap_or_else(|| policy.interval());
+        if policy.disabled.is_empty() && policy.bootout.is_empty() {
+            return Err(Error::ConfigurationError(
+                "enforce requires a policy in the adm settings".to_string(),
+            ));
+        }
+        let uid = self.user.resolve()?;
+        if self.install {
+            print_rows(&[PathRow::from(install_enforce_launch_agent(&uid, interval)?)])?;
+            return Ok(());
+        }
+        let quiet = !self.verbose || output_format().is_structured();
+        loop {
+            let corrections = enforce_policy(&uid, quiet, &policy, self.dry_run)?;
+            let rows = corrections.iter().map(EnforceRow::from).collect::<Vec<EnforceRow>>();
+            print_output(&rows, |rows| {
+                if self.dry_run {
+                    rows.iter().map(|row| format!("{}\n", row.deviation)).collect::<String>()
+                } else {
+                    String::new()
+                }
+            })?;
+            if self.once || self.dry_run {
+                return Ok(());
+            }
+            std::thread::sleep(std::time::Duration::from_secs(interval));
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnforceRow {
+    pub deviation: PolicyDeviation,
+    pub corrected: bool,
+    pub error: Option<Error>,
+}
+impl From<&(PolicyDeviation, Result<()>)> for EnforceRow {
+    fn from((deviation, result): &(PolicyDeviation, Result<()>)) -> EnforceRow {
+        EnforceRow {
+            deviation: deviation.clone(),
+            corrected: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        }
+    }
+}
+impl Row for EnforceRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("TARGET", Left), ("DEVIATION", Left), ("CORRECTED", Left), ("ERROR", Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.deviation.target(),
+            self.deviation.to_string(),
+            self.corrected.to_string(),
+            self.error.as_ref().map(|error| error.to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Create {
+    #[arg(long, help = "TOML spec of the agent or daemon, overridden by the other flags")]
+    pub spec: Option<String>,
+
+    #[arg(short, long)]
+    pub label: Option<String>,
+
+    #[arg(short, long, value_enum)]
+    pub kind: Option<LaunchdKind>,
+
+    #[arg(short, long, help = "StartInterval in seconds")]
+    pub interval: Option<u64>,
+
+    #[arg(short, long, help = "StartCalendarInterval, e.g.: hour=3,minute=30")]
+    pub calendar: Vec<CalendarInterval>,
 
+    #[arg(long)]
+    pub run_at_load: bool,
+
+    #[arg(long)]
+    pub keep_alive: bool,
+
+    #[arg(long)]
+    pub stdout: Option<String>,
+
+    #[arg(long)]
+    pub stderr: Option<String>,
+
+    #[arg(short, long, help = "environment variable as KEY=VALUE")]
+    pub env: Vec<String>,
+
+    #[arg(short, long, value_enum, default_value = "xml")]
+    pub format: PlistFormat,
+
+    #[arg(long, help = "write the plist into its launchd search root instead of stdout")]
+    pub install: bool,
+
+    #[arg(long, help = "install agents into /Library/LaunchAgents rather than ~/Library")]
+    pub system: bool,
+
+    #[arg(short, long, help = "bootstrap the installed plist via launchctl")]
+    pub bootstrap: bool,
+
+    #[command(flatten)]
+    pub user: UserArgs,
+
+    #[arg(last = true)]
+    pub program_arguments: Vec<String>,
+}
+impl Create {
+    pub fn launchd_plist_spec(&self) -> Result<LaunchdPlistSpec> {
+        let mut spec = match &self.spec {
+            Some(path) => LaunchdPlistSpec::from_toml(&iocore::Path::new(path).read()?)?,
+            None => LaunchdPlistSpec::default(),
+        };
+        if let Some(label) = &self.label {
+            spec.label = label.to_string();
+        }
+        if let Some(kind) = self.kind {
+            spec.kind = kind;
+        }
+        if !self.program_arguments.is_empty() {
+            spec.program_arguments = self.program_arguments.clone();
+        }
+        if self.interval.is_some() {
+            spec.start_interval = self.interval;
+        }
+        if !self.calendar.is_empty() {
+            spec.start_calendar_interval = self.calendar.clone();
+        }
+        if self.run_at_load {
+            spec.run_at_load = Some(true);
+        }
+        if self.keep_alive {
+            spec.keep_alive = Some(true);
+        }
+        if self.stdout.is_some() {
+            spec.standard_out_path = self.stdout.clone();
+        }
+        if self.stderr.is_some() {
+            spec.standard_error_path = self.stderr.clone();
+        }
+        for pair in &self.env {
+            let (key, value) = pair.split_once('=').ok_or_else(|| {
+                Error::ConfigurationError(format!("{:#?} is not KEY=VALUE", pair))
+            })?;
+            spec.environment_variables.insert(key.to_string(), value.to_string());
+        }
+        spec.validate()?;
+        Ok(spec)
+    }
+}
+impl ArgsDispatcher<Error> for Create {
+    fn dispatch(&self) -> Result<()> {
+        use std::io::Write;
+        let spec = self.launchd_plist_spec()?;
+        if self.install {
+            let path = install_launchd_plist(
+                &self.user.resolve()?,
+                &spec,
+                self.format,
+                self.system,
+                self.bootstrap,
+            )?;
+            print_rows(&[PathRow::from(path)])?;
+        } else if output_format().is_structured() {
+            // binary plists have no place in text rows, which carry
+            // the XML one instead
+            print_rows(&[PlistRow {
+                label: spec.label.to_string(),
+                plist: String::from_utf8_lossy(&spec.to_bytes(PlistFormat::Xml)?).to_string(),
+            }])?;
+        } else {
+            std::io::stdout().write_all(&spec.to_bytes(self.format)?)?;
+        }
         Ok(())
     }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlistRow {
+    pub label: String,
+    pub plist: String,
+}
+impl Row for PlistRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("LABEL", Left), ("PLIST", Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.label.to_string(), self.plist.to_string()]
+    }
+}
 
 #[derive(Args, Debug)]
 pub struct BootUp {
     #[arg()]
     pub services: Vec<String>,
 
-    #[arg(long, default_value = "501")]
-    pub uid: Uid,
+    #[command(flatten)]
+    pub user: UserArgs,
 
     #[arg(short, long)]
     pub quiet: bool,
 
-    #[arg(short, long)]
-    pub display_warnings: bool,
+    #[arg(short, long, num_args = 0..=1, default_missing_value = "true")]
+    pub display_warnings: Option<bool>,
 
-    #[arg(short, long)]
-    pub include_non_needed: bool,
+    #[arg(short, long, num_args = 0..=1, default_missing_value = "true")]
+    pub include_non_needed: Option<bool>,
 }
 impl ArgsDispatcher<Error> for BootUp {
     fn dispatch(&self) -> Result<()> {
-        boot_up_smart(&self.uid, self.quiet, self.services.clone(), self.include_non_needed);
+        let results = boot_up_smart(
+            &self.user.resolve()?,
+            self.quiet || output_format().is_structured(),
+            self.services.clone(),
+            adm_settings().include_non_needed(),
+        );
+        print_output(&results.iter().map(ActionRow::from).collect::<Vec<ActionRow>>(), |_| {
+            String::new()
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Tui {
+    #[command(flatten)]
+    pub user: UserArgs,
+
+    #[arg(short, long, default_value_t = DEFAULT_TUI_REFRESH_SECS)]
+    pub refresh: u64,
+
+    #[arg(short, long, help = "journal of changes, defaults to the backup path/adm-journal.jsonl")]
+    pub journal: Option<String>,
+}
+impl Tui {
+    pub fn journal_path(&self) -> iocore::Path {
+        match &self.journal {
+            Some(path) => iocore::Path::new(path),
+            None => adm_config().backup_path().join(TUI_JOURNAL_FILENAME),
+        }
+    }
+}
+impl ArgsDispatcher<Error> for Tui {
+    fn dispatch(&self) -> Result<()> {
+        run_tui(&self.user.resolve()?, Duration::from_secs(self.refresh), &self.journal_path())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Config {
+    /// Prints the effective adm settings and where each value comes from
+    Show,
+    /// Validates the settings files, printing each problem with its position
+    Check,
+}
+impl SubcommandDispatcher<Error> for Config {
+    fn dispatch(&self) -> Result<()> {
+        match self {
+            Config::Show => print_rows(&resolve_adm_config(&[])?.entries())?,
+            Config::Check => print_diagnostics(&check_settings(&SystemFacts::detect()))?,
+        }
         Ok(())
     }
 }
+impl Row for ConfigEntry {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("KEY", Left), ("VALUE", Left), ("SOURCE", Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.key.to_string(), self.value.to_string(), self.source.to_string()]
+    }
+}
+
+/// Privileged helper started by `adm --broker` through `sudo`. Reads
+/// the session token from stdin.
+#[derive(Args, Debug)]
+pub struct Broker {
+    #[arg(long)]
+    pub socket: String,
+
+    #[arg(long)]
+    pub client_uid: Option<u32>,
+
+    #[arg(long, default_value = DEFAULT_BROKER_AUDIT_LOG)]
+    pub audit_log: String,
+}
+impl ArgsDispatcher<Error> for Broker {
+    fn dispatch(&self) -> Result<()> {
+        let mut token = String::new();
+        std::io::stdin().read_line(&mut token)?;
+        let token = token.trim();
+        if token.is_empty() {
+            return Err(Error::AuthorizationError("no broker token in stdin".to_string()));
+        }
+        let broker = crate::Broker::new(
+            token,
+            command_runner(),
+            Some(iocore::Path::new(&self.audit_log)),
+        );
+        broker.serve(&iocore::Path::new(&self.socket), self.client_uid)
+    }
+}
 
 #[derive(Args, Debug)]
 pub struct List {
-    #[arg(short, long, default_value = "501")]
+    #[arg(short, long)]
     pub uid: Option<Uid>,
 
     #[arg(short, long)]
@@ -115,19 +715,29 @@ pub struct List {
 }
 impl ArgsDispatcher<Error> for List {
     fn dispatch(&self) -> Result<()> {
-        let data = list_agents_and_daemons()?
+        let rows = list_agents_and_daemons()?
             .iter()
-            .map(|(label, path)| vec![label.to_string(), path.to_string()])
-            .collect::<Vec<Vec<String>>>();
-        let table = Table::new()
-            .headers(&["SERVICE", "PATH"])
-            .alignments(&[Left, Left])
-            .data(&data)
-            .to_string();
-        print!("{table}");
+            .map(|(label, path)| ServiceRow { service: label.to_string(), path: path.to_string() })
+            .collect::<Vec<ServiceRow>>();
+        print_rows(&rows)?;
         Ok(())
     }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceRow {
+    pub service: String,
+    pub path: String,
+}
+impl Row for ServiceRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("SERVICE", Left), ("PATH", Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.service.to_string(), self.path.to_string()]
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct Path {
     #[arg()]
@@ -135,14 +745,16 @@ pub struct Path {
 }
 impl ArgsDispatcher<Error> for Path {
     fn dispatch(&self) -> Result<()> {
-        for (label, path) in list_agents_and_daemons()? {
-            if label.as_str() == self.label.as_str() {
-                println!("{}", path.to_string());
-            }
-        }
+        let rows = list_agents_and_daemons()?
+            .into_iter()
+            .filter(|(label, _)| label.as_str() == self.label.as_str())
+            .map(|(_, path)| PathRow::from(path))
+            .collect::<Vec<PathRow>>();
+        print_rows(&rows)?;
         Ok(())
     }
 }
+
 #[derive(Args, Debug)]
 pub struct Status {
     #[arg(short, long, help = "list all agents and daemons off and on")]
@@ -150,46 +762,394 @@ pub struct Status {
 
     #[arg(short = 'p', long = "path")]
     pub include_path: bool,
+
+    #[arg(short, long, help = "display why launchd started each running service")]
+    pub reason: bool,
+
+    #[arg(short, long, value_enum)]
+    pub domain: Option<DomainKind>,
+
+    #[command(flatten)]
+    pub user: UserArgs,
+
+    #[arg(long, conflicts_with = "stopped")]
+    pub running: bool,
+
+    #[arg(long)]
+    pub stopped: bool,
+
+    #[arg(long, conflicts_with = "enabled")]
+    pub disabled: bool,
+
+    #[arg(long)]
+    pub enabled: bool,
+
+    #[arg(long, help = "only services whose last exit status is not zero")]
+    pub crashed: bool,
+
+    #[arg(short, long, help = "label substring or glob, e.g.: 'com.apple.*agent'")]
+    pub label: Option<String>,
+
+    #[arg(short, long, help = "category from the service catalog, e.g.: non-needed, bluetooth")]
+    pub category: Option<String>,
+
+    #[arg(short, long, value_enum, default_value = "label")]
+    pub sort: StatusSort,
+
+    #[arg(long, help = "display memory, cpu time, threads and start time of running services")]
+    pub resources: bool,
+
+    #[arg(
+        long,
+        value_delimiter = ',',
+        help = "comma-separated columns: service,pid,domain,status,enabled,reason,category,path,\
+                rss,cpu,threads,started"
+    )]
+    pub columns: Vec<String>,
+}
+impl Status {
+    pub fn status_filter(&self, uid: &Uid) -> StatusFilter {
+        let stopped = self.stopped || self.disabled || self.crashed;
+        StatusFilter {
+            domain: self.domain,
+            uid: (self.user.uid.is_some() || self.user.user.is_some()).then_some(*uid),
+            running: if self.running {
+                Some(true)
+            } else if self.stopped {
+                Some(false)
+            } else if self.all || stopped {
+                None
+            } else {
+                Some(true)
+            },
+            enabled: if self.enabled {
+                Some(true)
+            } else if self.disabled {
+                Some(false)
+            } else {
+                None
+            },
+            crashed: self.crashed,
+            label: self.label.clone(),
+            category: self.category.clone(),
+        }
+    }
+
+    pub fn status_columns(&self) -> Result<Vec<&'static str>> {
+        if self.columns.is_empty() {
+            let mut columns = vec!["SERVICE", "PID", "DOMAIN", "STATUS"];
+            if self.reason {
+                columns.push("REASON");
+            }
+            if self.resources {
+                columns.extend(RESOURCE_COLUMNS);
+            }
+            if self.include_path {
+                columns.push("PATH");
+            }
+            return Ok(columns);
+        }
+        let known = StatusRow::columns();
+        let mut columns = Vec::<&'static str>::new();
+        for name in &self.columns {
+            match known.iter().find(|(column, _)| column.eq_ignore_ascii_case(name.trim())) {
+                Some((column, _)) => columns.push(column),
+                None => {
+                    return Err(Error::ConfigurationError(format!(
+                        "unknown column {:#?}, expected one of: {}",
+                        name,
+                        known
+                            .iter()
+                            .map(|(column, _)| column.to_lowercase())
+                            .collect::<Vec<String>>()
+                            .join(", ")
+                    )));
+                },
+            }
+        }
+        Ok(columns)
+    }
 }
 impl ArgsDispatcher<Error> for Status {
     fn dispatch(&self) -> Result<()> {
-        let uid = Uid::from(iocore::User::id()?.uid);
-        let mut ads = list_all_agents_and_daemons(&uid)?
-            .iter()
-            .filter(|(_, _, pid, _, _, _)| if self.all { true } else { *pid != 0 })
-            .map(|(domain, service, pid, status, _enabled, info)| {
-                vec![
-                    service.to_string(),
-                    pid.to_string(),
-                    domain.to_string(),
-                    status.map(|h| h.to_string()).unwrap_or_else(|| "-".to_string()),
-                    info.clone().map(|(path, _)| path.to_string()).unwrap_or_default(),
-                ]
-            })
-            .collect::<Vec<Vec<String>>>();
-        ads.sort_by_key(|service| service[0].to_string());
-        if self.include_path {
-            let table = Table::new()
-                .headers(&["SERVICE", "PID", "DOMAIN", "STATUS", "PATH"])
-                .alignments(&[Left, Left, Left, Left, Left])
-                .data(&ads)
-                .to_string();
-
-            print!("{table}");
+        let columns = self.status_columns()?;
+        let catalog = ServiceCatalog::builtin();
+        if let Some(category) =
+            self.category.as_ref().filter(|category| !catalog.known_categories().contains(category))
+        {
+            return Err(Error::ConfigurationError(format!(
+                "unknown category {:#?}, expected one of: {}",
+                category,
+                catalog.known_categories().join(", ")
+            )));
+        }
+        let uid = self.user.resolve()?;
+        let with_reason = self.reason || columns.contains(&"REASON");
+        let statuses = filter_and_sort_statuses(
+            service_statuses(&list_all_agents_and_daemons(&uid)?, &catalog),
+            &self.status_filter(&uid),
+            self.sort,
+        );
+        let mut resources = if columns.iter().any(|column| RESOURCE_COLUMNS.contains(column)) {
+            process_resources(&running_pids(&statuses))?
         } else {
-            let ads = ads
-                .iter()
-                .map(|ad| (0..4).map(|h| ad[h].to_string()).collect::<Vec<String>>())
-                .collect::<Vec<Vec<String>>>();
-            let table = Table::new()
-                .headers(&["SERVICE", "PID", "DOMAIN", "STATUS"])
-                .alignments(&[Left, Left, Right, Left])
-                .data(&ads)
-                .to_string();
+            BTreeMap::new()
+        };
+        let rows = statuses
+            .into_iter()
+            .map(|status| StatusRow {
+                reason: if with_reason && status.running() {
+                    launchctl_blame(&format!("{}/{}", status.domain, status.service))
+                        .map(|blame| blame.to_string())
+                        .ok()
+                } else {
+                    None
+                },
+                resources: resources.remove(&status.pid),
+                service: status.service,
+                pid: status.pid,
+                domain: status.domain,
+                status: status.status,
+                enabled: status.enabled,
+                categories: status.categories,
+                path: status.path,
+            })
+            .collect::<Vec<StatusRow>>();
+        print_columns(&rows, &columns)?;
+        Ok(())
+    }
+}
+const RESOURCE_COLUMNS: [&str; 4] = ["RSS", "CPU", "THREADS", "STARTED"];
+fn running_pids(statuses: &[ServiceStatus]) -> Vec<i64> {
+    let mut pids = statuses
+        .iter()
+        .filter(|status| status.running())
+        .map(|status| status.pid)
+        .collect::<Vec<i64>>();
+    pids.sort();
+    pids.dedup();
+    pids
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StatusRow {
+    pub service: String,
+    pub pid: i64,
+    pub domain: String,
+    pub status: Option<i64>,
+    pub enabled: bool,
+    pub reason: Option<String>,
+    pub categories: Vec<String>,
+    pub path: Option<String>,
+    pub resources: Option<ProcessResources>,
+}
+impl Row for StatusRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![
+            ("SERVICE", Left),
+            ("PID", Left),
+            ("DOMAIN", Right),
+            ("STATUS", Left),
+            ("ENABLED", Left),
+            ("REASON", Left),
+            ("CATEGORY", Left),
+            ("PATH", Left),
+            ("RSS", Right),
+            ("CPU", Right),
+            ("THREADS", Right),
+            ("STARTED", Left),
+        ]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.service.to_string(),
+            self.pid.to_string(),
+            self.domain.to_string(),
+            self.status.map(|h| h.to_string()).unwrap_or_else(|| "-".to_string()),
+            if self.enabled { "enabled" } else { "disabled" }.to_string(),
+            self.reason.clone().unwrap_or_else(|| "-".to_string()),
+            self.categories.join(","),
+            self.path.clone().unwrap_or_default(),
+            self.resource(ProcessResources::rss),
+            self.resource(ProcessResources::cpu_time),
+            self.resource(|resources| resources.threads.to_string()),
+            self.resource(ProcessResources::start_time),
+        ]
+    }
+}
+impl StatusRow {
+    fn resource(&self, cell: impl Fn(&ProcessResources) -> String) -> String {
+        self.resources.as_ref().map(cell).unwrap_or_else(|| "-".to_string())
+    }
+}
+
+/// Running services ranked by what they cost, most expensive first.
+#[derive(Args, Debug)]
+pub struct Top {
+    #[arg(short, long, value_enum, default_value = "memory")]
+    pub by: ResourceSort,
+
+    #[arg(short = 'n', long, default_value = "20", help = "number of services to display")]
+    pub limit: usize,
+
+    #[arg(short, long, value_enum)]
+    pub domain: Option<DomainKind>,
+
+    #[arg(short, long, help = "label substring or glob, e.g.: 'com.apple.*agent'")]
+    pub label: Option<String>,
+
+    #[arg(short, long, help = "category from the service catalog, e.g.: non-needed, bluetooth")]
+    pub category: Option<String>,
+
+    #[command(flatten)]
+    pub user: UserArgs,
+}
+impl ArgsDispatcher<Error> for Top {
+    fn dispatch(&self) -> Result<()> {
+        let uid = self.user.resolve()?;
+        let filter = StatusFilter {
+            domain: self.domain,
+            running: Some(true),
+            label: self.label.clone(),
+            category: self.category.clone(),
+            ..StatusFilter::default()
+        };
+        let statuses = filter_and_sort_statuses(
+            service_statuses(&list_all_agents_and_daemons(&uid)?, &ServiceCatalog::builtin()),
+            &filter,
+            StatusSort::Label,
+        );
+        let resources = process_resources(&running_pids(&statuses))?;
+        let rows = rank_by_resources(statuses, &resources, self.by)
+            .into_iter()
+            .take(self.limit)
+            .map(|(status, resources)| StatusRow {
+                service: status.service,
+                pid: status.pid,
+                domain: status.domain,
+                status: status.status,
+                enabled: status.enabled,
+                reason: None,
+                categories: status.categories,
+                path: status.path,
+                resources: Some(resources),
+            })
+            .collect::<Vec<StatusRow>>();
+        print_columns(&rows, &["SERVICE", "PID", "DOMAIN", "RSS", "CPU", "THREADS", "STARTED"])?;
+        Ok(())
+    }
+}
+
+/// Audit report of the agents, daemons and preferences of this
+/// machine, e.g.: to hand to a security reviewer.
+#[derive(Args, Debug)]
+pub struct Report {
+    #[arg(short, long, value_enum, default_value = "md")]
+    pub format: ReportFormat,
 
-            print!("{table}");
+    #[arg(short, long, help = "write the report here instead of printing it")]
+    pub output_path: Option<iocore::Path>,
+
+    #[command(flatten)]
+    pub user: UserArgs,
+
+    #[arg(short, long)]
+    pub quiet: bool,
+}
+impl ArgsDispatcher<Error> for Report {
+    fn dispatch(&self) -> Result<()> {
+        let report = AuditReport::collect(&self.user.resolve()?, self.quiet)?;
+        let rendered = report.render(self.format);
+        match &self.output_path {
+            Some(path) => {
+                path.write(rendered.as_bytes())?;
+                if !self.quiet {
+                    eprintln!("report written to {}", path);
+                }
+            },
+            None => {
+                print!("{}", rendered);
+            },
         }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Why {
+    #[arg()]
+    pub label: String,
 
+    #[command(flatten)]
+    pub user: UserArgs,
+}
+impl ArgsDispatcher<Error> for Why {
+    fn dispatch(&self) -> Result<()> {
+        let uid = self.user.resolve()?;
+        let mut targets = list_all_agents_and_daemons(&uid)?
+            .iter()
+            .filter(|(_, service, _, _, _, _)| service.as_str() == self.label.as_str())
+            .map(|(domain, service, pid, status, _, _)| {
+                (format!("{}/{}", domain, service), *pid, *status)
+            })
+            .collect::<Vec<(String, i64, Option<i64>)>>();
+        if targets.is_empty() {
+            targets.extend(
+                launchctl_list()?
+                    .iter()
+                    .filter(|entry| entry.label.as_str() == self.label.as_str())
+                    .map(|entry| {
+                        (
+                            format!("{}/{}", agent_or_daemon_prefix(Some(uid), true), entry.label),
+                            entry.pid.unwrap_or_default(),
+                            Some(entry.status),
+                        )
+                    }),
+            );
+        }
+        if targets.is_empty() {
+            return Err(Error::LaunchdError(format!(
+                "service {:#?} not found in any domain",
+                &self.label
+            )));
+        }
+        let rows = targets
+            .into_iter()
+            .map(|(target, pid, status)| {
+                let (reason, error) = match launchctl_blame(&target) {
+                    Ok(blame) => (Some(blame.to_string()), None),
+                    Err(error) => (None, Some(error)),
+                };
+                WhyRow { target, pid, status, reason, error }
+            })
+            .collect::<Vec<WhyRow>>();
+        print_rows(&rows)?;
         Ok(())
     }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WhyRow {
+    pub target: String,
+    pub pid: i64,
+    pub status: Option<i64>,
+    pub reason: Option<String>,
+    pub error: Option<Error>,
+}
+impl Row for WhyRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("TARGET", Left), ("PID", Left), ("STATUS", Left), ("REASON", Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.target.to_string(),
+            self.pid.to_string(),
+            self.status.map(|h| h.to_string()).unwrap_or_else(|| "-".to_string()),
+            match (&self.reason, &self.error) {
+                (Some(reason), _) => reason.to_string(),
+                (None, Some(error)) => format!("- ({})", error),
+                (None, None) => "-".to_string(),
+            },
+        ]
+    }
+}
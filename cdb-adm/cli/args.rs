@@ -0,0 +1,24 @@
+use clap::Args;
+
+use crate::{Result, Uid, UserDirectory};
+
+// Whose launchd domains a command acts on, the console user unless
+// `--uid` or `--user` says otherwise. Kept out of doc comments, which
+// clap would take as the about text of every subcommand flattening it.
+#[derive(Args, Debug, Clone, Default)]
+pub struct UserArgs {
+    #[arg(long, help = "uid of the user, defaults to the one logged in at the console")]
+    pub uid: Option<Uid>,
+
+    #[arg(long, conflicts_with = "uid", help = "name of the user instead of its uid")]
+    pub user: Option<String>,
+}
+impl UserArgs {
+    pub fn resolve(&self) -> Result<Uid> {
+        match (self.uid, self.user.as_deref()) {
+            (Some(uid), None) => Ok(uid),
+            (None, None) => Uid::console(),
+            (uid, user) => UserDirectory::detect()?.resolve(uid, user),
+        }
+    }
+}
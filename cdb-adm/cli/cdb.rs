@@ -1,10 +1,21 @@
+use std::collections::BTreeMap;
+use std::fmt::Alignment;
+
 use clap::{Args, Parser, Subcommand};
 use iocore::Path;
+use serde::{Deserialize, Serialize};
 
-use crate::cli::{ArgsDispatcher, ParserDispatcher, SubcommandDispatcher};
+use crate::cli::{
+    print_diagnostics, print_output, print_rows, report_error, set_output_format, ArgsDispatcher,
+    OutputFormat, ParserDispatcher, PathRow, Row, SubcommandDispatcher, UserArgs,
+};
 use crate::{
-    coredata_fix, delete_domains, export_domains, export_library_preferences, list_domains, Error,
-    Result,
+    check_settings, coredata_fix, delete_exported_domains, export_domains,
+    export_library_preferences, list_domains, read_backup, read_exported_domains,
+    render_initial_settings, restore_domains, rotate_backup_key, user_settings_path, write_backup,
+    write_backup_with_key, write_initial_settings, BackupKey, CDBSettings,
+    DeleteDefaultsMacOSResult, EncryptedBundle, Error, Result, SettingsEnvPath, SettingsInventory,
+    SystemFacts,
 };
 
 #[derive(Parser, Debug)]
@@ -12,11 +23,14 @@ use crate::{
 pub struct Cli {
     #[command(subcommand)]
     pub command: Command,
+
+    #[arg(long, global = true, value_enum, default_value = "table")]
+    pub output: OutputFormat,
 }
 impl ParserDispatcher<Error> for Cli {
     fn dispatch(&self) -> Result<()> {
-        self.command.dispatch()?;
-        Ok(())
+        set_output_format(self.output);
+        report_error(self.command.dispatch())
     }
 }
 
@@ -26,7 +40,13 @@ pub enum Command {
     Fix(Fix),
 
     Export(Export),
+    Restore(Restore),
+    Decrypt(Decrypt),
     List(List),
+    #[command(subcommand)]
+    Config(Config),
+    #[command(subcommand)]
+    Key(Key),
 }
 impl SubcommandDispatcher<Error> for Command {
     fn dispatch(&self) -> Result<()> {
@@ -35,11 +55,57 @@ impl SubcommandDispatcher<Error> for Command {
             Command::Delete(op) => op.dispatch()?,
             Command::Fix(op) => op.dispatch()?,
             Command::Export(op) => op.dispatch()?,
+            Command::Restore(op) => op.dispatch()?,
+            Command::Decrypt(op) => op.dispatch()?,
+            Command::Config(op) => op.dispatch()?,
+            Command::Key(op) => op.dispatch()?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Config {
+    /// Validates the settings files, printing each problem with its position
+    Check,
+    /// Writes settings proposed from the domains and disabled services
+    Init(Init),
+}
+impl SubcommandDispatcher<Error> for Config {
+    fn dispatch(&self) -> Result<()> {
+        match self {
+            Config::Check => print_diagnostics(&check_settings(&SystemFacts::detect()))?,
+            Config::Init(op) => op.dispatch()?,
         }
         Ok(())
     }
 }
 
+#[derive(Args, Debug)]
+pub struct Init {
+    #[command(flatten)]
+    pub user: UserArgs,
+
+    #[arg(short, long, help = "overwrite the settings file if it exists")]
+    pub force: bool,
+
+    #[arg(short, long, help = "print the settings instead of writing them")]
+    pub print: bool,
+}
+impl ArgsDispatcher<Error> for Init {
+    fn dispatch(&self) -> Result<()> {
+        let text = render_initial_settings(&SettingsInventory::detect(&self.user.resolve()?)?);
+        if self.print {
+            print!("{}", text);
+            return Ok(());
+        }
+        let path = user_settings_path();
+        write_initial_settings(&path, &text, self.force)?;
+        print_rows(&[PathRow::from(path)])?;
+        Ok(())
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct Fix {
     #[arg(short, long)]
@@ -50,10 +116,34 @@ pub struct Fix {
 }
 impl ArgsDispatcher<Error> for Fix {
     fn dispatch(&self) -> Result<()> {
-        coredata_fix(self.quiet, self.dry_run)?;
+        let rows = coredata_fix(self.quiet, self.dry_run)?
+            .into_iter()
+            .map(|args| DefaultsRow { command: args.join(" "), applied: !self.dry_run })
+            .collect::<Vec<DefaultsRow>>();
+        print_output(&rows, |rows| {
+            if self.dry_run {
+                rows.iter().map(|row| format!("defaults {}\n", row.command)).collect::<String>()
+            } else {
+                String::new()
+            }
+        })?;
         Ok(())
     }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DefaultsRow {
+    pub command: String,
+    pub applied: bool,
+}
+impl Row for DefaultsRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("COMMAND", Alignment::Left), ("APPLIED", Alignment::Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.command.to_string(), self.applied.to_string()]
+    }
+}
 
 #[derive(Args, Debug)]
 pub struct Export {
@@ -68,6 +158,9 @@ pub struct Export {
 
     #[arg(short, long)]
     pub output_path: Option<Path>,
+
+    #[arg(short, long, help = "encrypt the output with the backup key in the keychain")]
+    pub encrypt: bool,
 }
 impl ArgsDispatcher<Error> for Export {
     fn dispatch(&self) -> Result<()> {
@@ -85,9 +178,17 @@ impl ArgsDispatcher<Error> for Export {
         }
 
         let data = serde_json::to_string_pretty(&result)?;
+        let encrypt = encrypt_backups(self.encrypt);
         match &self.output_path {
             Some(path) => {
-                path.write(data.as_bytes())?;
+                write_backup(path, &data, encrypt)?;
+            },
+            None if encrypt => {
+                let key = BackupKey::current()?;
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&EncryptedBundle::seal(&key, data.as_bytes())?)?
+                );
             },
             None => {
                 println!("{}", data);
@@ -101,12 +202,31 @@ impl ArgsDispatcher<Error> for Export {
 pub struct List {}
 impl ArgsDispatcher<Error> for List {
     fn dispatch(&self) -> Result<()> {
-        for domain in list_domains()? {
-            println!("{}", &domain);
-        }
+        let rows = list_domains()?
+            .iter()
+            .map(|domain| DomainRow { domain: domain.to_string() })
+            .collect::<Vec<DomainRow>>();
+        print_rows(&rows)?;
         Ok(())
     }
 }
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainRow {
+    pub domain: String,
+}
+impl Row for DomainRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("DOMAIN", Alignment::Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.domain.to_string()]
+    }
+
+    fn plain() -> bool {
+        true
+    }
+}
 #[derive(Args, Debug)]
 pub struct Delete {
     #[arg()]
@@ -114,22 +234,174 @@ pub struct Delete {
 
     #[arg(short, long)]
     pub output_path: Path,
+
+    #[arg(short, long, help = "encrypt the output with the backup key in the keychain")]
+    pub encrypt: bool,
 }
 impl ArgsDispatcher<Error> for Delete {
     fn dispatch(&self) -> Result<()> {
         if self.output_path.exists() {
-            eprintln!("{} exists", &self.output_path);
-            std::process::exit(1);
+            return Err(Error::IOError(format!("{} exists", &self.output_path)));
         }
-        let result = delete_domains(
-            &self
-                .domains
-                .iter()
-                .filter(|domain| !domain.is_empty())
-                .map(|domain| domain.as_str())
-                .collect::<Vec<&str>>(),
-        )?;
-        self.output_path.write(serde_json::to_string_pretty(&result)?.as_bytes())?;
+        let domains = self
+            .domains
+            .iter()
+            .filter(|domain| !domain.is_empty())
+            .map(|domain| domain.as_str())
+            .collect::<Vec<&str>>();
+        let key = if encrypt_backups(self.encrypt) { Some(BackupKey::current()?) } else { None };
+        let write = |result: &DeleteDefaultsMacOSResult| -> Result<()> {
+            let data = serde_json::to_string_pretty(result)?;
+            match &key {
+                Some(key) => write_backup_with_key(&self.output_path, &data, key),
+                None => write_backup(&self.output_path, &data, false),
+            }
+        };
+        // nothing is deleted before the export of every domain is
+        // backed up, the backup is then rewritten with the outcome
+        let exported = DeleteDefaultsMacOSResult {
+            domain_map: export_domains(&domains, true)?,
+            errors: BTreeMap::new(),
+        };
+        write(&exported)?;
+        let result = delete_exported_domains(&domains, exported.domain_map);
+        write(&result)?;
+        let rows = domains
+            .iter()
+            .map(|domain| DomainResultRow {
+                domain: domain.to_string(),
+                ok: !result.errors.contains_key(*domain),
+                error: result.errors.get(*domain).cloned(),
+            })
+            .collect::<Vec<DomainResultRow>>();
+        print_output(&rows, |_| String::new())?;
+        Ok(())
+    }
+}
+
+/// Whether backups are encrypted, either because of `--encrypt` or of
+/// the `cdb.encrypt_backups` setting.
+fn encrypt_backups(flag: bool) -> bool {
+    flag || CDBSettings::cli(true).encrypt_backups()
+}
+
+#[derive(Args, Debug)]
+pub struct Restore {
+    #[arg(help = "output file of `cdb export` or `cdb delete`, encrypted or not")]
+    pub path: Path,
+
+    #[arg(help = "domains to restore, all of them when none is given")]
+    pub domains: Vec<String>,
+}
+impl ArgsDispatcher<Error> for Restore {
+    fn dispatch(&self) -> Result<()> {
+        let exported = read_exported_domains(&self.path)?;
+        let domains = self
+            .domains
+            .iter()
+            .filter(|domain| !domain.is_empty())
+            .map(|domain| domain.as_str())
+            .collect::<Vec<&str>>();
+        let rows = restore_domains(&exported, &domains)
+            .iter()
+            .map(DomainResultRow::from)
+            .collect::<Vec<DomainResultRow>>();
+        print_rows(&rows)?;
+        Ok(())
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DomainResultRow {
+    pub domain: String,
+    pub ok: bool,
+    pub error: Option<Error>,
+}
+impl From<&(String, Result<()>)> for DomainResultRow {
+    fn from((domain, result): &(String, Result<()>)) -> DomainResultRow {
+        DomainResultRow {
+            domain: domain.to_string(),
+            ok: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        }
+    }
+}
+impl Row for DomainResultRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("DOMAIN", Alignment::Left), ("OK", Alignment::Left), ("ERROR", Alignment::Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.domain.to_string(),
+            self.ok.to_string(),
+            self.error.as_ref().map(|error| error.to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Decrypt {
+    #[arg(help = "encrypted export, delete or snapshot file")]
+    pub path: Path,
+
+    #[arg(short, long, help = "write the plaintext here instead of printing it")]
+    pub output_path: Option<Path>,
+}
+impl ArgsDispatcher<Error> for Decrypt {
+    fn dispatch(&self) -> Result<()> {
+        let data = read_backup(&self.path)?;
+        match &self.output_path {
+            Some(path) => {
+                write_backup(path, &data, false)?;
+                print_output(&[PathRow::from(path.clone())], |_| String::new())?;
+            },
+            None => {
+                let row = PlaintextRow { path: self.path.to_string(), plaintext: data };
+                print_output(&[row], |rows| format!("{}\n", rows[0].plaintext))?;
+            },
+        }
+        Ok(())
+    }
+}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlaintextRow {
+    pub path: String,
+    pub plaintext: String,
+}
+impl Row for PlaintextRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("PATH", Alignment::Left), ("PLAINTEXT", Alignment::Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.path.to_string(), self.plaintext.to_string()]
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Key {
+    /// Makes a new backup key current, re-encrypting the given bundles with it
+    Rotate(Rotate),
+}
+impl SubcommandDispatcher<Error> for Key {
+    fn dispatch(&self) -> Result<()> {
+        match self {
+            Key::Rotate(op) => op.dispatch()?,
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct Rotate {
+    #[arg(help = "bundles to re-encrypt, plaintext ones being left untouched")]
+    pub paths: Vec<Path>,
+}
+impl ArgsDispatcher<Error> for Rotate {
+    fn dispatch(&self) -> Result<()> {
+        let (key, paths) = rotate_backup_key(&self.paths)?;
+        eprintln!("backup key {} is now current", key.id());
+        print_rows(&paths.into_iter().map(PathRow::from).collect::<Vec<PathRow>>())?;
         Ok(())
     }
 }
@@ -0,0 +1,347 @@
+use std::fmt::Alignment;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use verynicetable::Table;
+
+use crate::{Diagnostic, Error, Result};
+
+static OUTPUT_FORMAT: RwLock<OutputFormat> = RwLock::new(OutputFormat::Table);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Jsonl,
+    Csv,
+    Tsv,
+}
+impl OutputFormat {
+    /// Whether the output is meant for machines, in which case
+    /// commands keep their progress messages out of stdout.
+    pub fn is_structured(&self) -> bool {
+        *self != OutputFormat::Table
+    }
+}
+
+pub fn set_output_format(format: OutputFormat) {
+    if let Ok(mut current) = OUTPUT_FORMAT.write() {
+        *current = format;
+    }
+}
+pub fn output_format() -> OutputFormat {
+    OUTPUT_FORMAT.read().map(|format| *format).unwrap_or_default()
+}
+
+/// Typed result of a subcommand: serialized as-is in JSON and JSON
+/// lines and rendered through [`Row::columns`] and [`Row::cells`] in
+/// tables, CSV and TSV.
+pub trait Row: Serialize {
+    fn columns() -> Vec<(&'static str, Alignment)>;
+    fn cells(&self) -> Vec<String>;
+    /// Tables of rows of plain types are printed as bare lines
+    /// without headers, e.g.: `adm path` and `cdb list`.
+    fn plain() -> bool {
+        false
+    }
+}
+
+/// Renders `rows` in `format` restricted to the table, CSV and TSV
+/// columns named in `columns`, or all of them when it is empty.
+pub fn render_rows<R: Row>(format: OutputFormat, rows: &[R], columns: &[&str]) -> Result<String> {
+    let selected = R::columns()
+        .iter()
+        .enumerate()
+        .filter(|(_, (name, _))| columns.is_empty() || columns.contains(name))
+        .map(|(index, (name, alignment))| (index, *name, *alignment))
+        .collect::<Vec<(usize, &str, Alignment)>>();
+    let data = rows
+        .iter()
+        .map(|row| {
+            let cells = row.cells();
+            selected.iter().map(|(index, _, _)| cells[*index].to_string()).collect()
+        })
+        .collect::<Vec<Vec<String>>>();
+    let headers = selected.iter().map(|(_, name, _)| *name).collect::<Vec<&str>>();
+    Ok(match format {
+        OutputFormat::Table if R::plain() =>
+            data.iter().map(|cells| format!("{}\n", cells.join(" "))).collect::<String>(),
+        OutputFormat::Table if data.is_empty() => String::new(),
+        OutputFormat::Table => Table::new()
+            .headers(&headers)
+            .alignments(&selected.iter().map(|(_, _, alignment)| *alignment).collect::<Vec<_>>())
+            .data(&data)
+            .to_string(),
+        OutputFormat::Json => format!("{}\n", serde_json::to_string_pretty(rows)?),
+        OutputFormat::Jsonl => {
+            let mut lines = String::new();
+            for row in rows {
+                lines.push_str(&serde_json::to_string(row)?);
+                lines.push('\n');
+            }
+            lines
+        },
+        OutputFormat::Csv => delimited(&headers, &data, ',', csv_escape),
+        OutputFormat::Tsv => delimited(&headers, &data, '\t', tsv_escape),
+    })
+}
+
+/// Renders `rows` in `format`, except for tables where `text` renders
+/// them for people instead, e.g.: the summary of `adm bisect`.
+pub fn render_output<R: Row>(
+    format: OutputFormat,
+    rows: &[R],
+    text: impl FnOnce(&[R]) -> String,
+) -> Result<String> {
+    match format {
+        OutputFormat::Table => Ok(text(rows)),
+        format => render_rows(format, rows, &[]),
+    }
+}
+
+pub fn print_rows<R: Row>(rows: &[R]) -> Result<()> {
+    print_columns(rows, &[])
+}
+pub fn print_columns<R: Row>(rows: &[R], columns: &[&str]) -> Result<()> {
+    print!("{}", render_rows(output_format(), rows, columns)?);
+    Ok(())
+}
+pub fn print_output<R: Row>(rows: &[R], text: impl FnOnce(&[R]) -> String) -> Result<()> {
+    print!("{}", render_output(output_format(), rows, text)?);
+    Ok(())
+}
+
+fn delimited(
+    headers: &[&str],
+    data: &[Vec<String>],
+    separator: char,
+    escape: fn(&str) -> String,
+) -> String {
+    let mut lines = String::new();
+    let separator = separator.to_string();
+    lines.push_str(&headers.iter().map(|h| escape(h)).collect::<Vec<String>>().join(&separator));
+    lines.push('\n');
+    for cells in data {
+        lines.push_str(&cells.iter().map(|h| escape(h)).collect::<Vec<String>>().join(&separator));
+        lines.push('\n');
+    }
+    lines
+}
+fn csv_escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+fn tsv_escape(cell: &str) -> String {
+    cell.replace('\\', "\\\\").replace('\t', "\\t").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+impl Row for Error {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("KIND", Alignment::Left), ("MESSAGE", Alignment::Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.variant(), self.message()]
+    }
+}
+
+/// Prints the error of a failed subcommand in the current output
+/// format before handing it back to `main`, which prints it to stderr.
+pub fn report_error(result: Result<()>) -> Result<()> {
+    if let Err(error) = &result {
+        print_output(std::slice::from_ref(error), |_| String::new())?;
+    }
+    result
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ActionRow {
+    pub target: String,
+    pub pid: i64,
+    pub ok: bool,
+    pub error: Option<Error>,
+}
+impl From<&(String, String, i64, Result<()>)> for ActionRow {
+    fn from((domain, service, pid, result): &(String, String, i64, Result<()>)) -> ActionRow {
+        ActionRow {
+            target: format!("{}/{}", domain, service),
+            pid: *pid,
+            ok: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        }
+    }
+}
+impl Row for ActionRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![
+            ("TARGET", Alignment::Left),
+            ("PID", Alignment::Right),
+            ("OK", Alignment::Left),
+            ("ERROR", Alignment::Left),
+        ]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.target.to_string(),
+            self.pid.to_string(),
+            self.ok.to_string(),
+            self.error.as_ref().map(|error| error.to_string()).unwrap_or_default(),
+        ]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathRow {
+    pub path: String,
+}
+impl From<iocore::Path> for PathRow {
+    fn from(path: iocore::Path) -> PathRow {
+        PathRow { path: path.to_string() }
+    }
+}
+impl Row for PathRow {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![("PATH", Alignment::Left)]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![self.path.to_string()]
+    }
+
+    fn plain() -> bool {
+        true
+    }
+}
+
+impl Row for Diagnostic {
+    fn columns() -> Vec<(&'static str, Alignment)> {
+        vec![
+            ("FILE", Alignment::Left),
+            ("LINE", Alignment::Right),
+            ("COLUMN", Alignment::Right),
+            ("SEVERITY", Alignment::Left),
+            ("KEY", Alignment::Left),
+            ("MESSAGE", Alignment::Left),
+        ]
+    }
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.file.to_string(),
+            self.line.to_string(),
+            self.column.to_string(),
+            self.severity.to_string(),
+            self.key.to_string(),
+            self.message.to_string(),
+        ]
+    }
+}
+
+/// Prints the diagnostics of `config check`, failing when any of them
+/// is an error.
+pub fn print_diagnostics(diagnostics: &[Diagnostic]) -> Result<()> {
+    print_rows(diagnostics)?;
+    match diagnostics.iter().filter(|diagnostic| diagnostic.is_error()).count() {
+        0 => Ok(()),
+        1 => Err(Error::ConfigurationError("1 error in the settings".to_string())),
+        errors => Err(Error::ConfigurationError(format!("{} errors in the settings", errors))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Alignment;
+
+    use serde::Serialize;
+
+    use super::{render_output, render_rows, OutputFormat, Row};
+    use crate::Error;
+
+    #[derive(Serialize)]
+    struct Service {
+        service: String,
+        pid: i64,
+        status: Option<i64>,
+    }
+    impl Row for Service {
+        fn columns() -> Vec<(&'static str, Alignment)> {
+            vec![
+                ("SERVICE", Alignment::Left),
+                ("PID", Alignment::Right),
+                ("STATUS", Alignment::Left),
+            ]
+        }
+
+        fn cells(&self) -> Vec<String> {
+            vec![
+                self.service.to_string(),
+                self.pid.to_string(),
+                self.status.map(|h| h.to_string()).unwrap_or_else(|| "-".to_string()),
+            ]
+        }
+    }
+
+    fn services() -> Vec<Service> {
+        vec![
+            Service { service: "com.apple.Siri.agent".to_string(), pid: 612, status: Some(0) },
+            Service { service: "weird, \"label\"\tx".to_string(), pid: 0, status: None },
+        ]
+    }
+
+    #[test]
+    fn test_render_json_and_jsonl() {
+        assert_eq!(
+            render_rows(OutputFormat::Jsonl, &services(), &[]).unwrap(),
+            concat!(
+                "{\"service\":\"com.apple.Siri.agent\",\"pid\":612,\"status\":0}\n",
+                "{\"service\":\"weird, \\\"label\\\"\\tx\",\"pid\":0,\"status\":null}\n",
+            )
+        );
+        let json = render_rows(OutputFormat::Json, &services(), &["SERVICE"]).unwrap();
+        let value = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+        assert_eq!(value[1]["status"], serde_json::Value::Null);
+        assert_eq!(value[0]["pid"], 612);
+    }
+
+    #[test]
+    fn test_render_csv_and_tsv() {
+        assert_eq!(
+            render_rows(OutputFormat::Csv, &services(), &[]).unwrap(),
+            "SERVICE,PID,STATUS\ncom.apple.Siri.agent,612,0\n\"weird, \"\"label\"\"\tx\",0,-\n"
+        );
+        assert_eq!(
+            render_rows(OutputFormat::Tsv, &services(), &["SERVICE", "STATUS"]).unwrap(),
+            "SERVICE\tSTATUS\ncom.apple.Siri.agent\t0\nweird, \"label\"\\tx\t-\n"
+        );
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = render_rows(OutputFormat::Table, &services(), &["SERVICE", "PID"]).unwrap();
+        assert!(table.starts_with("SERVICE"));
+        assert!(table.contains("com.apple.Siri.agent"));
+        assert!(!table.contains("STATUS"));
+        assert_eq!(render_rows::<Service>(OutputFormat::Table, &[], &[]).unwrap(), "");
+    }
+
+    #[test]
+    fn test_render_output() {
+        let text = |services: &[Service]| format!("{} services\n", services.len());
+        assert_eq!(render_output(OutputFormat::Table, &services(), text).unwrap(), "2 services\n");
+        assert_eq!(
+            render_output(OutputFormat::Csv, &services(), text).unwrap(),
+            render_rows(OutputFormat::Csv, &services(), &[]).unwrap()
+        );
+        let errors = [Error::LaunchdError("service not found".to_string())];
+        assert_eq!(
+            render_output(OutputFormat::Jsonl, &errors, |_| String::new()).unwrap(),
+            "{\"kind\":\"LaunchdError\",\"message\":\"service not found\"}\n"
+        );
+    }
+}
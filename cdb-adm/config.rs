@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::{ADMSettings, Error, Result};
+
+/// Environment variables overriding adm settings, by settings key.
+pub const ADM_ENV_VARS: [(&str, &str); 7] = [
+    ("backup-path", "CDB_ADM_BACKUP_PATH"),
+    ("display_warnings", "ADM_DISPLAY_WARNINGS"),
+    ("include_non_needed", "ADM_INCLUDE_NON_NEEDED"),
+    ("include_system_uids", "ADM_INCLUDE_SYSTEM_UIDS"),
+    ("execution.timeout_secs", "ADM_TIMEOUT_SECS"),
+    ("execution.retries", "ADM_RETRIES"),
+    ("execution.concurrency", "ADM_CONCURRENCY"),
+];
+
+static ADM_CONFIG: RwLock<Option<ResolvedConfig>> = RwLock::new(None);
+
+/// Where the effective value of a settings key comes from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case", tag = "kind", content = "origin")]
+pub enum ConfigSource {
+    Default,
+    File(String),
+    Env(String),
+    Flag(String),
+}
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default"),
+            ConfigSource::File(path) => write!(f, "file {}", path),
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+            ConfigSource::Flag(name) => write!(f, "flag {}", name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigEntry {
+    pub key: String,
+    pub value: toml::Value,
+    pub source: ConfigSource,
+}
+
+/// Settings flattened into dotted keys, each with the source that set
+/// it last. Arrays of the defaults are replaced, other arrays appended
+/// to, like [`merge_tables`](crate::merge_tables) does.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ResolvedConfig {
+    entries: BTreeMap<String, (toml::Value, ConfigSource)>,
+}
+impl ResolvedConfig {
+    pub fn with_defaults<T: Serialize>(defaults: &T) -> Result<ResolvedConfig> {
+        let mut config = ResolvedConfig::default();
+        match toml::Value::try_from(defaults) {
+            Ok(toml::Value::Table(table)) => config.apply_table(&table, &ConfigSource::Default),
+            Ok(_) => {},
+            Err(error) => return Err(Error::SettingsError(format!("default settings: {}", error))),
+        }
+        Ok(config)
+    }
+
+    pub fn apply_table(&mut self, table: &toml::Table, source: &ConfigSource) {
+        self.apply_prefixed("", table, source)
+    }
+
+    fn apply_prefixed(&mut self, prefix: &str, table: &toml::Table, source: &ConfigSource) {
+        for (name, value) in table {
+            let key = format!("{}{}", prefix, name);
+            if let toml::Value::Table(table) = value {
+                self.apply_prefixed(&format!("{}.", key), table, source);
+                continue;
+            }
+            match (self.entries.get_mut(&key), value) {
+                (Some((toml::Value::Array(current), current_source)), toml::Value::Array(values))
+                    if *current_source != ConfigSource::Default =>
+                {
+                    crate::append_new(current, values);
+                    *current_source = source.clone();
+                },
+                (_, value) => self.set(&key, value.clone(), source.clone()),
+            }
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: toml::Value, source: ConfigSource) {
+        self.entries.insert(key.to_string(), (value, source));
+    }
+
+    /// Sets each key of `vars` whose environment variable is defined,
+    /// parsing it like the value it overrides.
+    pub fn apply_env(
+        &mut self,
+        vars: &[(&str, &str)],
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<()> {
+        for (key, name) in vars {
+            if let Some(raw) = lookup(name) {
+                let value = parse_like(self.get(key), &raw).map_err(|error| {
+                    Error::ConfigurationError(format!("{}={:#?}: {}", name, raw, error))
+                })?;
+                self.set(key, value, ConfigSource::Env(name.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&toml::Value> {
+        self.entries.get(key).map(|(value, _)| value)
+    }
+
+    pub fn source(&self, key: &str) -> Option<&ConfigSource> {
+        self.entries.get(key).map(|(_, source)| source)
+    }
+
+    pub fn entries(&self) -> Vec<ConfigEntry> {
+        self.entries
+            .iter()
+            .map(|(key, (value, source))| ConfigEntry {
+                key: key.to_string(),
+                value: value.clone(),
+                source: source.clone(),
+            })
+            .collect()
+    }
+
+    pub fn to_table(&self) -> toml::Table {
+        let mut table = toml::Table::new();
+        for (key, (value, _)) in &self.entries {
+            let mut names = key.split('.').collect::<Vec<&str>>();
+            let last = names.pop().unwrap_or_default();
+            let mut current = &mut table;
+            for name in names {
+                current = match current
+                    .entry(name.to_string())
+                    .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+                {
+                    toml::Value::Table(table) => table,
+                    _ => unreachable!("{} is both a value and a table", name),
+                };
+            }
+            current.insert(last.to_string(), value.clone());
+        }
+        table
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        toml::Value::Table(self.to_table())
+            .try_into()
+            .map_err(|error| Error::SettingsError(format!("effective settings: {}", error)))
+    }
+
+    pub fn adm_settings(&self) -> Result<ADMSettings> {
+        self.deserialize()
+    }
+
+    pub fn backup_path(&self) -> iocore::Path {
+        match self.get("backup-path") {
+            Some(toml::Value::String(path)) => iocore::Path::raw(path).try_canonicalize(),
+            _ => iocore::Path::cwd(),
+        }
+    }
+}
+
+fn parse_like(current: Option<&toml::Value>, raw: &str) -> Result<toml::Value> {
+    match current {
+        Some(toml::Value::Boolean(_)) => match raw.trim().to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(toml::Value::Boolean(true)),
+            "0" | "false" | "no" | "off" => Ok(toml::Value::Boolean(false)),
+            _ => Err(Error::ParseError("expected a boolean".to_string())),
+        },
+        Some(toml::Value::Integer(_)) => Ok(toml::Value::Integer(raw.trim().parse::<i64>()?)),
+        _ => Ok(toml::Value::String(raw.to_string())),
+    }
+}
+
+/// Effective adm settings: `flags` override the environment, which
+/// overrides the `[adm]` section and `backup-path` of the
+/// [`settings_layers`](crate::settings_layers), which override the
+/// defaults. Arrays of the settings files are appended to one another.
+pub fn resolve_adm_config(flags: &[(&str, &str, toml::Value)]) -> Result<ResolvedConfig> {
+    let mut config = ResolvedConfig::with_defaults(&ADMSettings::default())?;
+    config.set(
+        "backup-path",
+        toml::Value::String(crate::settings::DEFAULT_BACKUP_PATH.to_string()),
+        ConfigSource::Default,
+    );
+    for layer in crate::settings_layers()? {
+        for name in ["backup-path", "backup_path"] {
+            if let Some(value) = layer.table.get(name) {
+                config.set("backup-path", value.clone(), layer.source.clone());
+            }
+        }
+        if let Some(toml::Value::Table(adm)) = layer.table.get("adm") {
+            config.apply_table(adm, &layer.source);
+        }
+    }
+    config.apply_env(&ADM_ENV_VARS, |name| iocore::env::var(name).ok())?;
+    for (key, flag, value) in flags {
+        config.set(key, value.clone(), ConfigSource::Flag(flag.to_string()));
+    }
+    config.adm_settings()?;
+    Ok(config)
+}
+
+pub fn set_adm_config(config: ResolvedConfig) {
+    if let Ok(mut current) = ADM_CONFIG.write() {
+        *current = Some(config);
+    }
+}
+/// The adm settings set by [`set_adm_config`] or the defaults.
+pub fn adm_config() -> ResolvedConfig {
+    ADM_CONFIG
+        .read()
+        .ok()
+        .and_then(|config| config.clone())
+        .or_else(|| ResolvedConfig::with_defaults(&ADMSettings::default()).ok())
+        .unwrap_or_default()
+}
+pub fn adm_settings() -> ADMSettings {
+    adm_config().adm_settings().unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConfigSource, ResolvedConfig};
+    use crate::ADMSettings;
+
+    #[test]
+    fn test_later_layers_override_earlier_ones_per_key() {
+        let mut config = ResolvedConfig::with_defaults(&ADMSettings::default()).unwrap();
+        let file = ConfigSource::File("adm.toml".to_string());
+        config.apply_table(
+            &toml::from_str("include_non_needed = true\n[execution]\nconcurrency = 8\n").unwrap(),
+            &file,
+        );
+        let env = [
+            ("include_non_needed", "ADM_INCLUDE_NON_NEEDED"),
+            ("execution.retries", "ADM_RETRIES"),
+        ];
+        config
+            .apply_env(&env, |name| (name == "ADM_RETRIES").then(|| "3".to_string()))
+            .unwrap();
+        config.set(
+            "include_non_needed",
+            toml::Value::Boolean(false),
+            ConfigSource::Flag("--include-non-needed".to_string()),
+        );
+
+        let settings = config.adm_settings().unwrap();
+        assert!(!settings.include_non_needed());
+        assert_eq!(settings.execution().concurrency, 8);
+        assert_eq!(settings.execution().retries, 3);
+        assert_eq!(config.source("execution.concurrency"), Some(&file));
+        assert_eq!(
+            config.source("execution.retries"),
+            Some(&ConfigSource::Env("ADM_RETRIES".to_string()))
+        );
+        assert_eq!(config.source("display_warnings"), Some(&ConfigSource::Default));
+    }
+
+    #[test]
+    fn test_env_values_must_match_the_type_they_override() {
+        let mut config = ResolvedConfig::with_defaults(&ADMSettings::default()).unwrap();
+        let env = [("include_system_uids", "ADM_INCLUDE_SYSTEM_UIDS")];
+        assert!(config.apply_env(&env, |_| Some("maybe".to_string())).is_err());
+        config.apply_env(&env, |_| Some("no".to_string())).unwrap();
+        assert!(!config.adm_settings().unwrap().include_system_uids());
+    }
+}
@@ -1,9 +1,9 @@
 use std::collections::BTreeMap;
-use std::io::{pipe, Write};
-use std::process::{Command, Stdio};
 
 use crate::{to_slice_str, to_vec_string, Error, Result, Settings, SettingsEnvPath};
 
+pub const DEFAULTS_PATH: &str = "/usr/bin/defaults";
+
 pub fn defaults_write(domain: impl std::fmt::Display, key: &[&str]) -> Result<plist::Value> {
     validate_domain_path_for_current_user(&domain)?;
     let mut args = vec!["write".to_string(), domain.to_string()];
@@ -60,9 +60,14 @@ pub fn defaults_delete(key: &[&str]) -> Result<()> {
     }
 }
 pub fn delete_domains(domains: &[&str]) -> Result<DeleteDefaultsMacOSResult> {
+    Ok(delete_exported_domains(domains, export_domains(domains, true)?))
+}
+/// Deletes `domains` once `domain_map`, their export, is backed up.
+pub fn delete_exported_domains(
+    domains: &[&str],
+    mut domain_map: BTreeMap<String, (plist::Value, Option<iocore::Path>)>,
+) -> DeleteDefaultsMacOSResult {
     let mut errors = BTreeMap::<String, Error>::new();
-    let mut domain_map = export_domains(domains, true)?;
-
     for domain in domains {
         match defaults_delete_domain(&domain) {
             Ok((domain, plist)) => {
@@ -77,7 +82,7 @@ pub fn delete_domains(domains: &[&str]) -> Result<DeleteDefaultsMacOSResult> {
             },
         }
     }
-    Ok(DeleteDefaultsMacOSResult { domain_map, errors })
+    DeleteDefaultsMacOSResult { domain_map, errors }
 }
 
 use serde::{Deserialize, Serialize};
@@ -148,30 +153,12 @@ pub fn defaults(args: &[&str]) -> Result<(i64, String, String)> {
     }
 }
 pub fn defaults_ok(args: &[&str], stdin: Option<Vec<u8>>) -> Result<(i64, String, String)> {
-    let mut cmd = Command::new("defaults");
-    let cmd = cmd.current_dir("/System");
-    let cmd = cmd.args(args);
-    let cmd = cmd.stdin(match stdin {
-        Some(bytes) => {
-            let (read_bytes, mut write_bytes) = pipe()?;
-            write_bytes.write_all(&bytes)?;
-            Stdio::from(read_bytes)
-        },
-        None => Stdio::null(),
-    });
-    let cmd = cmd.stdout(Stdio::piped());
-    let cmd = cmd.stderr(Stdio::piped());
-    let child = cmd.spawn()?;
-    let output = child.wait_with_output()?;
-    let exit_code: i64 = output.status.code().unwrap_or_default().into();
-    Ok((
-        exit_code,
-        String::from_utf8(output.stdout).unwrap_or_default(),
-        String::from_utf8(output.stderr).unwrap_or_default(),
-    ))
+    crate::command_runner().run_with_input(DEFAULTS_PATH, args, stdin.as_deref())
 }
 
-pub fn coredata_fix(quiet: bool, dry_run: bool) -> Result<()> {
+/// Resets the preferences known to misbehave and returns the arguments
+/// of each `defaults` command run, or that would run with `dry_run`.
+pub fn coredata_fix(quiet: bool, dry_run: bool) -> Result<Vec<Vec<String>>> {
     use iocore::Path;
     let settings = Settings::cli(quiet);
     let user_preferences = Path::raw("~/Library/Preferences").try_canonicalize();
@@ -213,28 +200,17 @@ pub fn coredata_fix(quiet: bool, dry_run: bool) -> Result<()> {
         }),
     )?;
 
-    for args in settings.cdb().defaults_exec_args() {
-        if dry_run {
-            println!("defaults {}", args.join(""));
-        } else {
+    let mut commands = settings.cdb().defaults_exec_args();
+    commands.extend(defaults_exec_args().into_iter().map(|args| to_vec_string!(args)));
+    if !dry_run {
+        for args in &commands {
             defaults_ok(to_slice_str!(args), None)?;
             if !quiet {
                 eprintln!("defaults {} -", args.join(" "));
             }
         }
     }
-    for args in defaults_exec_args() {
-        if dry_run {
-            println!("defaults {}", args.join(""));
-        } else {
-            defaults_ok(&args, None)?;
-            if !quiet {
-                eprintln!("defaults {} -", args.join(" "));
-            }
-        }
-    }
-
-    Ok(())
+    Ok(commands)
 }
 fn defaults_exec_args<'a>() -> Vec<Vec<&'a str>> {
     use iocore::Path;
@@ -1052,7 +1028,8 @@ fn defaults_exec_args<'a>() -> Vec<Vec<&'a str>> {
         ],
     ]
 }
-#[cfg(test)]
+// reads the launchd plists and preferences of the running system
+#[cfg(all(test, feature = "macos", target_os = "macos"))]
 mod tests {
     use std::collections::BTreeMap;
 
@@ -1114,3 +1091,16 @@ pub fn defaults_import_json(domain: impl std::fmt::Display, json: serde_json::Va
     }
     Ok(())
 }
+pub fn defaults_import(domain: impl std::fmt::Display, value: &plist::Value) -> Result<()> {
+    validate_domain_path_for_current_user(&domain)?;
+    let mut bytes = Vec::<u8>::new();
+    value.to_writer_xml(&mut bytes)?;
+    let (exit_code, _, err) = defaults_ok(&["import", &domain.to_string(), "-"], Some(bytes))?;
+    if exit_code != 0 {
+        return Err(Error::IOError(format!(
+            "defaults import {} failed[{}]: {}",
+            &domain, exit_code, err
+        )));
+    }
+    Ok(())
+}
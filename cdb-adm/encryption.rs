@@ -0,0 +1,236 @@
+use std::io::Read;
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use serde::{Deserialize, Serialize};
+
+use crate::{is_secret_not_found, Error, KeychainData, Result};
+
+/// Keychain service holding the id of the key that new bundles are
+/// encrypted with, each key being stored under
+/// `cdb-adm-backup-key.<id>`.
+pub const BACKUP_KEY_SERVICE: &str = "cdb-adm-backup-key";
+pub const ENCRYPTED_BUNDLE_FORMAT: &str = "cdb-adm-encrypted-bundle";
+pub const ENCRYPTED_BUNDLE_VERSION: u32 = 1;
+const KEY_LEN: usize = 32;
+const KEY_ID_LEN: usize = 8;
+const NONCE_LEN: usize = 12;
+
+/// ChaCha20-Poly1305 key of encrypted backups, kept in the keychain
+/// through [`KeychainData`].
+#[derive(Clone, PartialEq, Eq)]
+pub struct BackupKey {
+    id: String,
+    bytes: Vec<u8>,
+}
+impl std::fmt::Debug for BackupKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "BackupKey[id: {:#?}]", &self.id)
+    }
+}
+impl BackupKey {
+    pub fn new(id: impl std::fmt::Display, bytes: &[u8]) -> Result<BackupKey> {
+        if bytes.len() != KEY_LEN {
+            return Err(Error::EncryptionError(format!(
+                "backup key must be {} bytes long, got {}",
+                KEY_LEN,
+                bytes.len()
+            )));
+        }
+        Ok(BackupKey { id: id.to_string(), bytes: bytes.to_vec() })
+    }
+
+    pub fn generate() -> Result<BackupKey> {
+        BackupKey::new(hex::encode(random_bytes(KEY_ID_LEN)?), &random_bytes(KEY_LEN)?)
+    }
+
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    /// Key that new bundles are encrypted with, generated and saved to
+    /// the keychain on first use. Any other keychain error is returned
+    /// rather than replacing a current key that could not be read.
+    pub fn current() -> Result<BackupKey> {
+        match KeychainData::get(BACKUP_KEY_SERVICE) {
+            Ok(current) => BackupKey::find(current.data()),
+            Err(error) if is_secret_not_found(&error) => BackupKey::rotate(),
+            Err(error) => Err(error),
+        }
+    }
+
+    pub fn find(id: impl std::fmt::Display) -> Result<BackupKey> {
+        let id = id.to_string();
+        let data = KeychainData::get(key_service(&id)).map_err(|error| {
+            Error::EncryptionError(format!("backup key {:#?} not found: {}", id, error))
+        })?;
+        BackupKey::new(
+            &id,
+            &hex::decode(data.data()).map_err(|error| {
+                Error::EncryptionError(format!("backup key {:#?} is not hex: {}", id, error))
+            })?,
+        )
+    }
+
+    pub fn save(&self) -> Result<()> {
+        KeychainData::new(key_service(&self.id), hex::encode(&self.bytes).as_bytes()).save()
+    }
+
+    pub fn make_current(&self) -> Result<()> {
+        KeychainData::new(BACKUP_KEY_SERVICE, self.id.as_bytes()).save()
+    }
+
+    pub fn delete(&self) -> Result<()> {
+        KeychainData::new(key_service(&self.id), &[]).delete()
+    }
+
+    /// Generates a key and makes it current. Earlier keys stay in the
+    /// keychain so that bundles encrypted with them can still be read.
+    pub fn rotate() -> Result<BackupKey> {
+        let key = BackupKey::generate()?;
+        key.save()?;
+        key.make_current()?;
+        Ok(key)
+    }
+
+    fn cipher(&self) -> ChaCha20Poly1305 {
+        ChaCha20Poly1305::new(Key::from_slice(&self.bytes))
+    }
+}
+
+fn key_service(id: &str) -> String {
+    format!("{}.{}", BACKUP_KEY_SERVICE, id)
+}
+
+pub(crate) fn random_bytes(len: usize) -> Result<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+/// JSON envelope written in place of a plaintext export, delete or
+/// snapshot file. The format, version and key id are authenticated
+/// along with the ciphertext.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EncryptedBundle {
+    pub format: String,
+    pub version: u32,
+    pub key_id: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+impl EncryptedBundle {
+    pub fn seal(key: &BackupKey, plaintext: &[u8]) -> Result<EncryptedBundle> {
+        let mut bundle = EncryptedBundle {
+            format: ENCRYPTED_BUNDLE_FORMAT.to_string(),
+            version: ENCRYPTED_BUNDLE_VERSION,
+            key_id: key.id(),
+            nonce: hex::encode(random_bytes(NONCE_LEN)?),
+            ciphertext: String::new(),
+        };
+        let nonce = bundle.nonce_bytes()?;
+        let ciphertext = key
+            .cipher()
+            .encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &bundle.aad() })
+            .map_err(|_| Error::EncryptionError("encrypting bundle".to_string()))?;
+        bundle.ciphertext = hex::encode(ciphertext);
+        Ok(bundle)
+    }
+
+    pub fn open(&self, key: &BackupKey) -> Result<Vec<u8>> {
+        if key.id != self.key_id {
+            return Err(Error::EncryptionError(format!(
+                "bundle is encrypted with key {:#?}, not {:#?}",
+                self.key_id, key.id
+            )));
+        }
+        let ciphertext = hex::decode(&self.ciphertext)
+            .map_err(|error| Error::EncryptionError(format!("ciphertext is not hex: {}", error)))?;
+        key.cipher()
+            .decrypt(
+                Nonce::from_slice(&self.nonce_bytes()?),
+                Payload { msg: &ciphertext, aad: &self.aad() },
+            )
+            .map_err(|_| {
+                Error::EncryptionError(format!(
+                    "bundle encrypted with key {:#?} was tampered with or the key is wrong",
+                    self.key_id
+                ))
+            })
+    }
+
+    /// The bundle `data` holds, or `None` when it is plaintext.
+    pub fn parse(data: &str) -> Option<EncryptedBundle> {
+        serde_json::from_str::<EncryptedBundle>(data)
+            .ok()
+            .filter(|bundle| bundle.format == ENCRYPTED_BUNDLE_FORMAT)
+    }
+
+    fn nonce_bytes(&self) -> Result<Vec<u8>> {
+        match hex::decode(&self.nonce) {
+            Ok(nonce) if nonce.len() == NONCE_LEN => Ok(nonce),
+            _ => Err(Error::EncryptionError(format!("invalid nonce {:#?}", self.nonce))),
+        }
+    }
+
+    fn aad(&self) -> Vec<u8> {
+        format!("{}:{}:{}", self.format, self.version, self.key_id).into_bytes()
+    }
+}
+
+/// Contents of a backup, decrypted with the key `find_key` returns
+/// for its id when it is an [`EncryptedBundle`].
+pub fn open_backup(data: &str, find_key: impl Fn(&str) -> Result<BackupKey>) -> Result<String> {
+    match EncryptedBundle::parse(data) {
+        Some(bundle) => {
+            let plaintext = bundle.open(&find_key(&bundle.key_id)?)?;
+            String::from_utf8(plaintext).map_err(|error| {
+                Error::EncryptionError(format!("decrypted bundle is not utf-8: {}", error))
+            })
+        },
+        None => Ok(data.to_string()),
+    }
+}
+
+/// Reads a backup written by [`write_backup`], looking up the key of
+/// encrypted bundles in the keychain.
+pub fn read_backup(path: &iocore::Path) -> Result<String> {
+    open_backup(&path.read()?, |id| BackupKey::find(id))
+}
+
+/// Writes `data` to `path`, encrypted with the
+/// [current](BackupKey::current) key when `encrypt` is set.
+pub fn write_backup(path: &iocore::Path, data: &str, encrypt: bool) -> Result<()> {
+    if encrypt {
+        write_backup_with_key(path, data, &BackupKey::current()?)
+    } else {
+        path.write(data.as_bytes())?;
+        Ok(())
+    }
+}
+
+pub fn write_backup_with_key(path: &iocore::Path, data: &str, key: &BackupKey) -> Result<()> {
+    let bundle = EncryptedBundle::seal(key, data.as_bytes())?;
+    path.write(serde_json::to_string_pretty(&bundle)?.as_bytes())?;
+    Ok(())
+}
+
+/// Rotates the backup key and re-encrypts the encrypted bundles among
+/// `paths` with it, leaving plaintext files untouched. Every bundle is
+/// decrypted before the key changes so that a single unreadable one
+/// aborts the rotation.
+pub fn rotate_backup_key(paths: &[iocore::Path]) -> Result<(BackupKey, Vec<iocore::Path>)> {
+    let mut bundles = Vec::<(iocore::Path, String)>::new();
+    for path in paths {
+        let data = path.read()?;
+        if EncryptedBundle::parse(&data).is_some() {
+            bundles.push((path.clone(), open_backup(&data, |id| BackupKey::find(id))?));
+        }
+    }
+    let key = BackupKey::rotate()?;
+    for (path, data) in &bundles {
+        write_backup_with_key(path, data, &key)?;
+    }
+    Ok((key, bundles.into_iter().map(|(path, _)| path).collect()))
+}
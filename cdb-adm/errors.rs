@@ -1,15 +1,28 @@
+use std::collections::BTreeMap;
 use std::fmt::Display;
 
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+use crate::LaunchctlOutcome;
+
+/// Serialized as `{"kind": "LaunchdError", "message": "..."}` wherever
+/// it appears, failures of launchctl adding the fields of
+/// [`Error::LaunchctlError`].
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Error {
     IOError(String),
     JsonError(String),
     LaunchdError(String),
-    LaunchdServiceNotRunning(String),
+    LaunchctlError {
+        outcome: LaunchctlOutcome,
+        target: Option<String>,
+        command: Vec<String>,
+        code: i64,
+        stderr: String,
+    },
     ParseIntError(String),
     KeychainError(String),
+    EncryptionError(String),
     PlistError(String),
     TomlError(String),
     CoreDataError(String),
@@ -17,41 +30,52 @@ pub enum Error {
     SystemError(String),
     ConfigurationError(String),
     SettingsError(String),
+    AuthorizationError(String),
+    TimeoutError(String),
 }
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(
-            f,
-            "{}: {}",
-            self.variant(),
-            match self {
-                Self::IOError(e) => e.to_string(),
-                Self::JsonError(e) => e.to_string(),
-                Self::LaunchdError(e) => e.to_string(),
-                Self::LaunchdServiceNotRunning(e) => e.to_string(),
-                Self::ParseIntError(e) => e.to_string(),
-                Self::KeychainError(e) => e.to_string(),
-                Self::PlistError(e) => e.to_string(),
-                Self::TomlError(e) => e.to_string(),
-                Self::CoreDataError(e) => e.to_string(),
-                Self::ParseError(e) => e.to_string(),
-                Self::SystemError(e) => e.to_string(),
-                Self::ConfigurationError(e) => e.to_string(),
-                Self::SettingsError(e) => e.to_string(),
-            }
-        )
+        write!(f, "{}: {}", self.variant(), self.message())
     }
 }
 
 impl Error {
+    pub fn message(&self) -> String {
+        match self {
+            Self::IOError(e) => e.to_string(),
+            Self::JsonError(e) => e.to_string(),
+            Self::LaunchdError(e) => e.to_string(),
+            Self::LaunchctlError { outcome, command, code, stderr, .. } => format!(
+                "`launchctl {}' failed with exit code {} ({}): {}",
+                command.join(" "),
+                code,
+                outcome,
+                stderr.trim()
+            ),
+            Self::ParseIntError(e) => e.to_string(),
+            Self::KeychainError(e) => e.to_string(),
+            Self::EncryptionError(e) => e.to_string(),
+            Self::PlistError(e) => e.to_string(),
+            Self::TomlError(e) => e.to_string(),
+            Self::CoreDataError(e) => e.to_string(),
+            Self::ParseError(e) => e.to_string(),
+            Self::SystemError(e) => e.to_string(),
+            Self::ConfigurationError(e) => e.to_string(),
+            Self::SettingsError(e) => e.to_string(),
+            Self::AuthorizationError(e) => e.to_string(),
+            Self::TimeoutError(e) => e.to_string(),
+        }
+    }
+
     pub fn variant(&self) -> String {
         match self {
             Error::IOError(_) => "IOError",
             Error::JsonError(_) => "JsonError",
             Error::LaunchdError(_) => "LaunchdError",
-            Error::LaunchdServiceNotRunning(_) => "LaunchdServiceNotRunning",
+            Error::LaunchctlError { .. } => "LaunchctlError",
             Error::ParseIntError(_) => "ParseIntError",
             Error::KeychainError(_) => "KeychainError",
+            Error::EncryptionError(_) => "EncryptionError",
             Error::PlistError(_) => "PlistError",
             Error::TomlError(_) => "TomlError",
             Error::CoreDataError(_) => "CoreDataError",
@@ -59,9 +83,128 @@ impl Error {
             Error::SystemError(_) => "SystemError",
             Error::ConfigurationError(_) => "ConfigurationError",
             Error::SettingsError(_) => "SettingsError",
+            Error::AuthorizationError(_) => "AuthorizationError",
+            Error::TimeoutError(_) => "TimeoutError",
         }
         .to_string()
     }
+
+    /// Failure of `launchctl args`, its target being the first argument
+    /// naming a domain or service.
+    pub fn launchctl(args: &[&str], code: i64, stderr: &str) -> Error {
+        Error::LaunchctlError {
+            outcome: LaunchctlOutcome::classify(code, stderr),
+            target: args
+                .iter()
+                .find(|arg| {
+                    **arg == "system"
+                        || ["system/", "gui/", "user/", "pid/"]
+                            .iter()
+                            .any(|prefix| arg.starts_with(prefix))
+                })
+                .map(|target| target.to_string()),
+            command: args.iter().map(|arg| arg.to_string()).collect(),
+            code,
+            stderr: stderr.to_string(),
+        }
+    }
+
+    pub fn launchctl_outcome(&self) -> Option<LaunchctlOutcome> {
+        match self {
+            Error::LaunchctlError { outcome, .. } => Some(*outcome),
+            _ => None,
+        }
+    }
+
+    /// Error of the variant named `kind`, the inverse of
+    /// [`Error::variant`] for the variants made of a message alone.
+    pub fn from_kind(kind: &str, message: String) -> Option<Error> {
+        Some(match kind {
+            "IOError" => Error::IOError(message),
+            "JsonError" => Error::JsonError(message),
+            "LaunchdError" => Error::LaunchdError(message),
+            "ParseIntError" => Error::ParseIntError(message),
+            "KeychainError" => Error::KeychainError(message),
+            "EncryptionError" => Error::EncryptionError(message),
+            "PlistError" => Error::PlistError(message),
+            "TomlError" => Error::TomlError(message),
+            "CoreDataError" => Error::CoreDataError(message),
+            "ParseError" => Error::ParseError(message),
+            "SystemError" => Error::SystemError(message),
+            "ConfigurationError" => Error::ConfigurationError(message),
+            "SettingsError" => Error::SettingsError(message),
+            "AuthorizationError" => Error::AuthorizationError(message),
+            "TimeoutError" => Error::TimeoutError(message),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ErrorData {
+    kind: String,
+    message: String,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    launchctl: Option<LaunchctlFailure>,
+}
+#[derive(Serialize, Deserialize)]
+struct LaunchctlFailure {
+    outcome: LaunchctlOutcome,
+    target: Option<String>,
+    command: Vec<String>,
+    code: i64,
+    stderr: String,
+}
+/// `{"Variant": "message"}` in which errors were serialized before,
+/// e.g.: in the `errors` of the backups of `cdb delete`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum LegacyErrorData {
+    Message(String),
+    Launchctl(LaunchctlFailure),
+}
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedError {
+    Data(ErrorData),
+    Legacy(BTreeMap<String, LegacyErrorData>),
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let launchctl = match self {
+            Error::LaunchctlError { outcome, target, command, code, stderr } =>
+                Some(LaunchctlFailure {
+                    outcome: *outcome,
+                    target: target.clone(),
+                    command: command.clone(),
+                    code: *code,
+                    stderr: stderr.to_string(),
+                }),
+            _ => None,
+        };
+        ErrorData { kind: self.variant(), message: self.message(), launchctl }.serialize(serializer)
+    }
+}
+impl<'de> Deserialize<'de> for Error {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Error, D::Error> {
+        let (kind, message, launchctl) = match SerializedError::deserialize(deserializer)? {
+            SerializedError::Data(data) => (data.kind, data.message, data.launchctl),
+            SerializedError::Legacy(map) => match map.into_iter().next() {
+                Some((kind, LegacyErrorData::Message(message))) => (kind, message, None),
+                Some((kind, LegacyErrorData::Launchctl(launchctl))) =>
+                    (kind, String::new(), Some(launchctl)),
+                None => return Err(serde::de::Error::custom("empty error")),
+            },
+        };
+        match (kind.as_str(), launchctl) {
+            ("LaunchctlError", Some(LaunchctlFailure { outcome, target, command, code, stderr })) =>
+                Ok(Error::LaunchctlError { outcome, target, command, code, stderr }),
+            (kind, _) => Error::from_kind(kind, message).ok_or_else(|| {
+                serde::de::Error::custom(format!("unknown error kind {:#?}", kind))
+            }),
+        }
+    }
 }
 
 impl std::error::Error for Error {}
@@ -90,6 +233,7 @@ impl From<serde_json::Error> for Error {
         Error::JsonError(e.to_string())
     }
 }
+#[cfg(all(feature = "macos", target_os = "macos"))]
 impl From<security_framework::base::Error> for Error {
     fn from(e: security_framework::base::Error) -> Self {
         Error::KeychainError(e.to_string())
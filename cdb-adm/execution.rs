@@ -0,0 +1,191 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, RwLock};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+pub const DEFAULT_COMMAND_TIMEOUT_SECS: u64 = 30;
+pub const DEFAULT_COMMAND_RETRIES: usize = 1;
+pub const DEFAULT_RETRY_DELAY_MILLIS: u64 = 500;
+pub const DEFAULT_CONCURRENCY: usize = 4;
+/// `EAGAIN` and `EALREADY`, which `launchctl` returns while launchd
+/// is still busy with a previous request for the same service. 113
+/// and 125 are not among them: the service is missing or does not
+/// support the request, which [`crate::LaunchctlOutcome`] tolerates
+/// and which running the command again would not change.
+pub const DEFAULT_RETRY_EXIT_CODES: [i64; 2] = [35, 37];
+/// What `sudo -n` reports when it would have to ask for a password.
+pub const SUDO_PASSWORD_REQUIRED: &str = "a password is required";
+
+static EXECUTION_SETTINGS: RwLock<Option<ExecutionSettings>> = RwLock::new(None);
+static SUDO_AUTHENTICATION: Mutex<()> = Mutex::new(());
+
+/// How privileged commands are executed: how long each may run, which
+/// exit codes are worth retrying and how many services are processed
+/// at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExecutionSettings {
+    pub timeout_secs: u64,
+    pub retries: usize,
+    pub retry_delay_millis: u64,
+    pub retry_exit_codes: Vec<i64>,
+    pub concurrency: usize,
+}
+impl Default for ExecutionSettings {
+    fn default() -> ExecutionSettings {
+        ExecutionSettings {
+            timeout_secs: DEFAULT_COMMAND_TIMEOUT_SECS,
+            retries: DEFAULT_COMMAND_RETRIES,
+            retry_delay_millis: DEFAULT_RETRY_DELAY_MILLIS,
+            retry_exit_codes: DEFAULT_RETRY_EXIT_CODES.to_vec(),
+            concurrency: DEFAULT_CONCURRENCY,
+        }
+    }
+}
+impl ExecutionSettings {
+    /// `None` when `timeout_secs` is 0, meaning no timeout.
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_secs > 0).then(|| Duration::from_secs(self.timeout_secs))
+    }
+
+    pub fn retry_delay(&self) -> Duration {
+        Duration::from_millis(self.retry_delay_millis)
+    }
+
+    fn should_retry(&self, result: &Result<(i64, String, String)>) -> bool {
+        match result {
+            Ok((exit_code, _, _)) => self.retry_exit_codes.contains(exit_code),
+            Err(Error::TimeoutError(_)) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+pub fn set_execution_settings(settings: ExecutionSettings) {
+    if let Ok(mut current) = EXECUTION_SETTINGS.write() {
+        *current = Some(settings);
+    }
+}
+pub fn execution_settings() -> ExecutionSettings {
+    EXECUTION_SETTINGS
+        .read()
+        .ok()
+        .and_then(|settings| settings.clone())
+        .unwrap_or_default()
+}
+
+/// Calls `run` with the configured timeout until it neither times out
+/// nor exits with a retryable code, at most `retries` more times.
+pub fn retry(
+    mut run: impl FnMut(Option<Duration>) -> Result<(i64, String, String)>,
+) -> Result<(i64, String, String)> {
+    let settings = execution_settings();
+    let mut attempt = 0;
+    loop {
+        let result = run(settings.timeout());
+        if attempt >= settings.retries || !settings.should_retry(&result) {
+            return result;
+        }
+        attempt += 1;
+        std::thread::sleep(settings.retry_delay());
+    }
+}
+
+/// Runs `program` through the current command runner with [`retry`].
+pub fn run_with_retries(program: &str, args: &[&str]) -> Result<(i64, String, String)> {
+    let runner = crate::command_runner();
+    retry(|timeout| runner.run_with_timeout(program, args, timeout))
+}
+
+/// Runs `sudo -n <args>` with [`run_with_retries`]. As `sudo` never
+/// prompts then, the timeout only counts the command itself. When the
+/// credentials of the user are missing or expired they are asked for
+/// with [`authenticate_sudo`] and the command is run again.
+pub fn run_sudo_with_retries(args: &[&str]) -> Result<(i64, String, String)> {
+    let argv = std::iter::once("-n").chain(args.iter().copied()).collect::<Vec<&str>>();
+    let result = run_with_retries("sudo", &argv)?;
+    match &result {
+        (1, _, stderr) if stderr.contains(SUDO_PASSWORD_REQUIRED) => {
+            authenticate_sudo()?;
+            run_with_retries("sudo", &argv)
+        },
+        _ => Ok(result),
+    }
+}
+
+/// Asks for the password of the user with `sudo -v`, without a
+/// timeout and one caller at a time, so that concurrent workers never
+/// prompt on the same terminal at once.
+pub fn authenticate_sudo() -> Result<()> {
+    let _lock = SUDO_AUTHENTICATION.lock().unwrap_or_else(|error| error.into_inner());
+    let runner = crate::command_runner();
+    // another worker may have been given the password meanwhile
+    if runner.run("sudo", &["-n", "-v"])?.0 == 0 {
+        return Ok(());
+    }
+    match runner.run("sudo", &["-v"])? {
+        (0, _, _) => Ok(()),
+        (code, _, stderr) => Err(Error::SystemError(format!(
+            "`sudo -v' failed with exit code {}: {}",
+            code,
+            stderr.trim()
+        ))),
+    }
+}
+
+/// Applies `f` to every item with at most `concurrency` worker threads,
+/// returning the results in the order of `items`.
+pub fn parallel_map<T: Sync, R: Send>(
+    items: &[T],
+    concurrency: usize,
+    f: impl Fn(&T) -> R + Sync,
+) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..items.len()).map(|_| None).collect::<Vec<Option<R>>>());
+    std::thread::scope(|scope| {
+        for _ in 0..concurrency.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                loop {
+                    let index = next.fetch_add(1, Ordering::SeqCst);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    let result = f(item);
+                    if let Ok(mut results) = results.lock() {
+                        results[index] = Some(result);
+                    }
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|result| result.expect("every item is processed by a worker"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::parallel_map;
+
+    #[test]
+    fn test_parallel_map_keeps_order_and_bounds_workers() {
+        let items = (0..8u64).rev().collect::<Vec<u64>>();
+        let started = Instant::now();
+        let results = parallel_map(&items, 4, |millis| {
+            std::thread::sleep(Duration::from_millis(millis * 20));
+            millis * 2
+        });
+        assert_eq!(results, vec![14, 12, 10, 8, 6, 4, 2, 0]);
+        assert!(started.elapsed() < Duration::from_millis(560));
+        assert_eq!(parallel_map(&Vec::<u64>::new(), 4, |n| *n), Vec::<u64>::new());
+        assert_eq!(parallel_map(&[1, 2, 3], 0, |n| n + 1), vec![2, 3, 4]);
+    }
+}
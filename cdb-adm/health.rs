@@ -0,0 +1,299 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{launchctl, Error, Result};
+
+pub const DEFAULT_HEALTH_CHECK_TIMEOUT_SECS: u64 = 10;
+
+pub trait HealthCheck {
+    fn name(&self) -> String;
+    fn check(&self) -> Result<()>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum HealthCheckSettings {
+    AppOpens {
+        app: String,
+        process: String,
+        timeout: Option<u64>,
+    },
+    ProcessAlive {
+        process: String,
+    },
+    MachService {
+        target: String,
+    },
+    Command {
+        command: Vec<String>,
+    },
+}
+impl HealthCheckSettings {
+    pub fn health_check(&self) -> Box<dyn HealthCheck> {
+        match self {
+            HealthCheckSettings::AppOpens { app, process, timeout } => Box::new(AppOpens {
+                app: app.to_string(),
+                process: process.to_string(),
+                timeout: Duration::from_secs(timeout.unwrap_or(DEFAULT_HEALTH_CHECK_TIMEOUT_SECS)),
+            }),
+            HealthCheckSettings::ProcessAlive { process } =>
+                Box::new(ProcessAlive { process: process.to_string() }),
+            HealthCheckSettings::MachService { target } =>
+                Box::new(MachServiceResponds { target: target.to_string() }),
+            HealthCheckSettings::Command { command } =>
+                Box::new(CommandSucceeds { command: command.clone() }),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AppOpens {
+    pub app: String,
+    pub process: String,
+    pub timeout: Duration,
+}
+impl HealthCheck for AppOpens {
+    fn name(&self) -> String {
+        format!("app {} opens and quits", self.app)
+    }
+
+    fn check(&self) -> Result<()> {
+        let (exit_code, out, err) = command_output("/usr/bin/open", &["-a", &self.app])?;
+        if exit_code != 0 {
+            return Err(Error::SystemError(format!(
+                "{} does not open({}):\n<stdout>{}</stdout>\n<stderr>{}</stderr>",
+                &self.app, exit_code, out, err
+            )));
+        }
+        wait_until(&self.timeout, || process_alive(&self.process)).map_err(|error| {
+            Error::SystemError(format!("waiting for {} to be up: {}", &self.app, error))
+        })?;
+        command_output("/usr/bin/osascript", &["-e", &format!("quit app {:#?}", &self.app)])?;
+        wait_until(&self.timeout, || !process_alive(&self.process)).map_err(|error| {
+            Error::SystemError(format!("waiting for {} to quit: {}", &self.app, error))
+        })?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessAlive {
+    pub process: String,
+}
+impl HealthCheck for ProcessAlive {
+    fn name(&self) -> String {
+        format!("process {} is alive", self.process)
+    }
+
+    fn check(&self) -> Result<()> {
+        if process_alive(&self.process) {
+            Ok(())
+        } else {
+            Err(Error::SystemError(format!("process {} is not running", self.process)))
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MachServiceResponds {
+    pub target: String,
+}
+impl HealthCheck for MachServiceResponds {
+    fn name(&self) -> String {
+        format!("service {} responds", self.target)
+    }
+
+    fn check(&self) -> Result<()> {
+        launchctl(&["print", &self.target], self.target.starts_with("system/"))?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandSucceeds {
+    pub command: Vec<String>,
+}
+impl HealthCheck for CommandSucceeds {
+    fn name(&self) -> String {
+        format!("command `{}' succeeds", self.command.join(" "))
+    }
+
+    fn check(&self) -> Result<()> {
+        let (program, args) = self.command.split_first().ok_or_else(|| {
+            Error::ConfigurationError("health check command cannot be empty".to_string())
+        })?;
+        let (exit_code, _, err) = command_output(program, crate::to_slice_str!(args))?;
+        if exit_code == 0 {
+            Ok(())
+        } else {
+            Err(Error::SystemError(format!(
+                "command `{}' failed with exit code {}: {}",
+                self.command.join(" "),
+                exit_code,
+                err.trim()
+            )))
+        }
+    }
+}
+
+pub fn run_health_checks(checks: &[Box<dyn HealthCheck>]) -> Vec<(String, Error)> {
+    checks
+        .iter()
+        .filter_map(|check| check.check().err().map(|error| (check.name(), error)))
+        .collect::<Vec<(String, Error)>>()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchOutcome<T> {
+    pub targets: Vec<T>,
+    pub failures: Vec<(String, Error)>,
+    pub rolled_back: bool,
+}
+
+/// Applies `turn_off` to each batch, runs every check afterwards and
+/// applies `roll_back` to the batch as soon as any of them fails.
+pub fn turn_off_in_batches<T: Clone>(
+    targets: &[T],
+    batch_size: usize,
+    checks: &[Box<dyn HealthCheck>],
+    mut turn_off: impl FnMut(&[T]),
+    mut roll_back: impl FnMut(&[T]),
+) -> Vec<BatchOutcome<T>> {
+    let mut outcomes = Vec::<BatchOutcome<T>>::new();
+    for batch in targets.chunks(batch_size.max(1)) {
+        turn_off(batch);
+        let failures = run_health_checks(checks);
+        let rolled_back = !failures.is_empty();
+        if rolled_back {
+            roll_back(batch);
+        }
+        outcomes.push(BatchOutcome { targets: batch.to_vec(), failures, rolled_back });
+    }
+    outcomes
+}
+
+pub fn process_alive(process: &str) -> bool {
+    command_output("/usr/bin/pgrep", &["-x", process])
+        .map(|(exit_code, _, _)| exit_code == 0)
+        .unwrap_or_default()
+}
+
+pub fn wait_until(timeout: &Duration, condition: impl Fn() -> bool) -> Result<()> {
+    let now = Instant::now();
+    while !condition() {
+        std::thread::sleep(Duration::from_millis(33));
+        if now.elapsed() > *timeout {
+            return Err(Error::SystemError(format!("timed out within {}s", timeout.as_secs())));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn command_output(program: &str, args: &[&str]) -> Result<(i64, String, String)> {
+    crate::command_runner().run(program, args)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use super::{turn_off_in_batches, HealthCheck, HealthCheckSettings};
+    use crate::{Error, Result};
+
+    struct FakeCheck {
+        name: String,
+        turned_off: Rc<RefCell<Vec<String>>>,
+        breaks_with: String,
+    }
+    impl HealthCheck for FakeCheck {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn check(&self) -> Result<()> {
+            if self.turned_off.borrow().contains(&self.breaks_with) {
+                Err(Error::SystemError(format!("{} is off", self.breaks_with)))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_turn_off_in_batches_rolls_back_failing_batch() {
+        let turned_off = Rc::new(RefCell::new(Vec::<String>::new()));
+        let checks: Vec<Box<dyn HealthCheck>> = vec![Box::new(FakeCheck {
+            name: "fake".to_string(),
+            turned_off: turned_off.clone(),
+            breaks_with: "c".to_string(),
+        })];
+        let targets = ["a", "b", "c", "d", "e"]
+            .iter()
+            .map(|h| h.to_string())
+            .collect::<Vec<String>>();
+        let outcomes = turn_off_in_batches(
+            &targets,
+            2,
+            &checks,
+            |batch| turned_off.borrow_mut().extend(batch.to_vec()),
+            |batch| turned_off.borrow_mut().retain(|target| !batch.contains(target)),
+        );
+        assert_eq!(
+            outcomes.iter().map(|outcome| outcome.rolled_back).collect::<Vec<bool>>(),
+            vec![false, true, false]
+        );
+        assert_eq!(outcomes[1].targets, vec!["c".to_string(), "d".to_string()]);
+        assert_eq!(
+            outcomes[1].failures,
+            vec![("fake".to_string(), Error::SystemError("c is off".to_string()))]
+        );
+        assert_eq!(
+            turned_off.borrow().clone(),
+            vec!["a".to_string(), "b".to_string(), "e".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_health_check_settings_from_toml() {
+        #[derive(serde::Deserialize)]
+        struct Checks {
+            checks: Vec<HealthCheckSettings>,
+        }
+        let checks = toml::from_str::<Checks>(
+            r#"
+            [[checks]]
+            type = "app-opens"
+            app = "Firefox"
+            process = "firefox"
+
+            [[checks]]
+            type = "mach-service"
+            target = "system/com.apple.audio.coreaudiod"
+
+            [[checks]]
+            type = "command"
+            command = ["true"]
+            "#,
+        )
+        .unwrap()
+        .checks;
+        assert_eq!(
+            checks,
+            vec![
+                HealthCheckSettings::AppOpens {
+                    app: "Firefox".to_string(),
+                    process: "firefox".to_string(),
+                    timeout: None,
+                },
+                HealthCheckSettings::MachService {
+                    target: "system/com.apple.audio.coreaudiod".to_string(),
+                },
+                HealthCheckSettings::Command { command: vec!["true".to_string()] },
+            ]
+        );
+        assert_eq!(checks[0].health_check().name(), "app Firefox opens and quits");
+        assert_eq!(checks[2].health_check().check(), Ok(()));
+    }
+}
@@ -0,0 +1,171 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{ADMSettings, CDBSettings, Error, ExecutionSettings, Result, Uid};
+
+/// Domains of applications and services that keep regenerating caches
+/// and tracking state, proposed for deletion when present.
+pub const DELETE_DOMAIN_CANDIDATES: [&str; 13] = [
+    "com.apple.CharacterPaletteIM",
+    "com.apple.CharacterPicker",
+    "com.apple.HIToolbox",
+    "com.apple.cloudd",
+    "com.apple.siriknowledged",
+    "com.google.Keystone.Agent",
+    "com.google.chrome",
+    "com.google.chrome.for.testing",
+    "com.googlecode.iterm2",
+    "com.qtproject",
+    "com.vivaldi.Vivaldi",
+    "org.chromium.Chromium",
+    "org.mozilla.firefox",
+];
+/// Key paths proposed for deletion, the first element being the domain.
+pub const DELETE_KEY_CANDIDATES: [&[&str]; 2] = [
+    &["NSGlobalDomain", "com.apple.gms.availability.useCasesWhoseAssetsNotReady"],
+    &["NSGlobalDomain", "com.apple.gms.availability.disallowedUseCases"],
+];
+
+/// What `config init` bases the proposed settings on.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsInventory {
+    pub domains: Vec<String>,
+    /// `domain/label` targets of the services currently disabled.
+    pub disabled: Vec<String>,
+}
+impl SettingsInventory {
+    pub fn new(domains: Vec<String>, disabled: Vec<String>) -> SettingsInventory {
+        SettingsInventory {
+            domains: crate::no_doubles(crate::to_slice_str!(domains)),
+            disabled: crate::no_doubles(crate::to_slice_str!(disabled)),
+        }
+    }
+
+    /// Domains of `defaults domains` and the services disabled in the
+    /// domains of `uid` and of the system.
+    pub fn detect(uid: &Uid) -> Result<SettingsInventory> {
+        let system_uids = crate::salient_system_uids();
+        let disabled = crate::ad_manager::list_disabled_agents_and_daemons(uid, &system_uids)?
+            .into_iter()
+            .filter(|(_, _, _, _, enabled, _)| !enabled)
+            .map(|(domain, service, _, _, _, _)| format!("{}/{}", domain, service))
+            .collect::<Vec<String>>();
+        Ok(SettingsInventory::new(crate::list_domains()?, disabled))
+    }
+
+    fn has_domain(&self, domain: &str) -> bool {
+        domain == "NSGlobalDomain" || self.domains.iter().any(|known| known == domain)
+    }
+}
+
+fn toml_string(value: &str) -> String {
+    toml::Value::String(value.to_string()).to_string()
+}
+
+fn toml_array(values: &[String]) -> String {
+    if values.is_empty() {
+        return "[]".to_string();
+    }
+    let mut array = "[\n".to_string();
+    for value in values {
+        array.push_str(&format!("    {},\n", value));
+    }
+    array.push(']');
+    array
+}
+
+/// Commented settings proposed from `inventory`: the candidate domains
+/// and key paths present on the system are deleted by `cdb fix` and the
+/// services disabled now are kept disabled by `adm enforce`.
+pub fn render_initial_settings(inventory: &SettingsInventory) -> String {
+    let inventory = SettingsInventory::new(inventory.domains.clone(), inventory.disabled.clone());
+    let domains = DELETE_DOMAIN_CANDIDATES
+        .iter()
+        .filter(|domain| inventory.has_domain(domain))
+        .map(|domain| toml_string(domain))
+        .collect::<Vec<String>>();
+    let keys = DELETE_KEY_CANDIDATES
+        .iter()
+        .filter(|path| inventory.has_domain(path[0]))
+        .map(|path| {
+            let path = path.iter().map(|key| toml_string(key)).collect::<Vec<String>>();
+            format!("[{}]", path.join(", "))
+        })
+        .collect::<Vec<String>>();
+    let disabled =
+        inventory.disabled.iter().map(|target| toml_string(target)).collect::<Vec<String>>();
+    let cdb = CDBSettings::default();
+    let adm = ADMSettings::default();
+    let execution = ExecutionSettings::default();
+    let policy = adm.policy();
+
+    let mut text = String::new();
+    text.push_str("# cdb-adm settings generated by `cdb config init` from the preferences\n");
+    text.push_str("# domains and the disabled services of this system.\n");
+    text.push_str("# Check them with `cdb config check` and `adm config show`.\n\n");
+    text.push_str("# Where backups and the journal of `adm tui` are kept\n");
+    text.push_str(&format!(
+        "backup-path = {}\n\n",
+        toml_string(crate::settings::DEFAULT_BACKUP_PATH)
+    ));
+    text.push_str("[cdb]\n");
+    text.push_str("# Encrypt export, delete and snapshot files with a keychain key\n");
+    text.push_str(&format!("encrypt_backups = {}\n\n", cdb.encrypt_backups()));
+    text.push_str("[cdb.delete]\n");
+    text.push_str(&format!(
+        "# {} of the {} candidate domains are present on this system\n",
+        domains.len(),
+        DELETE_DOMAIN_CANDIDATES.len()
+    ));
+    text.push_str(&format!("domains = {}\n", toml_array(&domains)));
+    text.push_str("# Key paths, each starting with its domain\n");
+    text.push_str(&format!("keys = {}\n\n", toml_array(&keys)));
+    text.push_str("[adm]\n");
+    text.push_str("# Print warnings about services whose plist is not found\n");
+    text.push_str(&format!("display_warnings = {}\n", adm.display_warnings()));
+    text.push_str("# Also turn off the services of the non-needed catalog\n");
+    text.push_str(&format!("include_non_needed = {}\n", adm.include_non_needed()));
+    text.push_str("# List the services of the system uids besides the given one\n");
+    text.push_str(&format!("include_system_uids = {}\n\n", adm.include_system_uids()));
+    text.push_str("[adm.policy]\n");
+    text.push_str(&format!(
+        "# The {} services disabled when this file was generated\n",
+        disabled.len()
+    ));
+    text.push_str(&format!("disabled = {}\n", toml_array(&disabled)));
+    text.push_str("# Services to boot out as well as disable\n");
+    text.push_str("bootout = []\n");
+    text.push_str("# Services `adm enforce` leaves alone\n");
+    text.push_str("ignore = []\n");
+    text.push_str("# Seconds between runs of the enforce launch agent\n");
+    text.push_str(&format!("interval = {}\n\n", policy.interval()));
+    text.push_str("[adm.execution]\n");
+    text.push_str("# Seconds before a launchctl command is killed, 0 for no timeout\n");
+    text.push_str(&format!("timeout_secs = {}\n", execution.timeout_secs));
+    text.push_str("# Attempts after the first for timeouts and the exit codes below\n");
+    text.push_str(&format!("retries = {}\n", execution.retries));
+    text.push_str(&format!("retry_delay_millis = {}\n", execution.retry_delay_millis));
+    text.push_str(&format!(
+        "retry_exit_codes = [{}]\n",
+        execution
+            .retry_exit_codes
+            .iter()
+            .map(|code| code.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
+    ));
+    text.push_str("# Services turned off or booted up at once\n");
+    text.push_str(&format!("concurrency = {}\n", execution.concurrency));
+    text
+}
+
+/// Writes `text` to `path` unless it exists and `force` is false.
+pub fn write_initial_settings(path: &iocore::Path, text: &str, force: bool) -> Result<()> {
+    if path.exists() && !force {
+        return Err(Error::ConfigurationError(format!(
+            "{:#?} already exists, use --force to overwrite it",
+            path.to_string()
+        )));
+    }
+    path.write(text.as_bytes())?;
+    Ok(())
+}
@@ -1,6 +1,4 @@
-use security_framework::os::macos::keychain::{SecKeychain, SecPreferencesDomain};
-
-use crate::Result;
+use crate::{secret_store, Result};
 
 const ACCOUNT: &'static str = "cdb-adm";
 
@@ -29,26 +27,19 @@ impl KeychainData {
         KeychainData { service, data }
     }
 
+    /// Saves the data through the current [`SecretStore`](crate::SecretStore).
     pub fn save(&mut self) -> Result<()> {
-        let keychain = SecKeychain::default_for_domain(SecPreferencesDomain::User)?;
-        keychain.set_generic_password(self.service.as_str(), ACCOUNT, &self.data)?;
-        Ok(())
+        secret_store().set(self.service.as_str(), ACCOUNT, &self.data)
     }
 
     pub fn get(service: impl std::fmt::Display) -> Result<KeychainData> {
         let service = service.to_string();
-
-        let keychain = SecKeychain::default()?;
-        let (item, _) = keychain.find_generic_password(service.as_str(), ACCOUNT)?;
-        let data = item.to_vec();
+        let data = secret_store().get(service.as_str(), ACCOUNT)?;
         Ok(KeychainData::new(service, &data))
     }
 
     pub fn delete(&mut self) -> Result<()> {
-        let keychain = SecKeychain::default()?;
-        let (_, item) = keychain.find_generic_password(self.service.as_str(), ACCOUNT)?;
-        item.delete();
-        Ok(())
+        secret_store().delete(self.service.as_str(), ACCOUNT)
     }
 
     pub fn data(&self) -> String {
@@ -57,10 +48,13 @@ impl KeychainData {
 }
 #[cfg(test)]
 mod test {
-    use crate::{Error, KeychainData, Result};
+    use std::sync::Arc;
+
+    use crate::{set_secret_store, Error, KeychainData, MemorySecretStore, Result};
 
     #[test]
     fn test_get_set_delete() -> Result<()> {
+        set_secret_store(Arc::new(MemorySecretStore::default()));
         let mut keychain_data = KeychainData::new("cdb-adm-test", b"cdb adm data");
         keychain_data.save()?;
         let result = KeychainData::get("cdb-adm-test");
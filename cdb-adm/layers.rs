@@ -0,0 +1,325 @@
+use std::collections::BTreeMap;
+
+use crate::{ADMSettings, CDBSettings, ConfigSource, Error, Result, Settings, SettingsEnvPath};
+
+pub const DEFAULT_SYSTEM_SETTINGS_PATH: &str = "/Library/Preferences/cdb-adm.toml";
+pub const PROJECT_SETTINGS_FILENAME: &str = ".cdb-adm.toml";
+const SYSTEM_SETTINGS_ENV_VAR: &str = "CDB_ADM_SYSTEM_SETTINGS";
+const HOSTNAME_ENV_VAR: &str = "CDB_ADM_HOSTNAME";
+
+/// One settings file, or the `[hosts.<hostname>]` section of one, in
+/// the shape of the cdb-adm settings.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingsLayer {
+    pub source: ConfigSource,
+    pub table: toml::Table,
+}
+
+/// Merges `overlay` into `base`: tables are merged key by key, arrays
+/// are appended to, skipping values already present, and any other
+/// value is replaced.
+pub fn merge_tables(base: &mut toml::Table, overlay: &toml::Table) {
+    for (name, value) in overlay {
+        match (base.get_mut(name), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overlay)) =>
+                merge_tables(base, overlay),
+            (Some(toml::Value::Array(base)), toml::Value::Array(overlay)) =>
+                append_new(base, overlay),
+            _ => {
+                base.insert(name.to_string(), value.clone());
+            },
+        }
+    }
+}
+
+pub(crate) fn append_new(base: &mut Vec<toml::Value>, overlay: &[toml::Value]) {
+    for value in overlay {
+        if !base.contains(value) {
+            base.push(value.clone());
+        }
+    }
+}
+
+/// Reads a settings file, merging the files listed in its `include`
+/// beneath it. Relative includes are resolved from the directory of
+/// the including file.
+pub fn read_settings_file(path: &iocore::Path) -> Result<toml::Table> {
+    read_with_includes(path, &mut Vec::new())
+}
+
+fn read_with_includes(path: &iocore::Path, chain: &mut Vec<String>) -> Result<toml::Table> {
+    let path = path.expand().try_canonicalize();
+    let name = path.to_string();
+    if chain.contains(&name) {
+        return Err(Error::SettingsError(format!(
+            "config {:#?} includes itself through {}",
+            name,
+            chain.join(" -> ")
+        )));
+    }
+    if !path.is_file() {
+        return Err(Error::SettingsError(format!("config {} does not exist", name)));
+    }
+    let mut table = toml::from_str::<toml::Table>(&path.read()?).map_err(|error| {
+        Error::SettingsError(format!("reading toml from config path {:#?}: {}", name, error))
+    })?;
+    let includes = match table.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::Array(includes)) => includes
+            .into_iter()
+            .map(|include| match include {
+                toml::Value::String(include) => Ok(include),
+                include => Err(Error::SettingsError(format!(
+                    "config {:#?} includes {} instead of a path",
+                    name, include
+                ))),
+            })
+            .collect::<Result<Vec<String>>>()?,
+        Some(_) => {
+            return Err(Error::SettingsError(format!(
+                "config {:#?}: `include` must be an array of paths",
+                name
+            )));
+        },
+    };
+    chain.push(name);
+    let mut merged = toml::Table::new();
+    for include in includes {
+        merge_tables(&mut merged, &read_with_includes(&include_path(&path, &include), chain)?);
+    }
+    chain.pop();
+    merge_tables(&mut merged, &table);
+    Ok(merged)
+}
+
+/// Path of `include` as listed in the settings file at `path`.
+pub(crate) fn include_path(path: &iocore::Path, include: &str) -> iocore::Path {
+    let included = iocore::Path::raw(include).expand();
+    if included.is_absolute() {
+        included
+    } else {
+        path.parent().unwrap_or_else(iocore::Path::cwd).join(include)
+    }
+}
+
+pub(crate) fn settings_path<T: SettingsEnvPath>() -> iocore::Path {
+    match iocore::env::var(T::env_var_name()) {
+        Ok(path) => iocore::Path::raw(path),
+        Err(_) => iocore::Path::raw(T::default_path()),
+    }
+    .expand()
+    .try_canonicalize()
+}
+
+/// `CDB_ADM_SETTINGS` or `~/.config/cdb-adm.toml`.
+pub fn user_settings_path() -> iocore::Path {
+    settings_path::<Settings>()
+}
+
+/// `CDB_ADM_HOSTNAME` or the short host name, which selects the
+/// `[hosts.<hostname>]` sections of the settings files.
+pub fn hostname() -> Option<String> {
+    if let Ok(name) = iocore::env::var(HOSTNAME_ENV_VAR) {
+        return Some(name);
+    }
+    let output = std::process::Command::new("hostname").arg("-s").output().ok()?;
+    let name = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !name.is_empty()).then_some(name)
+}
+
+/// `.cdb-adm.toml` files of the current directory and its ancestors,
+/// outermost first.
+pub fn project_settings_paths() -> Vec<iocore::Path> {
+    let Ok(current) = std::env::current_dir() else {
+        return Vec::new();
+    };
+    let mut paths = current
+        .ancestors()
+        .map(|directory| directory.join(PROJECT_SETTINGS_FILENAME))
+        .filter(|path| path.is_file())
+        .map(|path| iocore::Path::raw(path.display()))
+        .collect::<Vec<iocore::Path>>();
+    paths.reverse();
+    paths
+}
+
+fn file_layers(
+    path: &iocore::Path,
+    section: Option<&str>,
+    hostname: Option<&str>,
+) -> Result<(SettingsLayer, Option<SettingsLayer>)> {
+    let mut table = read_settings_file(path)?;
+    let host = match (table.remove("hosts"), hostname) {
+        (Some(toml::Value::Table(mut hosts)), Some(hostname)) => match hosts.remove(hostname) {
+            Some(toml::Value::Table(host)) => Some(SettingsLayer {
+                source: ConfigSource::File(format!("{} [hosts.{}]", path, hostname)),
+                table: within(section, host),
+            }),
+            _ => None,
+        },
+        _ => None,
+    };
+    let layer = SettingsLayer {
+        source: ConfigSource::File(path.to_string()),
+        table: within(section, table),
+    };
+    Ok((layer, host))
+}
+
+fn within(section: Option<&str>, table: toml::Table) -> toml::Table {
+    match section {
+        Some(section) => toml::Table::from_iter([(section.to_string(), toml::Value::Table(table))]),
+        None => table,
+    }
+}
+
+/// Existing system and user settings files, from the lowest to the
+/// highest precedence, with the section the `cdb` and `adm` ones are
+/// merged under.
+pub fn user_settings_files() -> Vec<(iocore::Path, Option<&'static str>)> {
+    [
+        (system_settings_path(), None),
+        (settings_path::<Settings>(), None),
+        (settings_path::<CDBSettings>(), Some("cdb")),
+        (settings_path::<ADMSettings>(), Some("adm")),
+    ]
+    .into_iter()
+    .filter(|(path, _)| path.is_file())
+    .collect()
+}
+
+/// Variables pinning each of the [`user_settings_files`] to its path,
+/// for processes which do not inherit this environment, such as the
+/// LaunchAgent of `adm enforce`.
+pub fn settings_environment() -> BTreeMap<String, String> {
+    [
+        (SYSTEM_SETTINGS_ENV_VAR, system_settings_path()),
+        (Settings::env_var_name(), settings_path::<Settings>()),
+        (CDBSettings::env_var_name(), settings_path::<CDBSettings>()),
+        (ADMSettings::env_var_name(), settings_path::<ADMSettings>()),
+    ]
+    .into_iter()
+    .filter(|(_, path)| path.is_file())
+    .map(|(name, path)| (name.to_string(), path.to_string()))
+    .collect()
+}
+
+fn system_settings_path() -> iocore::Path {
+    match iocore::env::var(SYSTEM_SETTINGS_ENV_VAR) {
+        Ok(path) => iocore::Path::raw(path),
+        Err(_) => iocore::Path::raw(DEFAULT_SYSTEM_SETTINGS_PATH),
+    }
+}
+
+/// Settings files from the lowest to the highest precedence: the system
+/// defaults, the user settings with the `cdb` and `adm` ones, their
+/// `[hosts.<hostname>]` sections, then the project files, each followed
+/// by its own host section.
+pub fn settings_layers() -> Result<Vec<SettingsLayer>> {
+    let hostname = hostname();
+    let mut layers = Vec::<SettingsLayer>::new();
+    let mut hosts = Vec::<SettingsLayer>::new();
+    for (path, section) in user_settings_files() {
+        let (layer, host) = file_layers(&path, section, hostname.as_deref())?;
+        layers.push(layer);
+        hosts.extend(host);
+    }
+    layers.extend(hosts);
+    for path in project_settings_paths() {
+        let (layer, host) = file_layers(&path, None, hostname.as_deref())?;
+        layers.push(layer);
+        layers.extend(host);
+    }
+    Ok(layers)
+}
+
+/// All [`settings_layers`] merged into one table, with `backup-path`
+/// spelled as [`Settings`] deserializes it.
+pub fn layered_settings_table() -> Result<toml::Table> {
+    let mut table = toml::Table::new();
+    for layer in settings_layers()? {
+        merge_tables(&mut table, &layer.table);
+    }
+    if let Some(path) = table.remove("backup-path") {
+        table.insert("backup_path".to_string(), path);
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge_tables, read_settings_file};
+
+    fn table(toml: &str) -> toml::Table {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn test_merge_appends_arrays_and_replaces_values() {
+        let mut base = table(
+            "backup_path = \"~/a\"\n[cdb.delete]\ndomains = [\"com.apple.a\", \"com.apple.b\"]\n\
+             [adm]\ndisplay_warnings = true\n",
+        );
+        merge_tables(
+            &mut base,
+            &table(
+                "backup_path = \"~/b\"\n\
+                 [cdb.delete]\ndomains = [\"com.apple.b\", \"com.apple.c\"]\n\
+                 keys = [[\"com.apple.d\", \"Key\"]]\n[adm.execution]\nretries = 3\n",
+            ),
+        );
+        assert_eq!(
+            base,
+            table(
+                "backup_path = \"~/b\"\n[cdb.delete]\n\
+                 domains = [\"com.apple.a\", \"com.apple.b\", \"com.apple.c\"]\n\
+                 keys = [[\"com.apple.d\", \"Key\"]]\n\
+                 [adm]\ndisplay_warnings = true\n[adm.execution]\nretries = 3\n"
+            )
+        );
+    }
+
+    #[test]
+    fn test_merge_replaces_values_of_another_type() {
+        let mut base = table("include_non_needed = true\n[execution]\nretries = 3\n");
+        merge_tables(&mut base, &table("execution = \"none\"\ninclude_non_needed = [1]\n"));
+        assert_eq!(base, table("execution = \"none\"\ninclude_non_needed = [1]\n"));
+    }
+
+    #[test]
+    fn test_includes_are_merged_beneath_the_including_file() {
+        let directory =
+            std::env::temp_dir().join(format!("cdb-adm-layers-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("shared")).unwrap();
+        std::fs::write(
+            directory.join("main.toml"),
+            "include = [\"shared/base.toml\"]\n[cdb.delete]\ndomains = [\"com.example.main\"]\n",
+        )
+        .unwrap();
+        std::fs::write(
+            directory.join("shared/base.toml"),
+            "include = [\"../more.toml\"]\nbackup_path = \"~/base\"\n\
+             [cdb.delete]\ndomains = [\"com.example.base\"]\n",
+        )
+        .unwrap();
+        std::fs::write(directory.join("more.toml"), "backup_path = \"~/more\"\n").unwrap();
+        std::fs::write(directory.join("cycle.toml"), "include = [\"cycle.toml\"]\n").unwrap();
+
+        let merged =
+            read_settings_file(&iocore::Path::raw(directory.join("main.toml").display())).unwrap();
+        assert_eq!(
+            merged,
+            table(
+                "backup_path = \"~/base\"\n[cdb.delete]\n\
+                 domains = [\"com.example.base\", \"com.example.main\"]\n"
+            )
+        );
+        assert!(
+            read_settings_file(&iocore::Path::raw(directory.join("cycle.toml").display()))
+                .unwrap_err()
+                .to_string()
+                .contains("includes itself")
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -5,28 +5,126 @@ pub mod cli;
 pub use cli::{adb, cdb};
 mod settings;
 pub use settings::{ADMSettings, CDBSettings, Settings, SettingsEnvPath};
+mod config;
+pub use config::{
+    adm_config, adm_settings, resolve_adm_config, set_adm_config, ConfigEntry, ConfigSource,
+    ResolvedConfig, ADM_ENV_VARS,
+};
+mod layers;
+pub use layers::{
+    hostname, layered_settings_table, merge_tables, project_settings_paths, read_settings_file,
+    settings_environment, settings_layers, user_settings_files, user_settings_path, SettingsLayer,
+    DEFAULT_SYSTEM_SETTINGS_PATH, PROJECT_SETTINGS_FILENAME,
+};
+pub(crate) use layers::append_new;
+mod validation;
+pub use validation::{
+    backup_path_problem, check_settings, check_settings_files, validate_settings, Diagnostic,
+    Severity, SystemFacts,
+};
+mod init;
+pub use init::{
+    render_initial_settings, write_initial_settings, SettingsInventory, DELETE_DOMAIN_CANDIDATES,
+    DELETE_KEY_CANDIDATES,
+};
 mod md;
 pub use md::turn_off_mdutil;
 mod spctl;
-pub use spctl::spctl_global_disable;
+pub use spctl::{spctl, spctl_global_disable, SPCTL_PATH};
 mod coredata;
 pub use coredata::{
-    coredata_fix, defaults_delete, defaults_delete_domain, defaults_write, delete_domains,
-    export_all_domains, export_domain, export_domains, export_library_preferences,
-    export_plists_from_path, list_domains, DeleteDefaultsMacOSResult,
+    coredata_fix, defaults_delete, defaults_delete_domain, defaults_import, defaults_write,
+    delete_domains, delete_exported_domains, export_all_domains, export_domain, export_domains,
+    export_library_preferences, export_plists_from_path, list_domains, DeleteDefaultsMacOSResult,
+    DEFAULTS_PATH,
 };
+mod preferences;
+pub use preferences::{diff_preferences, read_exported_domains, restore_domains, PreferenceChange};
 mod key_chain_data;
 pub use key_chain_data::KeychainData;
+mod secret_store;
+#[cfg(all(feature = "macos", target_os = "macos"))]
+pub use secret_store::KeychainSecretStore;
+pub use secret_store::{
+    is_secret_not_found, secret_store, set_secret_store, FileSecretStore, MemorySecretStore, SecretStore,
+    DEFAULT_SECRET_STORE_PATH, SECRET_NOT_FOUND,
+};
+mod encryption;
+pub use encryption::{
+    open_backup, read_backup, rotate_backup_key, write_backup, write_backup_with_key, BackupKey,
+    EncryptedBundle, BACKUP_KEY_SERVICE, ENCRYPTED_BUNDLE_FORMAT, ENCRYPTED_BUNDLE_VERSION,
+};
+mod health;
+pub use health::{
+    process_alive, run_health_checks, turn_off_in_batches, wait_until, AppOpens, BatchOutcome,
+    CommandSucceeds, HealthCheck, HealthCheckSettings, MachServiceResponds, ProcessAlive,
+};
+mod runner;
+pub use runner::{
+    command_runner, macos_execution, macos_program, override_command_runner, set_command_runner,
+    CommandRunner, CommandRunnerOverride, FakeCommandRunner, SystemCommandRunner,
+    LAUNCHCTL_WORKING_DIRECTORY, MACOS_PROGRAMS,
+};
+mod execution;
+pub use execution::{
+    authenticate_sudo, execution_settings, parallel_map, retry, run_sudo_with_retries,
+    run_with_retries, set_execution_settings, ExecutionSettings, DEFAULT_COMMAND_RETRIES,
+    DEFAULT_COMMAND_TIMEOUT_SECS, DEFAULT_CONCURRENCY, DEFAULT_RETRY_DELAY_MILLIS,
+    DEFAULT_RETRY_EXIT_CODES, SUDO_PASSWORD_REQUIRED,
+};
+mod broker;
+pub use broker::{
+    broker_client, random_token, set_broker_client, start_broker, Broker, BrokerAuditEntry,
+    BrokerClient, BrokerRequest, BrokerResponse, PrivilegedCommand, BROKER_LAUNCHCTL_SUBCOMMANDS,
+    BROKER_SPCTL_OPTIONS, DEFAULT_BROKER_AUDIT_LOG,
+};
+mod users;
+pub use users::{
+    current_console_uid, current_sudo_uid, parse_dscl_list, parse_passwd, User, UserDirectory,
+    CONSOLE_PATH, DSCL_PATH, FIRST_LOGIN_UID, PASSWD_PATH,
+};
+mod bisect;
+pub use bisect::{BisectState, DEFAULT_BISECT_STATE_PATH};
+mod report;
+pub use report::{
+    fill_template, html_escape, markdown_escape, plist_program, preference_reports,
+    render_report, service_reports, AuditReport, PreferenceReport, ReportFormat, ReportTemplate,
+    ServiceReport, ServiceRisk, REPORT_TITLE,
+};
+
+mod tui;
+pub use tui::{
+    append_tui_journal, apply_tui_action, read_tui_journal, run_tui, TuiAction, TuiCommand,
+    TuiJournalEntry, TuiKey, TuiState, DEFAULT_TUI_REFRESH_SECS, TUI_JOURNAL_FILENAME,
+};
 
 pub mod ad_manager;
 use std::collections::BTreeSet;
 
 pub use ad_manager::{
     agent_or_daemon, agent_or_daemon_prefix, agents_and_daemons_path_map,
-    agents_and_daemons_to_turn_off, boot_up_smart, extract_service_info_opt, extract_service_name,
-    launchctl, launchctl_ok, list_active_agents_and_daemons, list_agents_and_daemons,
-    list_agents_and_daemons_paths, list_all_agents_and_daemons, parse_services,
-    salient_system_uids, system_uids, turn_off_agents_and_daemons, turn_off_smart, Uid,
+    agents_and_daemons_to_turn_off, analyse_impact, bisect_smart, boot_up_agents_and_daemons,
+    boot_up_smart, category_name, diff_service_snapshots, enforce_launch_agent_plist,
+    enforce_policy, evaluate_policy, explain_turn_off_smart, extract_service_info_opt,
+    extract_service_name, filter_and_sort_statuses, format_epoch, format_kib, included_system_uids,
+    install_enforce_launch_agent, install_launchd_plist, label_matches, launchctl, launchctl_blame,
+    launchctl_list, launchctl_ok, launchd_log_findings, launchd_system_uids,
+    list_active_agents_and_daemons, list_agents_and_daemons, list_agents_and_daemons_paths,
+    list_all_agents_and_daemons, live_service_states, mach_services_from_plist, parse_catalog,
+    parse_endpoints, parse_launchctl_blame, parse_launchctl_list, parse_launchd_log,
+    parse_proc_boot_time, parse_proc_resources, parse_ps_duration_ms, parse_ps_resources,
+    parse_ps_threads, parse_services, parse_user_subdomains, proc_resources, process_resources,
+    ps_resources, rank_by_resources, salient_system_uids, service_states, service_statuses,
+    split_service_target, system_uids, take_service_snapshot, turn_off_agent_or_daemon,
+    turn_off_agents_and_daemons, turn_off_capturing_launchd_log, turn_off_smart,
+    turn_off_smart_verified, BlameReason, CalendarInterval, Deviation, DomainKind, ImpactHint,
+    LaunchctlBlame, LaunchctlListEntry, LaunchctlOutcome, LaunchdEndpoint, LaunchdKind,
+    LaunchdLogCapture, LaunchdLogCursor, LaunchdLogEntry, LaunchdLogFinding, LaunchdLogReport,
+    LaunchdPlistSpec, PlistFormat, Policy, PolicyDeviation, PolicyRule, ProcessResources,
+    ResourceSort, ServiceCatalog, ServiceChange, ServiceDifference, ServiceSnapshot, ServiceState,
+    ServiceStatus, StatusFilter, StatusSort, Uid, BOOTOUT_CATALOG, DEFAULT_ENFORCE_INTERVAL_SECS,
+    ENFORCE_LAUNCH_AGENT_LABEL, LAUNCHCTL_PATH, LAUNCHD_LOG_PATH, NON_NEEDED_CATALOG,
+    PROC_CLOCK_TICKS, PROC_PATH, PS_PATH,
 };
 
 pub fn no_doubles(list: &[&str]) -> Vec<String> {
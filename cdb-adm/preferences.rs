@@ -0,0 +1,88 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{defaults_import, read_backup, DeleteDefaultsMacOSResult, Result};
+
+/// Reads the domains of a `cdb export` or `cdb delete` output file,
+/// decrypting it when it is an encrypted bundle.
+pub fn read_exported_domains(
+    path: &iocore::Path,
+) -> Result<BTreeMap<String, (plist::Value, Option<iocore::Path>)>> {
+    let data = read_backup(path)?;
+    match serde_json::from_str::<BTreeMap<String, (plist::Value, Option<iocore::Path>)>>(&data) {
+        Ok(domains) => Ok(domains),
+        Err(_) => Ok(serde_json::from_str::<DeleteDefaultsMacOSResult>(&data)?.domain_map),
+    }
+}
+
+/// Imports each exported domain back with `defaults import`, only
+/// those named in `domains` unless it is empty.
+pub fn restore_domains(
+    exported: &BTreeMap<String, (plist::Value, Option<iocore::Path>)>,
+    domains: &[&str],
+) -> Vec<(String, Result<()>)> {
+    exported
+        .iter()
+        .filter(|(domain, _)| domains.is_empty() || domains.contains(&domain.as_str()))
+        .map(|(domain, (value, _))| (domain.to_string(), defaults_import(domain, value)))
+        .collect()
+}
+
+/// Preference that differs between two exports, `key` being the path
+/// of dictionary keys down to the value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PreferenceChange {
+    pub domain: String,
+    pub key: Vec<String>,
+    pub before: Option<plist::Value>,
+    pub after: Option<plist::Value>,
+}
+
+pub fn diff_preferences(
+    before: &BTreeMap<String, (plist::Value, Option<iocore::Path>)>,
+    after: &BTreeMap<String, (plist::Value, Option<iocore::Path>)>,
+) -> Vec<PreferenceChange> {
+    let mut domains = before.keys().chain(after.keys()).collect::<Vec<&String>>();
+    domains.sort();
+    domains.dedup();
+    let mut changes = Vec::<PreferenceChange>::new();
+    for domain in domains {
+        diff_plist_values(
+            domain,
+            &mut Vec::new(),
+            before.get(domain).map(|(value, _)| value),
+            after.get(domain).map(|(value, _)| value),
+            &mut changes,
+        );
+    }
+    changes
+}
+
+fn diff_plist_values(
+    domain: &str,
+    key: &mut Vec<String>,
+    before: Option<&plist::Value>,
+    after: Option<&plist::Value>,
+    changes: &mut Vec<PreferenceChange>,
+) {
+    match (before, after) {
+        (Some(plist::Value::Dictionary(before)), Some(plist::Value::Dictionary(after))) => {
+            let mut keys = before.keys().chain(after.keys()).collect::<Vec<&String>>();
+            keys.sort();
+            keys.dedup();
+            for name in keys {
+                key.push(name.to_string());
+                diff_plist_values(domain, key, before.get(name), after.get(name), changes);
+                key.pop();
+            }
+        },
+        (before, after) if before != after => changes.push(PreferenceChange {
+            domain: domain.to_string(),
+            key: key.clone(),
+            before: before.cloned(),
+            after: after.cloned(),
+        }),
+        _ => {},
+    }
+}
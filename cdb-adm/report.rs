@@ -0,0 +1,426 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    export_domain, format_epoch, list_all_agents_and_daemons, list_domains,
+    mach_services_from_plist, service_statuses, DomainKind, Result, ServiceCatalog, Uid,
+};
+
+pub const REPORT_TITLE: &str = "Audit report of agents, daemons and preferences";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReportFormat {
+    #[default]
+    Md,
+    Html,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServiceRisk {
+    #[default]
+    Low,
+    Medium,
+    High,
+}
+impl ServiceRisk {
+    /// Third-party services are `high` when they run as root in the
+    /// system domain or are started at load or kept alive, `medium`
+    /// otherwise. Apple services listed in the `non-needed` or
+    /// `bootout` catalogs that are still enabled are `medium`, every
+    /// other one is `low`.
+    pub fn assess(
+        domain: &str,
+        enabled: bool,
+        categories: &[String],
+        run_at_load: bool,
+        keep_alive: bool,
+    ) -> ServiceRisk {
+        let category = |name: &str| categories.iter().any(|category| category == name);
+        if category("third-party") {
+            if DomainKind::of(domain) == Some(DomainKind::System) || run_at_load || keep_alive {
+                ServiceRisk::High
+            } else {
+                ServiceRisk::Medium
+            }
+        } else if enabled && (category("non-needed") || category("bootout")) {
+            ServiceRisk::Medium
+        } else {
+            ServiceRisk::Low
+        }
+    }
+}
+impl std::fmt::Display for ServiceRisk {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ServiceRisk::Low => "low",
+                ServiceRisk::Medium => "medium",
+                ServiceRisk::High => "high",
+            }
+        )
+    }
+}
+
+/// One agent or daemon of an [`AuditReport`] along with the details
+/// of its plist.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceReport {
+    pub domain: String,
+    pub service: String,
+    pub pid: i64,
+    pub status: Option<i64>,
+    pub enabled: bool,
+    pub path: Option<String>,
+    pub program: Option<String>,
+    pub run_at_load: bool,
+    pub keep_alive: bool,
+    pub mach_services: Vec<String>,
+    pub categories: Vec<String>,
+    pub risk: ServiceRisk,
+}
+
+/// Preference domain of an [`AuditReport`] with the number of keys
+/// `defaults export` gave for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PreferenceReport {
+    pub domain: String,
+    pub keys: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditReport {
+    /// Seconds since the unix epoch.
+    pub generated: u64,
+    pub uid: Uid,
+    pub services: Vec<ServiceReport>,
+    pub preferences: Vec<PreferenceReport>,
+}
+impl AuditReport {
+    /// Reads every agent and daemon of the domains of `uid` and every
+    /// preference domain, leaving out the domains that cannot be
+    /// exported.
+    pub fn collect(uid: &Uid, quiet: bool) -> Result<AuditReport> {
+        let services =
+            service_reports(&list_all_agents_and_daemons(uid)?, &ServiceCatalog::builtin());
+        let mut domains = BTreeMap::<String, plist::Value>::new();
+        for domain in std::iter::once("NSGlobalDomain".to_string()).chain(list_domains()?) {
+            match export_domain(&domain) {
+                Ok(value) => {
+                    domains.insert(domain, value);
+                },
+                Err(error) =>
+                    if !quiet {
+                        eprintln!("[warning] exporting {}: {}", domain, error);
+                    },
+            }
+        }
+        Ok(AuditReport {
+            generated: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            uid: *uid,
+            services,
+            preferences: preference_reports(&domains),
+        })
+    }
+
+    pub fn running(&self) -> usize {
+        self.services.iter().filter(|service| service.pid != 0).count()
+    }
+
+    pub fn disabled(&self) -> usize {
+        self.services.iter().filter(|service| !service.enabled).count()
+    }
+
+    pub fn count(&self, risk: ServiceRisk) -> usize {
+        self.services.iter().filter(|service| service.risk == risk).count()
+    }
+
+    pub fn render(&self, format: ReportFormat) -> String {
+        render_report(self, &ReportTemplate::of(format))
+    }
+}
+
+/// Combines the statuses of `agents_and_daemons` as returned by
+/// [`list_all_agents_and_daemons`] with their plists and catalog
+/// categories, riskiest first.
+#[allow(clippy::type_complexity)]
+pub fn service_reports(
+    agents_and_daemons: &[(
+        String,
+        String,
+        i64,
+        Option<i64>,
+        bool,
+        Option<(iocore::Path, plist::Dictionary)>,
+    )],
+    catalog: &ServiceCatalog,
+) -> Vec<ServiceReport> {
+    let mut reports = service_statuses(agents_and_daemons, catalog)
+        .into_iter()
+        .zip(agents_and_daemons.iter().map(|(_, _, _, _, _, info)| info))
+        .map(|(status, info)| {
+            let dictionary = info.as_ref().map(|(_, dictionary)| dictionary);
+            let flag = |key: &str| match dictionary.and_then(|dictionary| dictionary.get(key)) {
+                Some(plist::Value::Boolean(value)) => *value,
+                Some(plist::Value::Dictionary(conditions)) => !conditions.is_empty(),
+                _ => false,
+            };
+            let (run_at_load, keep_alive) = (flag("RunAtLoad"), flag("KeepAlive"));
+            ServiceReport {
+                risk: ServiceRisk::assess(
+                    &status.domain,
+                    status.enabled,
+                    &status.categories,
+                    run_at_load,
+                    keep_alive,
+                ),
+                program: dictionary.and_then(plist_program),
+                mach_services: dictionary.map(mach_services_from_plist).unwrap_or_default(),
+                run_at_load,
+                keep_alive,
+                domain: status.domain,
+                service: status.service,
+                pid: status.pid,
+                status: status.status,
+                enabled: status.enabled,
+                path: status.path,
+                categories: status.categories,
+            }
+        })
+        .collect::<Vec<ServiceReport>>();
+    reports.sort_by(|a, b| {
+        b.risk.cmp(&a.risk).then(a.service.cmp(&b.service)).then(a.domain.cmp(&b.domain))
+    });
+    reports
+}
+
+/// `Program` of a launchd plist or else the first of its
+/// `ProgramArguments`.
+pub fn plist_program(dictionary: &plist::Dictionary) -> Option<String> {
+    match (dictionary.get("Program"), dictionary.get("ProgramArguments")) {
+        (Some(plist::Value::String(program)), _) => Some(program.to_string()),
+        (_, Some(plist::Value::Array(arguments))) =>
+            arguments.first().and_then(|argument| argument.as_string()).map(String::from),
+        _ => None,
+    }
+}
+
+pub fn preference_reports(domains: &BTreeMap<String, plist::Value>) -> Vec<PreferenceReport> {
+    domains
+        .iter()
+        .map(|(domain, value)| PreferenceReport {
+            domain: domain.to_string(),
+            keys: value.as_dictionary().map(|dictionary| dictionary.len()).unwrap_or_default(),
+        })
+        .collect()
+}
+
+/// Templates an [`AuditReport`] is rendered with: `document` receives
+/// the summary along with the rendered `service` and `preference`
+/// rows, every value going through `escape` first.
+#[derive(Debug, Clone, Copy)]
+pub struct ReportTemplate {
+    pub document: &'static str,
+    pub service: &'static str,
+    pub preference: &'static str,
+    pub escape: fn(&str) -> String,
+}
+impl ReportTemplate {
+    pub fn of(format: ReportFormat) -> ReportTemplate {
+        match format {
+            ReportFormat::Md => ReportTemplate::markdown(),
+            ReportFormat::Html => ReportTemplate::html(),
+        }
+    }
+
+    pub fn markdown() -> ReportTemplate {
+        ReportTemplate {
+            document: MARKDOWN_DOCUMENT,
+            service: MARKDOWN_SERVICE,
+            preference: MARKDOWN_PREFERENCE,
+            escape: markdown_escape,
+        }
+    }
+
+    pub fn html() -> ReportTemplate {
+        ReportTemplate {
+            document: HTML_DOCUMENT,
+            service: HTML_SERVICE,
+            preference: HTML_PREFERENCE,
+            escape: html_escape,
+        }
+    }
+}
+
+const MARKDOWN_DOCUMENT: &str = "# {{title}}
+
+Generated {{generated}} for uid {{uid}}.
+
+## Summary
+
+- services: {{service_count}}
+- running: {{running}}
+- disabled: {{disabled}}
+- high risk: {{high}}
+- medium risk: {{medium}}
+- preference domains: {{preference_count}}
+
+## Agents and daemons
+
+| SERVICE | DOMAIN | PID | STATUS | ENABLED | PROGRAM \
+| RUN AT LOAD | KEEP ALIVE | MACH SERVICES | CATEGORY | RISK |
+|---|---|---:|---:|---|---|---|---|---|---|---|
+{{services}}
+## Preferences
+
+| DOMAIN | KEYS |
+|---|---:|
+{{preferences}}";
+const MARKDOWN_SERVICE: &str =
+    "| `{{service}}` | {{domain}} | {{pid}} | {{status}} | {{enabled}} | {{program}} \
+     | {{run_at_load}} | {{keep_alive}} | {{mach_services}} | {{categories}} | {{risk}} |\n";
+const MARKDOWN_PREFERENCE: &str = "| `{{domain}}` | {{keys}} |\n";
+
+const HTML_DOCUMENT: &str = "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>{{title}}</title>
+<style>
+table { border-collapse: collapse; }
+th, td { border: 1px solid #ccc; padding: 2px 6px; text-align: left; }
+tr.risk-high { background: #fdd; }
+tr.risk-medium { background: #ffd; }
+</style>
+</head>
+<body>
+<h1>{{title}}</h1>
+<p>Generated {{generated}} for uid {{uid}}.</p>
+<h2>Summary</h2>
+<ul>
+<li>services: {{service_count}}</li>
+<li>running: {{running}}</li>
+<li>disabled: {{disabled}}</li>
+<li>high risk: {{high}}</li>
+<li>medium risk: {{medium}}</li>
+<li>preference domains: {{preference_count}}</li>
+</ul>
+<h2>Agents and daemons</h2>
+<table>
+<tr><th>SERVICE</th><th>DOMAIN</th><th>PID</th><th>STATUS</th><th>ENABLED</th><th>PROGRAM</th>\
+<th>RUN AT LOAD</th><th>KEEP ALIVE</th><th>MACH SERVICES</th><th>CATEGORY</th><th>RISK</th></tr>
+{{services}}</table>
+<h2>Preferences</h2>
+<table>
+<tr><th>DOMAIN</th><th>KEYS</th></tr>
+{{preferences}}</table>
+</body>
+</html>
+";
+const HTML_SERVICE: &str = "<tr class=\"risk-{{risk}}\"><td><code>{{service}}</code></td>\
+                            <td>{{domain}}</td><td>{{pid}}</td><td>{{status}}</td>\
+                            <td>{{enabled}}</td><td>{{program}}</td><td>{{run_at_load}}</td>\
+                            <td>{{keep_alive}}</td><td>{{mach_services}}</td>\
+                            <td>{{categories}}</td><td>{{risk}}</td></tr>\n";
+const HTML_PREFERENCE: &str = "<tr><td><code>{{domain}}</code></td><td>{{keys}}</td></tr>\n";
+
+pub fn render_report(report: &AuditReport, template: &ReportTemplate) -> String {
+    let escape = template.escape;
+    let dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+    let yes_no = |value: bool| if value { "yes" } else { "no" }.to_string();
+    let services = report
+        .services
+        .iter()
+        .map(|service| {
+            fill_template(
+                template.service,
+                &[
+                    ("service", escape(&service.service)),
+                    ("domain", escape(&service.domain)),
+                    ("pid", service.pid.to_string()),
+                    ("status", dash(service.status.map(|status| status.to_string()))),
+                    ("enabled", if service.enabled { "enabled" } else { "disabled" }.to_string()),
+                    ("program", escape(&dash(service.program.clone()))),
+                    ("run_at_load", yes_no(service.run_at_load)),
+                    ("keep_alive", yes_no(service.keep_alive)),
+                    (
+                        "mach_services",
+                        escape(&dash(
+                            Some(service.mach_services.join(", "))
+                                .filter(|names| !names.is_empty()),
+                        )),
+                    ),
+                    ("categories", escape(&service.categories.join(", "))),
+                    ("risk", service.risk.to_string()),
+                ],
+            )
+        })
+        .collect::<String>();
+    let preferences = report
+        .preferences
+        .iter()
+        .map(|preference| {
+            fill_template(
+                template.preference,
+                &[("domain", escape(&preference.domain)), ("keys", preference.keys.to_string())],
+            )
+        })
+        .collect::<String>();
+    fill_template(
+        template.document,
+        &[
+            ("title", escape(REPORT_TITLE)),
+            ("generated", format!("{} UTC", format_epoch(report.generated))),
+            ("uid", report.uid.to_string()),
+            ("service_count", report.services.len().to_string()),
+            ("running", report.running().to_string()),
+            ("disabled", report.disabled().to_string()),
+            ("high", report.count(ServiceRisk::High).to_string()),
+            ("medium", report.count(ServiceRisk::Medium).to_string()),
+            ("preference_count", report.preferences.len().to_string()),
+            ("services", services),
+            ("preferences", preferences),
+        ],
+    )
+}
+
+/// Replaces each `{{name}}` of `template` with its value in a single
+/// pass, so that values are never expanded themselves. Unknown names
+/// are left as they are.
+pub fn fill_template(template: &str, values: &[(&str, String)]) -> String {
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let Some(end) = after.find("}}") else {
+            rest = &rest[start..];
+            break;
+        };
+        match values.iter().find(|(name, _)| *name == &after[..end]) {
+            Some((_, value)) => filled.push_str(value),
+            None => filled.push_str(&rest[start..start + end + 4]),
+        }
+        rest = &after[end + 2..];
+    }
+    filled.push_str(rest);
+    filled
+}
+
+pub fn markdown_escape(value: &str) -> String {
+    value.replace('|', "\\|").replace(['\r', '\n'], " ")
+}
+
+pub fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -0,0 +1,365 @@
+use std::io::{Read, Write};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+static COMMAND_RUNNER: RwLock<Option<Arc<dyn CommandRunner>>> = RwLock::new(None);
+static COMMAND_RUNNER_OVERRIDE: Mutex<()> = Mutex::new(());
+
+/// Programs that only exist on macOS, which [`SystemCommandRunner`]
+/// refuses to execute, directly or through `sudo`, unless
+/// [`macos_execution`] is available.
+pub const MACOS_PROGRAMS: &[&str] = &["launchctl", "defaults", "spctl", "mdutil"];
+/// Working directory of `launchctl`, which `sudo -u` would otherwise
+/// start in a directory the target user may not be able to read.
+pub const LAUNCHCTL_WORKING_DIRECTORY: &str = "/System";
+
+/// Whether macOS programs are executed: only in builds for macOS with
+/// the `macos` feature, the default.
+pub fn macos_execution() -> bool {
+    cfg!(all(feature = "macos", target_os = "macos"))
+}
+
+/// The macOS program `program args` executes, if any, looking past
+/// `sudo` and its options.
+pub fn macos_program<'a>(program: &'a str, args: &[&'a str]) -> Option<&'a str> {
+    let name = |path: &'a str| path.rsplit('/').next().unwrap_or(path);
+    let program = match name(program) {
+        "sudo" => {
+            let mut args = args.iter();
+            loop {
+                match *args.next()? {
+                    "-u" | "-g" | "-p" | "-U" | "-C" | "-D" | "-h" | "-r" | "-t" => {
+                        args.next();
+                    },
+                    option if option.starts_with('-') => {},
+                    program => break name(program),
+                }
+            }
+        },
+        program => program,
+    };
+    MACOS_PROGRAMS.contains(&program).then_some(program)
+}
+
+/// Executes external programs on behalf of the library, returning
+/// their exit code, stdout and stderr. Every `launchctl`, `spctl` and
+/// `defaults` call goes through the current runner so that frontends
+/// such as `adm tui` can be exercised against [`FakeCommandRunner`].
+///
+/// Programs are always executed with an argv vector and never through
+/// a shell, so arguments reach them verbatim whatever they contain.
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, program: &str, args: &[&str]) -> Result<(i64, String, String)>;
+
+    /// Like [`CommandRunner::run`] but writing `input` to the stdin of
+    /// the program. Runners that do not execute anything may ignore it.
+    fn run_with_input(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: Option<&[u8]>,
+    ) -> Result<(i64, String, String)> {
+        let _ = input;
+        self.run(program, args)
+    }
+
+    /// Like [`CommandRunner::run`] but failing with
+    /// [`Error::TimeoutError`] once `timeout` elapses. Runners that
+    /// cannot hang may ignore it.
+    fn run_with_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<(i64, String, String)> {
+        let _ = timeout;
+        self.run(program, args)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemCommandRunner;
+impl CommandRunner for SystemCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<(i64, String, String)> {
+        self.execute(program, args, None, None)
+    }
+
+    fn run_with_input(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: Option<&[u8]>,
+    ) -> Result<(i64, String, String)> {
+        self.execute(program, args, input, None)
+    }
+
+    fn run_with_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<(i64, String, String)> {
+        self.execute(program, args, None, timeout)
+    }
+}
+impl SystemCommandRunner {
+    fn execute(
+        &self,
+        program: &str,
+        args: &[&str],
+        input: Option<&[u8]>,
+        timeout: Option<Duration>,
+    ) -> Result<(i64, String, String)> {
+        if let Some(name) = macos_program(program, args).filter(|_| !macos_execution()) {
+            return Err(Error::SystemError(format!(
+                "`{}' is only executed on macOS builds with the `macos` feature",
+                name
+            )));
+        }
+        let mut cmd = Command::new(program);
+        if macos_program(program, args) == Some("launchctl") {
+            cmd.current_dir(LAUNCHCTL_WORKING_DIRECTORY);
+        }
+        let cmd = cmd.args(args);
+        let cmd = cmd.stdin(if input.is_some() { Stdio::piped() } else { Stdio::null() });
+        let cmd = cmd.stdout(Stdio::piped());
+        let cmd = cmd.stderr(Stdio::piped());
+        let mut child = cmd.spawn()?;
+        if let (Some(input), Some(stdin)) = (input, child.stdin.take()) {
+            let input = input.to_vec();
+            // written from another thread so that a program filling its
+            // stdout before reading all of stdin does not deadlock
+            std::thread::spawn(move || {
+                let mut stdin = stdin;
+                stdin.write_all(&input)
+            });
+        }
+        let Some(timeout) = timeout else {
+            let output = child.wait_with_output()?;
+            let exit_code: i64 = output.status.code().unwrap_or_default().into();
+            return Ok((
+                exit_code,
+                String::from_utf8(output.stdout).unwrap_or_default(),
+                String::from_utf8(output.stderr).unwrap_or_default(),
+            ));
+        };
+        let stdout = child.stdout.take().map(read_to_end_in_background);
+        let stderr = child.stderr.take().map(read_to_end_in_background);
+        let started = Instant::now();
+        let status = loop {
+            if let Some(status) = child.try_wait()? {
+                break status;
+            }
+            if started.elapsed() > timeout {
+                child.kill()?;
+                child.wait()?;
+                return Err(Error::TimeoutError(format!(
+                    "`{} {}' did not exit within {}s",
+                    program,
+                    args.join(" "),
+                    timeout.as_secs_f64()
+                )));
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        let output = |reader: Option<std::thread::JoinHandle<Vec<u8>>>| {
+            reader
+                .and_then(|reader| reader.join().ok())
+                .map(|bytes| String::from_utf8(bytes).unwrap_or_default())
+                .unwrap_or_default()
+        };
+        let exit_code: i64 = status.code().unwrap_or_default().into();
+        Ok((exit_code, output(stdout), output(stderr)))
+    }
+}
+
+fn read_to_end_in_background(
+    mut reader: impl Read + Send + 'static,
+) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut bytes = Vec::<u8>::new();
+        reader.read_to_end(&mut bytes).unwrap_or_default();
+        bytes
+    })
+}
+
+/// Answers each command with the output of the first response whose
+/// pattern is part of the command line and records the argv of every
+/// call. Commands without a matching response exit with 127.
+///
+/// Commands matching a [`FakeCommandRunner::sleep`] pattern take that
+/// long to answer, so that timeouts can be exercised.
+#[derive(Debug, Default)]
+pub struct FakeCommandRunner {
+    responses: Vec<(String, (i64, String, String))>,
+    delays: Vec<(String, Duration)>,
+    calls: Mutex<Vec<Vec<String>>>,
+}
+impl FakeCommandRunner {
+    pub fn new() -> FakeCommandRunner {
+        FakeCommandRunner::default()
+    }
+
+    pub fn respond(mut self, pattern: &str, exit_code: i64, stdout: &str) -> FakeCommandRunner {
+        self.responses
+            .push((pattern.to_string(), (exit_code, stdout.to_string(), String::new())));
+        self
+    }
+
+    pub fn fail(mut self, pattern: &str, exit_code: i64, stderr: &str) -> FakeCommandRunner {
+        self.responses
+            .push((pattern.to_string(), (exit_code, String::new(), stderr.to_string())));
+        self
+    }
+
+    pub fn sleep(mut self, pattern: &str, duration: Duration) -> FakeCommandRunner {
+        self.delays.push((pattern.to_string(), duration));
+        self
+    }
+
+    fn delay(&self, command_line: &str) -> Duration {
+        self.delays
+            .iter()
+            .find(|(pattern, _)| command_line.contains(pattern.as_str()))
+            .map(|(_, duration)| *duration)
+            .unwrap_or_default()
+    }
+
+    /// Command lines of every call, arguments joined by spaces.
+    pub fn calls(&self) -> Vec<String> {
+        self.argvs().iter().map(|argv| argv.join(" ")).collect()
+    }
+
+    /// Program and arguments of every call exactly as received.
+    pub fn argvs(&self) -> Vec<Vec<String>> {
+        self.calls.lock().map(|calls| calls.clone()).unwrap_or_default()
+    }
+}
+impl CommandRunner for FakeCommandRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<(i64, String, String)> {
+        self.run_with_timeout(program, args, None)
+    }
+
+    fn run_with_timeout(
+        &self,
+        program: &str,
+        args: &[&str],
+        timeout: Option<Duration>,
+    ) -> Result<(i64, String, String)> {
+        let argv = std::iter::once(program)
+            .chain(args.iter().copied())
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>();
+        let command_line = argv.join(" ");
+        if let Ok(mut calls) = self.calls.lock() {
+            calls.push(argv);
+        }
+        let delay = self.delay(&command_line);
+        match timeout {
+            Some(timeout) if delay > timeout => {
+                std::thread::sleep(timeout);
+                return Err(Error::TimeoutError(format!(
+                    "`{}' did not exit within {}s",
+                    command_line,
+                    timeout.as_secs_f64()
+                )));
+            },
+            _ => std::thread::sleep(delay),
+        }
+        Ok(self
+            .responses
+            .iter()
+            .find(|(pattern, _)| command_line.contains(pattern.as_str()))
+            .map(|(_, output)| output.clone())
+            .unwrap_or_else(|| {
+                (127, String::new(), format!("no fake response for `{}'", command_line))
+            }))
+    }
+}
+
+pub fn set_command_runner(runner: Arc<dyn CommandRunner>) {
+    if let Ok(mut current) = COMMAND_RUNNER.write() {
+        *current = Some(runner);
+    }
+}
+pub fn command_runner() -> Arc<dyn CommandRunner> {
+    COMMAND_RUNNER
+        .read()
+        .ok()
+        .and_then(|runner| runner.clone())
+        .unwrap_or_else(|| Arc::new(SystemCommandRunner))
+}
+
+/// Makes `runner` the command runner until the returned guard drops,
+/// one override at a time, so that tests running in parallel threads
+/// each see the runner they set.
+pub fn override_command_runner(runner: Arc<dyn CommandRunner>) -> CommandRunnerOverride {
+    let lock = COMMAND_RUNNER_OVERRIDE.lock().unwrap_or_else(|error| error.into_inner());
+    let previous = COMMAND_RUNNER.write().ok().and_then(|mut current| current.replace(runner));
+    CommandRunnerOverride { previous, _lock: lock }
+}
+/// Guard of [`override_command_runner`], which puts the previous
+/// runner back when dropped.
+pub struct CommandRunnerOverride {
+    previous: Option<Arc<dyn CommandRunner>>,
+    _lock: MutexGuard<'static, ()>,
+}
+impl Drop for CommandRunnerOverride {
+    fn drop(&mut self) {
+        if let Ok(mut current) = COMMAND_RUNNER.write() {
+            *current = self.previous.take();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use super::{
+        command_runner, macos_execution, macos_program, override_command_runner, CommandRunner,
+        FakeCommandRunner, SystemCommandRunner,
+    };
+    use crate::Error;
+
+    #[test]
+    fn test_system_runner_kills_commands_that_time_out() {
+        let started = Instant::now();
+        let timeout = Some(Duration::from_millis(200));
+        let result = SystemCommandRunner.run_with_timeout("sleep", &["10"], timeout);
+        assert!(matches!(result, Err(Error::TimeoutError(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+
+        let (exit_code, stdout, _) = SystemCommandRunner
+            .run_with_timeout("echo", &["a b", "$(c)"], Some(Duration::from_secs(5)))
+            .unwrap();
+        assert_eq!((exit_code, stdout.as_str()), (0, "a b $(c)\n"));
+    }
+
+    #[test]
+    fn test_macos_programs_are_refused_elsewhere() {
+        assert_eq!(macos_program("/bin/launchctl", &["list"]), Some("launchctl"));
+        assert_eq!(macos_program("sudo", &["-n", "/usr/sbin/spctl", "--status"]), Some("spctl"));
+        assert_eq!(macos_program("sudo", &["-u", "me", "/bin/launchctl"]), Some("launchctl"));
+        assert_eq!(macos_program("sudo", &["/usr/bin/true"]), None);
+        assert_eq!(macos_program("echo", &["launchctl"]), None);
+        if !macos_execution() {
+            let result = SystemCommandRunner.run("/bin/launchctl", &["list"]);
+            assert!(matches!(result, Err(Error::SystemError(_))));
+        }
+    }
+
+    #[test]
+    fn test_override_puts_the_previous_runner_back() {
+        let guard =
+            override_command_runner(Arc::new(FakeCommandRunner::new().respond("", 0, "fake")));
+        assert_eq!(command_runner().run("/usr/bin/true", &[]).unwrap().1, "fake");
+        drop(guard);
+        let (exit_code, stdout, _) = command_runner().run("echo", &["system"]).unwrap();
+        assert_eq!((exit_code, stdout.as_str()), (0, "system\n"));
+    }
+}
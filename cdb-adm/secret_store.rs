@@ -0,0 +1,232 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use crate::encryption::random_bytes;
+use crate::{BackupKey, EncryptedBundle, Error, Result};
+
+static SECRET_STORE: RwLock<Option<Arc<dyn SecretStore>>> = RwLock::new(None);
+
+/// Message of the [`Error::KeychainError`] every store returns for a
+/// missing secret, the one of the macOS keychain.
+pub const SECRET_NOT_FOUND: &str = "The specified item could not be found in the keychain.";
+pub const DEFAULT_SECRET_STORE_PATH: &str = "~/.config/cdb-adm-secrets.json";
+const SECRET_STORE_KEY_ID: &str = "secret-store";
+
+/// Hex-encoded data of each account of each service.
+type Secrets = BTreeMap<String, BTreeMap<String, String>>;
+
+/// Keeps the generic passwords of [`KeychainData`](crate::KeychainData),
+/// each identified by a service and an account. The current store is
+/// the macOS keychain when built for macOS with the `macos` feature
+/// and a [`FileSecretStore`] otherwise,
+/// unless `CDB_ADM_SECRET_STORE` names another one or
+/// [`set_secret_store`] replaces it.
+pub trait SecretStore: Send + Sync {
+    fn set(&self, service: &str, account: &str, data: &[u8]) -> Result<()>;
+    fn get(&self, service: &str, account: &str) -> Result<Vec<u8>>;
+    fn delete(&self, service: &str, account: &str) -> Result<()>;
+}
+
+fn not_found() -> Error {
+    Error::KeychainError(SECRET_NOT_FOUND.to_string())
+}
+
+/// Whether `error` only says that a secret does not exist, as opposed
+/// to the store being locked or access to it being denied.
+pub fn is_secret_not_found(error: &Error) -> bool {
+    matches!(error, Error::KeychainError(message) if message == SECRET_NOT_FOUND)
+}
+
+fn poisoned<T>(error: PoisonError<T>) -> Error {
+    Error::KeychainError(error.to_string())
+}
+
+#[cfg(all(feature = "macos", target_os = "macos"))]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeychainSecretStore;
+#[cfg(all(feature = "macos", target_os = "macos"))]
+impl SecretStore for KeychainSecretStore {
+    fn set(&self, service: &str, account: &str, data: &[u8]) -> Result<()> {
+        use security_framework::os::macos::keychain::{SecKeychain, SecPreferencesDomain};
+        let keychain = SecKeychain::default_for_domain(SecPreferencesDomain::User)?;
+        keychain.set_generic_password(service, account, data)?;
+        Ok(())
+    }
+
+    fn get(&self, service: &str, account: &str) -> Result<Vec<u8>> {
+        use security_framework::os::macos::keychain::SecKeychain;
+        let keychain = SecKeychain::default()?;
+        let (item, _) = keychain.find_generic_password(service, account)?;
+        Ok(item.to_vec())
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        use security_framework::os::macos::keychain::SecKeychain;
+        let keychain = SecKeychain::default()?;
+        let (_, item) = keychain.find_generic_password(service, account)?;
+        item.delete();
+        Ok(())
+    }
+}
+
+/// Secrets kept in process memory, lost on exit.
+#[derive(Debug, Default)]
+pub struct MemorySecretStore {
+    secrets: Mutex<BTreeMap<(String, String), Vec<u8>>>,
+}
+impl SecretStore for MemorySecretStore {
+    fn set(&self, service: &str, account: &str, data: &[u8]) -> Result<()> {
+        let mut secrets = self.secrets.lock().map_err(poisoned)?;
+        secrets.insert((service.to_string(), account.to_string()), data.to_vec());
+        Ok(())
+    }
+
+    fn get(&self, service: &str, account: &str) -> Result<Vec<u8>> {
+        let secrets = self.secrets.lock().map_err(poisoned)?;
+        secrets.get(&(service.to_string(), account.to_string())).cloned().ok_or_else(not_found)
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        let mut secrets = self.secrets.lock().map_err(poisoned)?;
+        match secrets.remove(&(service.to_string(), account.to_string())) {
+            Some(_) => Ok(()),
+            None => Err(not_found()),
+        }
+    }
+}
+
+/// Secrets kept in a JSON file sealed as an [`EncryptedBundle`], its
+/// key being read from `CDB_ADM_SECRET_STORE_KEY` as hex or else from
+/// a `.key` file next to it readable only by its owner, generated on
+/// first use.
+#[derive(Debug)]
+pub struct FileSecretStore {
+    path: iocore::Path,
+    lock: Mutex<()>,
+}
+impl FileSecretStore {
+    pub fn new(path: &iocore::Path) -> FileSecretStore {
+        FileSecretStore { path: path.clone(), lock: Mutex::new(()) }
+    }
+
+    pub fn path(&self) -> iocore::Path {
+        self.path.clone()
+    }
+
+    pub fn key_path(&self) -> iocore::Path {
+        iocore::Path::raw(format!("{}.key", self.path))
+    }
+
+    fn key(&self) -> Result<BackupKey> {
+        let (source, data) = match iocore::env::var("CDB_ADM_SECRET_STORE_KEY") {
+            Ok(data) => ("CDB_ADM_SECRET_STORE_KEY".to_string(), data),
+            Err(_) => {
+                let key_path = self.key_path();
+                if !key_path.is_file() {
+                    key_path.write(b"")?;
+                    key_path.clone().set_mode(0o600)?;
+                    key_path.write(hex::encode(random_bytes(32)?).as_bytes())?;
+                }
+                (key_path.to_string(), key_path.read()?)
+            },
+        };
+        let bytes = hex::decode(data.trim())
+            .map_err(|error| Error::KeychainError(format!("{} is not hex: {}", source, error)))?;
+        BackupKey::new(SECRET_STORE_KEY_ID, &bytes)
+    }
+
+    fn read(&self, key: &BackupKey) -> Result<Secrets> {
+        if !self.path.is_file() {
+            return Ok(BTreeMap::new());
+        }
+        let data = self.path.read()?;
+        let bundle = EncryptedBundle::parse(&data).ok_or_else(|| {
+            Error::KeychainError(format!("{} is not an encrypted secret store", self.path))
+        })?;
+        Ok(serde_json::from_slice(&bundle.open(key)?)?)
+    }
+
+    fn write(&self, key: &BackupKey, secrets: &Secrets) -> Result<()> {
+        let bundle = EncryptedBundle::seal(key, serde_json::to_string(secrets)?.as_bytes())?;
+        if !self.path.is_file() {
+            self.path.write(b"")?;
+            self.path.clone().set_mode(0o600)?;
+        }
+        self.path.write(serde_json::to_string_pretty(&bundle)?.as_bytes())?;
+        Ok(())
+    }
+
+    fn update<T>(
+        &self,
+        change: impl FnOnce(&mut Secrets) -> Result<T>,
+    ) -> Result<T> {
+        let _guard = self.lock.lock().map_err(poisoned)?;
+        let key = self.key()?;
+        let mut secrets = self.read(&key)?;
+        let result = change(&mut secrets)?;
+        self.write(&key, &secrets)?;
+        Ok(result)
+    }
+}
+impl Default for FileSecretStore {
+    fn default() -> FileSecretStore {
+        FileSecretStore::new(&iocore::Path::raw(DEFAULT_SECRET_STORE_PATH).try_canonicalize())
+    }
+}
+impl SecretStore for FileSecretStore {
+    fn set(&self, service: &str, account: &str, data: &[u8]) -> Result<()> {
+        self.update(|secrets| {
+            secrets
+                .entry(service.to_string())
+                .or_default()
+                .insert(account.to_string(), hex::encode(data));
+            Ok(())
+        })
+    }
+
+    fn get(&self, service: &str, account: &str) -> Result<Vec<u8>> {
+        let _guard = self.lock.lock().map_err(poisoned)?;
+        let secrets = self.read(&self.key()?)?;
+        let data =
+            secrets.get(service).and_then(|accounts| accounts.get(account)).ok_or_else(not_found)?;
+        hex::decode(data).map_err(|error| Error::KeychainError(error.to_string()))
+    }
+
+    fn delete(&self, service: &str, account: &str) -> Result<()> {
+        self.update(|secrets| {
+            let accounts = secrets.get_mut(service).ok_or_else(not_found)?;
+            accounts.remove(account).ok_or_else(not_found)?;
+            if accounts.is_empty() {
+                secrets.remove(service);
+            }
+            Ok(())
+        })
+    }
+}
+
+pub fn set_secret_store(store: Arc<dyn SecretStore>) {
+    if let Ok(mut current) = SECRET_STORE.write() {
+        *current = Some(store);
+    }
+}
+pub fn secret_store() -> Arc<dyn SecretStore> {
+    if let Some(store) = SECRET_STORE.read().ok().and_then(|store| store.clone()) {
+        return store;
+    }
+    let store = default_secret_store();
+    set_secret_store(store.clone());
+    store
+}
+
+/// Store named by `CDB_ADM_SECRET_STORE`, one of `keychain`, `file`
+/// and `memory`, defaulting to the keychain where there is one.
+fn default_secret_store() -> Arc<dyn SecretStore> {
+    match iocore::env::var("CDB_ADM_SECRET_STORE").unwrap_or_default().as_str() {
+        "memory" => Arc::new(MemorySecretStore::default()),
+        "file" => Arc::new(FileSecretStore::default()),
+        #[cfg(all(feature = "macos", target_os = "macos"))]
+        _ => Arc::new(KeychainSecretStore),
+        #[cfg(not(all(feature = "macos", target_os = "macos")))]
+        _ => Arc::new(FileSecretStore::default()),
+    }
+}
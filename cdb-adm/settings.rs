@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-const DEFAULT_BACKUP_PATH: &'static str = "~/cdb-adm-backup";
+use crate::{ExecutionSettings, HealthCheck, HealthCheckSettings, Policy};
+
+pub(crate) const DEFAULT_BACKUP_PATH: &str = "~/cdb-adm-backup";
 const DEFAULT_SETTINGS_PATH: &'static str = "~/.config/cdb-adm.toml";
 const DEFAULT_CDB_SETTINGS_PATH: &'static str = "~/.config/cdb.toml";
 const DEFAULT_ADM_SETTINGS_PATH: &'static str = "~/.config/adm.toml";
@@ -19,8 +21,12 @@ pub struct Settings {
 
     pub cdb: Option<CDBSettings>,
     pub adm: Option<ADMSettings>,
+    #[serde(default = "default_settings_path")]
     path: iocore::Path,
 }
+fn default_settings_path() -> iocore::Path {
+    crate::layers::settings_path::<Settings>()
+}
 impl Default for Settings {
     fn default() -> Settings {
         Settings {
@@ -39,6 +45,20 @@ impl SettingsEnvPath for Settings {
     fn default_path() -> &'static str {
         DEFAULT_SETTINGS_PATH
     }
+
+    /// Merges the [`settings_layers`](crate::settings_layers) rather
+    /// than reading a single file.
+    fn detect() -> crate::Result<Settings> {
+        let mut settings = toml::Value::Table(crate::layered_settings_table()?)
+            .try_into::<Settings>()
+            .map_err(|error| {
+                crate::Error::SettingsError(format!("merging settings layers: {}", error))
+            })?;
+        if settings.backup_path.is_none() {
+            settings.backup_path = Some(default_backup_path().to_string());
+        }
+        Ok(settings)
+    }
 }
 
 impl Settings {
@@ -62,6 +82,8 @@ impl Settings {
                 "'backup_path' cannot be a file: {:#?}",
                 path.to_string()
             )))
+        } else if let Some(problem) = crate::backup_path_problem(&path) {
+            Err(crate::Error::ConfigurationError(problem))
         } else {
             Ok(())
         }
@@ -75,6 +97,7 @@ impl Settings {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CDBSettingsDelete {
     pub domains: Vec<String>,
     pub keys: Vec<Vec<String>>,
@@ -106,7 +129,9 @@ impl CDBSettingsDelete {
     }
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct CDBSettings {
+    encrypt_backups: bool,
     delete: CDBSettingsDelete,
 }
 impl SettingsEnvPath for CDBSettings {
@@ -117,35 +142,76 @@ impl SettingsEnvPath for CDBSettings {
     fn default_path() -> &'static str {
         DEFAULT_CDB_SETTINGS_PATH
     }
+
+    fn detect() -> crate::Result<CDBSettings> {
+        Ok(Settings::detect()?.cdb())
+    }
 }
 
 impl CDBSettings {
     pub fn defaults_exec_args(&self) -> Vec<Vec<String>> {
         self.delete.defaults_exec_args()
     }
+
+    pub fn encrypt_backups(&self) -> bool {
+        self.encrypt_backups
+    }
 }
 impl Default for CDBSettings {
     fn default() -> CDBSettings {
         CDBSettings {
+            encrypt_backups: false,
             delete: CDBSettingsDelete::default(),
         }
     }
 }
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ADMSettings {
     display_warnings: bool,
     include_non_needed: bool,
     include_system_uids: bool,
+    checks: Vec<HealthCheckSettings>,
+    policy: Policy,
+    execution: ExecutionSettings,
 }
 impl Default for ADMSettings {
     fn default() -> ADMSettings {
         ADMSettings {
             display_warnings: true,
-            include_non_needed: true,
+            include_non_needed: false,
             include_system_uids: true,
+            checks: Vec::new(),
+            policy: Policy::default(),
+            execution: ExecutionSettings::default(),
         }
     }
 }
+impl ADMSettings {
+    pub fn display_warnings(&self) -> bool {
+        self.display_warnings
+    }
+
+    pub fn include_non_needed(&self) -> bool {
+        self.include_non_needed
+    }
+
+    pub fn include_system_uids(&self) -> bool {
+        self.include_system_uids
+    }
+
+    pub fn health_checks(&self) -> Vec<Box<dyn HealthCheck>> {
+        self.checks.iter().map(|check| check.health_check()).collect()
+    }
+
+    pub fn policy(&self) -> Policy {
+        self.policy.clone()
+    }
+
+    pub fn execution(&self) -> ExecutionSettings {
+        self.execution.clone()
+    }
+}
 impl SettingsEnvPath for ADMSettings {
     fn env_var_name() -> &'static str {
         "ADM_SETTINGS"
@@ -154,6 +220,10 @@ impl SettingsEnvPath for ADMSettings {
     fn default_path() -> &'static str {
         DEFAULT_ADM_SETTINGS_PATH
     }
+
+    fn detect() -> crate::Result<ADMSettings> {
+        Ok(Settings::detect()?.adm())
+    }
 }
 
 pub trait SettingsEnvPath: Default + serde::de::DeserializeOwned {
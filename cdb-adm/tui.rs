@@ -0,0 +1,562 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::Write;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    boot_up_agents_and_daemons, label_matches, list_all_agents_and_daemons, service_statuses,
+    turn_off_agents_and_daemons, Error, Result, ServiceCatalog, ServiceStatus, Uid,
+};
+
+pub const DEFAULT_TUI_REFRESH_SECS: u64 = 5;
+pub const TUI_JOURNAL_FILENAME: &str = "adm-journal.jsonl";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TuiAction {
+    TurnOff,
+    BootUp,
+}
+impl TuiAction {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            TuiAction::TurnOff => "bootout and disable",
+            TuiAction::BootUp => "enable and kickstart",
+        }
+    }
+}
+
+/// Keys understood by [`TuiState::handle_key`], decoupled from the
+/// terminal backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TuiKey {
+    Char(char),
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Enter,
+    Esc,
+    Backspace,
+    Interrupt,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TuiCommand {
+    Apply(TuiAction, Vec<ServiceStatus>),
+    Refresh,
+    Quit,
+}
+
+/// State of `adm tui`: the services listed by
+/// `list_all_agents_and_daemons`, the plists of those with a known
+/// path, the incremental search, the multi-selection and the pending
+/// confirmation.
+#[derive(Debug, Clone, Default)]
+pub struct TuiState {
+    pub services: Vec<ServiceStatus>,
+    pub plists: BTreeMap<String, plist::Dictionary>,
+    pub search: String,
+    pub searching: bool,
+    pub cursor: usize,
+    pub selected: BTreeSet<String>,
+    pub confirm: Option<TuiAction>,
+    pub message: Option<String>,
+}
+impl TuiState {
+    #[allow(clippy::type_complexity)]
+    pub fn new(
+        agents_and_daemons: &[(
+            String,
+            String,
+            i64,
+            Option<i64>,
+            bool,
+            Option<(iocore::Path, plist::Dictionary)>,
+        )],
+        catalog: &ServiceCatalog,
+    ) -> TuiState {
+        let mut state = TuiState::default();
+        state.update(agents_and_daemons, catalog);
+        state
+    }
+
+    /// Replaces the services with a fresh listing while keeping the
+    /// search, the selection of services still present and the
+    /// cursor on the same service whenever possible.
+    #[allow(clippy::type_complexity)]
+    pub fn update(
+        &mut self,
+        agents_and_daemons: &[(
+            String,
+            String,
+            i64,
+            Option<i64>,
+            bool,
+            Option<(iocore::Path, plist::Dictionary)>,
+        )],
+        catalog: &ServiceCatalog,
+    ) {
+        let current = self.current().map(|status| status.target());
+        let mut services = service_statuses(agents_and_daemons, catalog);
+        services.sort_by(|a, b| a.service.cmp(&b.service).then(a.domain.cmp(&b.domain)));
+        self.services = services;
+        self.plists = agents_and_daemons
+            .iter()
+            .filter_map(|(_, service, _, _, _, info)| {
+                info.as_ref().map(|(_, dictionary)| (service.to_string(), dictionary.clone()))
+            })
+            .collect();
+        let targets =
+            self.services.iter().map(|status| status.target()).collect::<BTreeSet<String>>();
+        self.selected.retain(|target| targets.contains(target));
+        let visible = self.visible();
+        self.cursor = current
+            .and_then(|target| visible.iter().position(|status| status.target() == target))
+            .unwrap_or(self.cursor)
+            .min(visible.len().saturating_sub(1));
+    }
+
+    /// Services whose label matches the search, see [`label_matches`].
+    pub fn visible(&self) -> Vec<&ServiceStatus> {
+        self.services
+            .iter()
+            .filter(|status| self.search.is_empty() || label_matches(&self.search, &status.service))
+            .collect()
+    }
+
+    pub fn current(&self) -> Option<&ServiceStatus> {
+        self.visible().get(self.cursor).copied()
+    }
+
+    /// Selected services or, when none is selected, the one under the
+    /// cursor.
+    pub fn targets(&self) -> Vec<ServiceStatus> {
+        if self.selected.is_empty() {
+            self.current().cloned().into_iter().collect()
+        } else {
+            self.services
+                .iter()
+                .filter(|status| self.selected.contains(&status.target()))
+                .cloned()
+                .collect()
+        }
+    }
+
+    /// Sets the message to the outcome of `action`, which failing as a
+    /// whole leaves the interface running all the same.
+    pub fn show_outcome(&mut self, action: TuiAction, result: &Result<Vec<TuiJournalEntry>>) {
+        let entries = match result {
+            Ok(entries) => entries,
+            Err(error) => {
+                self.message = Some(format!("{} failed: {}", action.describe(), error));
+                return;
+            },
+        };
+        let failed = entries.iter().filter(|entry| !entry.ok).collect::<Vec<_>>();
+        self.message = Some(match failed.first() {
+            None => format!("{}: {} ok", action.describe(), entries.len()),
+            Some(entry) => format!(
+                "{}: {} ok, {} failed, e.g.: {}: {}",
+                action.describe(),
+                entries.len() - failed.len(),
+                failed.len(),
+                entry.target,
+                entry.error.as_ref().map(|error| error.to_string()).unwrap_or_default()
+            ),
+        });
+    }
+
+    pub fn handle_key(&mut self, key: TuiKey) -> Option<TuiCommand> {
+        if key == TuiKey::Interrupt {
+            return Some(TuiCommand::Quit);
+        }
+        if let Some(action) = self.confirm {
+            self.confirm = None;
+            return match key {
+                TuiKey::Char('y') | TuiKey::Char('Y') => {
+                    let targets = self.targets();
+                    self.selected.clear();
+                    Some(TuiCommand::Apply(action, targets))
+                },
+                _ => {
+                    self.message = Some(format!("{} cancelled", action.describe()));
+                    None
+                },
+            };
+        }
+        if self.searching {
+            match key {
+                TuiKey::Char(c) => self.search.push(c),
+                TuiKey::Backspace => {
+                    self.search.pop();
+                },
+                TuiKey::Enter => self.searching = false,
+                TuiKey::Esc => {
+                    self.search.clear();
+                    self.searching = false;
+                },
+                _ => {},
+            }
+            self.cursor = 0;
+            return None;
+        }
+        let count = self.visible().len();
+        let page = 10;
+        self.message = None;
+        match key {
+            TuiKey::Char('q') => return Some(TuiCommand::Quit),
+            TuiKey::Esc if self.search.is_empty() => return Some(TuiCommand::Quit),
+            TuiKey::Esc => {
+                self.search.clear();
+                self.cursor = 0;
+            },
+            TuiKey::Char('/') => self.searching = true,
+            TuiKey::Up | TuiKey::Char('k') => self.cursor = self.cursor.saturating_sub(1),
+            TuiKey::Down | TuiKey::Char('j') =>
+                self.cursor = (self.cursor + 1).min(count.saturating_sub(1)),
+            TuiKey::PageUp => self.cursor = self.cursor.saturating_sub(page),
+            TuiKey::PageDown => self.cursor = (self.cursor + page).min(count.saturating_sub(1)),
+            TuiKey::Home | TuiKey::Char('g') => self.cursor = 0,
+            TuiKey::End | TuiKey::Char('G') => self.cursor = count.saturating_sub(1),
+            TuiKey::Char(' ') =>
+                if let Some(target) = self.current().map(|status| status.target()) {
+                    if !self.selected.remove(&target) {
+                        self.selected.insert(target);
+                    }
+                    self.cursor = (self.cursor + 1).min(count.saturating_sub(1));
+                },
+            TuiKey::Char('a') => {
+                let targets =
+                    self.visible().iter().map(|status| status.target()).collect::<Vec<String>>();
+                self.selected.extend(targets);
+            },
+            TuiKey::Char('u') => self.selected.clear(),
+            TuiKey::Char('d') | TuiKey::Char('e') if self.targets().is_empty() => {},
+            TuiKey::Char('d') => self.confirm = Some(TuiAction::TurnOff),
+            TuiKey::Char('e') => self.confirm = Some(TuiAction::BootUp),
+            TuiKey::Char('r') => return Some(TuiCommand::Refresh),
+            _ => {},
+        }
+        None
+    }
+
+    /// Lines of a `width` x `height` screen: header, service list,
+    /// detail pane of the service under the cursor and a status line
+    /// holding the search, the confirmation prompt or the last message.
+    pub fn render(&self, width: usize, height: usize) -> Vec<String> {
+        let visible = self.visible();
+        let mut lines = vec![
+            format!(
+                "adm tui: {} of {} services, {} selected | / search, space select, a all, \
+                 u none, d turn off, e boot up, r refresh, q quit",
+                visible.len(),
+                self.services.len(),
+                self.selected.len()
+            ),
+            format!(
+                "    {:<label$} {:>7} {:>7} {:<8} {}",
+                "SERVICE",
+                "PID",
+                "STATUS",
+                "ENABLED",
+                "DOMAIN",
+                label = label_width(width)
+            ),
+        ];
+        let details = self.details();
+        let detail_height = if height > 12 { (height / 3).min(details.len() + 1) } else { 0 };
+        let list_height = height.saturating_sub(lines.len() + detail_height + 1);
+        let offset = (self.cursor + 1).saturating_sub(list_height);
+        for (index, status) in visible.iter().enumerate().skip(offset).take(list_height) {
+            lines.push(format!(
+                "{}{} {:<label$} {:>7} {:>7} {:<8} {}",
+                if index == self.cursor { ">" } else { " " },
+                if self.selected.contains(&status.target()) { "[x]" } else { "[ ]" },
+                status.service,
+                status.pid,
+                status.status.map(|status| status.to_string()).unwrap_or_else(|| "-".to_string()),
+                if status.enabled { "enabled" } else { "disabled" },
+                status.domain,
+                label = label_width(width)
+            ));
+        }
+        while lines.len() < height.saturating_sub(detail_height + 1) {
+            lines.push(String::new());
+        }
+        if detail_height > 0 {
+            lines.push("-".repeat(width));
+            lines.extend(details.into_iter().take(detail_height - 1));
+        }
+        while lines.len() < height.saturating_sub(1) {
+            lines.push(String::new());
+        }
+        lines.push(if self.searching {
+            format!("/{}", self.search)
+        } else if let Some(action) = self.confirm {
+            let targets = self.targets();
+            format!(
+                "{} {}? [y/N]",
+                action.describe(),
+                match targets.as_slice() {
+                    [status] => status.target(),
+                    targets => format!("{} services", targets.len()),
+                }
+            )
+        } else if let Some(message) = &self.message {
+            message.to_string()
+        } else if !self.search.is_empty() {
+            format!("search: {}", self.search)
+        } else {
+            String::new()
+        });
+        lines.into_iter().take(height).map(|line| line.chars().take(width).collect()).collect()
+    }
+
+    /// Detail pane of the service under the cursor: its target, plist
+    /// path, categories and the entries of the parsed plist.
+    pub fn details(&self) -> Vec<String> {
+        let status = match self.current() {
+            Some(status) => status,
+            None => return Vec::new(),
+        };
+        let mut lines = vec![
+            status.target(),
+            format!("path: {}", status.path.clone().unwrap_or_else(|| "-".to_string())),
+            format!("categories: {}", status.categories.join(", ")),
+        ];
+        if let Some(dictionary) = self.plists.get(&status.service) {
+            for (key, value) in dictionary {
+                lines.push(format!("{}: {}", key, plist_value_summary(value)));
+            }
+        }
+        lines
+    }
+}
+
+fn label_width(width: usize) -> usize {
+    width.saturating_sub(4 + 8 + 8 + 9 + 12).max(16)
+}
+
+fn plist_value_summary(value: &plist::Value) -> String {
+    match value {
+        plist::Value::String(string) => string.to_string(),
+        plist::Value::Boolean(boolean) => boolean.to_string(),
+        plist::Value::Integer(integer) => integer.to_string(),
+        plist::Value::Real(real) => real.to_string(),
+        plist::Value::Data(data) => format!("<{} bytes>", data.len()),
+        plist::Value::Array(array) =>
+            format!("[{}]", array.iter().map(plist_value_summary).collect::<Vec<_>>().join(", ")),
+        plist::Value::Dictionary(dictionary) => format!(
+            "{{{}}}",
+            dictionary
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, plist_value_summary(value)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        value => format!("{:?}", value),
+    }
+}
+
+/// Record of a change made through `adm tui`, appended as JSON lines
+/// to the journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TuiJournalEntry {
+    pub timestamp: u64,
+    pub action: TuiAction,
+    pub target: String,
+    pub pid: i64,
+    pub ok: bool,
+    pub error: Option<Error>,
+}
+
+pub fn append_tui_journal(
+    path: &iocore::Path,
+    action: TuiAction,
+    results: &[(String, String, i64, Result<()>)],
+) -> Result<Vec<TuiJournalEntry>> {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let entries = results
+        .iter()
+        .map(|(domain, service, pid, result)| TuiJournalEntry {
+            timestamp,
+            action,
+            target: format!("{}/{}", domain, service),
+            pid: *pid,
+            ok: result.is_ok(),
+            error: result.as_ref().err().cloned(),
+        })
+        .collect::<Vec<TuiJournalEntry>>();
+    let mut lines = String::new();
+    for entry in &entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    if !path.is_file() {
+        path.write(b"")?;
+    }
+    path.append(lines.as_bytes())?;
+    Ok(entries)
+}
+
+pub fn read_tui_journal(path: &iocore::Path) -> Result<Vec<TuiJournalEntry>> {
+    let mut entries = Vec::<TuiJournalEntry>::new();
+    for line in path.read()?.lines().filter(|line| !line.trim().is_empty()) {
+        entries.push(serde_json::from_str(line)?);
+    }
+    Ok(entries)
+}
+
+/// Applies `action` to `targets` through the same library calls as
+/// `adm turn-off` and `adm boot-up` and journals the outcome.
+pub fn apply_tui_action(
+    uid: &Uid,
+    action: TuiAction,
+    targets: &[ServiceStatus],
+    plists: &BTreeMap<String, plist::Dictionary>,
+    journal: &iocore::Path,
+) -> Result<Vec<TuiJournalEntry>> {
+    let results = match action {
+        TuiAction::TurnOff => turn_off_agents_and_daemons(
+            uid,
+            true,
+            targets
+                .iter()
+                .map(|status| (status.domain.to_string(), status.service.to_string(), status.pid))
+                .collect(),
+            false,
+        ),
+        TuiAction::BootUp => {
+            let mut results = Vec::<(String, String, i64, Result<()>)>::new();
+            let mut to_boot_up = Vec::new();
+            for status in targets {
+                match (&status.path, plists.get(&status.service)) {
+                    (Some(path), Some(dictionary)) => to_boot_up.push((
+                        status.domain.to_string(),
+                        status.service.to_string(),
+                        status.pid,
+                        (iocore::Path::raw(path), dictionary.clone()),
+                    )),
+                    _ => results.push((
+                        status.domain.to_string(),
+                        status.service.to_string(),
+                        status.pid,
+                        Err(Error::LaunchdError(format!(
+                            "path not found for {:#?}",
+                            status.service
+                        ))),
+                    )),
+                }
+            }
+            results.extend(boot_up_agents_and_daemons(true, to_boot_up));
+            results
+        },
+    };
+    append_tui_journal(journal, action, &results)
+}
+
+struct TerminalGuard;
+impl TerminalGuard {
+    fn enter() -> Result<TerminalGuard> {
+        crossterm::terminal::enable_raw_mode()?;
+        crossterm::execute!(
+            std::io::stdout(),
+            crossterm::terminal::EnterAlternateScreen,
+            crossterm::cursor::Hide
+        )?;
+        Ok(TerminalGuard)
+    }
+}
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(
+            std::io::stdout(),
+            crossterm::cursor::Show,
+            crossterm::terminal::LeaveAlternateScreen
+        );
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+fn tui_key(event: crossterm::event::KeyEvent) -> Option<TuiKey> {
+    use crossterm::event::{KeyCode, KeyEventKind, KeyModifiers};
+    if event.kind != KeyEventKind::Press {
+        return None;
+    }
+    Some(match event.code {
+        KeyCode::Char('c') if event.modifiers.contains(KeyModifiers::CONTROL) => TuiKey::Interrupt,
+        KeyCode::Char(c) => TuiKey::Char(c),
+        KeyCode::Up => TuiKey::Up,
+        KeyCode::Down => TuiKey::Down,
+        KeyCode::PageUp => TuiKey::PageUp,
+        KeyCode::PageDown => TuiKey::PageDown,
+        KeyCode::Home => TuiKey::Home,
+        KeyCode::End => TuiKey::End,
+        KeyCode::Enter => TuiKey::Enter,
+        KeyCode::Esc => TuiKey::Esc,
+        KeyCode::Backspace => TuiKey::Backspace,
+        _ => return None,
+    })
+}
+
+fn draw(lines: &[String]) -> Result<()> {
+    use crossterm::{cursor, queue, terminal};
+    let mut stdout = std::io::stdout();
+    for (row, line) in lines.iter().enumerate() {
+        queue!(
+            stdout,
+            cursor::MoveTo(0, row as u16),
+            terminal::Clear(terminal::ClearType::CurrentLine),
+            crossterm::style::Print(line)
+        )?;
+    }
+    stdout.flush()?;
+    Ok(())
+}
+
+/// Runs `adm tui` until the user quits, reloading the services every
+/// `refresh` and after each change.
+pub fn run_tui(uid: &Uid, refresh: Duration, journal: &iocore::Path) -> Result<()> {
+    let catalog = ServiceCatalog::builtin();
+    let mut state = TuiState::new(&list_all_agents_and_daemons(uid)?, &catalog);
+    let _guard = TerminalGuard::enter()?;
+    let mut last_refresh = Instant::now();
+    loop {
+        let (width, height) = crossterm::terminal::size()?;
+        draw(&state.render(width as usize, height as usize))?;
+        let timeout = refresh.saturating_sub(last_refresh.elapsed());
+        let command = if crossterm::event::poll(timeout)? {
+            match crossterm::event::read()? {
+                crossterm::event::Event::Key(event) => match tui_key(event) {
+                    Some(key) => state.handle_key(key),
+                    None => None,
+                },
+                _ => None,
+            }
+        } else {
+            Some(TuiCommand::Refresh)
+        };
+        match command {
+            Some(TuiCommand::Quit) => break,
+            Some(TuiCommand::Refresh) => {},
+            Some(TuiCommand::Apply(action, targets)) => {
+                state.message =
+                    Some(format!("{} {} services...", action.describe(), targets.len()));
+                draw(&state.render(width as usize, height as usize))?;
+                let result = apply_tui_action(uid, action, &targets, &state.plists, journal);
+                state.show_outcome(action, &result);
+            },
+            None => continue,
+        }
+        match list_all_agents_and_daemons(uid) {
+            Ok(agents_and_daemons) => state.update(&agents_and_daemons, &catalog),
+            Err(error) => state.message = Some(format!("refreshing failed: {}", error)),
+        }
+        last_refresh = Instant::now();
+    }
+    Ok(())
+}
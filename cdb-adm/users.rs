@@ -0,0 +1,217 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result, Uid};
+
+pub const PASSWD_PATH: &str = "/etc/passwd";
+pub const DSCL_PATH: &str = "/usr/bin/dscl";
+pub const CONSOLE_PATH: &str = "/dev/console";
+/// Uids below this one belong to the system on macOS, the first login
+/// user getting 501.
+pub const FIRST_LOGIN_UID: u64 = 500;
+const NOLOGIN_SHELLS: &[&str] =
+    &["/usr/bin/false", "/bin/false", "/usr/sbin/nologin", "/sbin/nologin"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub name: String,
+    pub uid: Uid,
+    pub gid: u64,
+    pub home: String,
+    pub shell: String,
+}
+impl User {
+    /// Accounts of daemons rather than people: uids below
+    /// [`FIRST_LOGIN_UID`], names starting with `_` and accounts that
+    /// cannot log in.
+    pub fn is_system(&self) -> bool {
+        self.uid.0 < FIRST_LOGIN_UID
+            || self.name.starts_with('_')
+            || NOLOGIN_SHELLS.contains(&self.shell.as_str())
+    }
+}
+
+/// Parses `/etc/passwd` as well as the `master.passwd` format of the
+/// BSDs, skipping comments and accounts with negative uids such as
+/// `nobody`.
+pub fn parse_passwd(data: &str) -> Result<Vec<User>> {
+    let mut users = Vec::<User>::new();
+    for (number, line) in data.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields = line.split(':').collect::<Vec<&str>>();
+        let (home, shell) = match fields.len() {
+            7 => (fields[5], fields[6]),
+            10 => (fields[8], fields[9]),
+            count =>
+                return Err(Error::ParseError(format!(
+                    "passwd line {} has {} fields instead of 7 or 10: {:#?}",
+                    number + 1,
+                    count,
+                    line
+                ))),
+        };
+        let (Ok(uid), Ok(gid)) = (fields[2].parse::<u64>(), fields[3].parse::<u64>()) else {
+            continue;
+        };
+        users.push(User {
+            name: fields[0].to_string(),
+            uid: Uid(uid),
+            gid,
+            home: home.to_string(),
+            shell: shell.to_string(),
+        });
+    }
+    Ok(users)
+}
+
+/// Parses the `name value` lines of `dscl . -list /Users <key>`,
+/// keeping the first value of keys holding several.
+pub fn parse_dscl_list(data: &str) -> BTreeMap<String, String> {
+    data.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some((parts.next()?.to_string(), parts.next()?.to_string()))
+        })
+        .collect()
+}
+
+/// Users of the system, read from Open Directory through `dscl` on
+/// macOS and from `/etc/passwd` elsewhere.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UserDirectory {
+    pub users: Vec<User>,
+}
+impl UserDirectory {
+    pub fn new(mut users: Vec<User>) -> UserDirectory {
+        users.sort_by(|a, b| a.uid.cmp(&b.uid).then(a.name.cmp(&b.name)));
+        users.dedup_by(|a, b| a.name == b.name);
+        UserDirectory { users }
+    }
+
+    pub fn from_passwd(data: &str) -> Result<UserDirectory> {
+        Ok(UserDirectory::new(parse_passwd(data)?))
+    }
+
+    /// Users from the outputs of `dscl . -list /Users` for the
+    /// `UniqueID`, `PrimaryGroupID`, `NFSHomeDirectory` and `UserShell`
+    /// keys. Users without a uid or with a negative one are skipped.
+    pub fn from_dscl(
+        unique_ids: &str,
+        primary_group_ids: &str,
+        homes: &str,
+        shells: &str,
+    ) -> UserDirectory {
+        let (gids, homes, shells) =
+            (parse_dscl_list(primary_group_ids), parse_dscl_list(homes), parse_dscl_list(shells));
+        UserDirectory::new(
+            parse_dscl_list(unique_ids)
+                .into_iter()
+                .filter_map(|(name, uid)| {
+                    Some(User {
+                        uid: Uid(uid.parse::<u64>().ok()?),
+                        gid: gids
+                            .get(&name)
+                            .and_then(|gid| gid.parse::<u64>().ok())
+                            .unwrap_or_default(),
+                        home: homes.get(&name).cloned().unwrap_or_default(),
+                        shell: shells.get(&name).cloned().unwrap_or_default(),
+                        name,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Reads the users with `dscl` and falls back to `/etc/passwd` when
+    /// it is not available.
+    pub fn detect() -> Result<UserDirectory> {
+        let dscl = |key: &str| -> Result<String> {
+            match crate::command_runner().run(DSCL_PATH, &[".", "-list", "/Users", key])? {
+                (0, stdout, _) => Ok(stdout),
+                (code, _, stderr) => Err(Error::SystemError(format!(
+                    "`dscl . -list /Users {}' failed with exit code {}: {}",
+                    key,
+                    code,
+                    stderr.trim()
+                ))),
+            }
+        };
+        let unique_ids = crate::macos_execution().then(|| dscl("UniqueID").ok()).flatten();
+        if let Some(unique_ids) = unique_ids {
+            return Ok(UserDirectory::from_dscl(
+                &unique_ids,
+                &dscl("PrimaryGroupID")?,
+                &dscl("NFSHomeDirectory")?,
+                &dscl("UserShell")?,
+            ));
+        }
+        UserDirectory::from_passwd(&iocore::Path::raw(PASSWD_PATH).read()?)
+    }
+
+    pub fn find_by_name(&self, name: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.name == name)
+    }
+
+    pub fn find_by_uid(&self, uid: &Uid) -> Option<&User> {
+        self.users.iter().find(|user| user.uid == *uid)
+    }
+
+    pub fn login_users(&self) -> Vec<&User> {
+        self.users.iter().filter(|user| !user.is_system()).collect()
+    }
+
+    /// Uids of the system accounts, whose launchd domains are listed
+    /// along with those of the user when `include_system_uids` is set.
+    pub fn system_uids(&self) -> Vec<Uid> {
+        self.users.iter().filter(|user| user.is_system()).map(|user| user.uid).collect()
+    }
+
+    /// The user logged in at the console: the owner of `console_uid`
+    /// when given, else the user that invoked `sudo` according to
+    /// `sudo_uid`, else the only login user.
+    pub fn console_user(&self, console_uid: Option<Uid>, sudo_uid: Option<Uid>) -> Result<&User> {
+        let login_user = |uid: Option<Uid>| {
+            uid.and_then(|uid| self.find_by_uid(&uid)).filter(|user| !user.is_system())
+        };
+        if let Some(user) = login_user(console_uid).or_else(|| login_user(sudo_uid)) {
+            return Ok(user);
+        }
+        match self.login_users().as_slice() {
+            [user] => Ok(*user),
+            [] => Err(Error::SystemError("no login user found".to_string())),
+            users => Err(Error::SystemError(format!(
+                "cannot tell the console user among {}, pass --user",
+                users.iter().map(|user| user.name.as_str()).collect::<Vec<&str>>().join(", ")
+            ))),
+        }
+    }
+
+    /// Uid of the user named `name`, or else `uid`, or else of the
+    /// [console user](Self::console_user) of this system.
+    pub fn resolve(&self, uid: Option<Uid>, name: Option<&str>) -> Result<Uid> {
+        if let Some(name) = name {
+            return self
+                .find_by_name(name)
+                .map(|user| user.uid)
+                .ok_or_else(|| Error::SystemError(format!("user {:#?} not found", name)));
+        }
+        if let Some(uid) = uid {
+            return Ok(uid);
+        }
+        Ok(self.console_user(current_console_uid(), current_sudo_uid())?.uid)
+    }
+}
+
+/// Owner of `/dev/console`, the user logged in at the screen on macOS.
+pub fn current_console_uid() -> Option<Uid> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(CONSOLE_PATH).ok().map(|metadata| Uid::from(metadata.uid()))
+}
+
+pub fn current_sudo_uid() -> Option<Uid> {
+    iocore::env::var("SUDO_UID").ok().and_then(|uid| uid.parse::<Uid>().ok())
+}
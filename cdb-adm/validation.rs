@@ -0,0 +1,570 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+use toml_edit::{Item, TableLike};
+
+use crate::HealthCheckSettings;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Problem found in a settings file, positioned at the key or value it
+/// concerns. Lines and columns start at 1.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+impl Diagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let Diagnostic { file, line, column, severity, message, .. } = self;
+        write!(f, "{}:{}:{}: {}: {}", file, line, column, severity, message)
+    }
+}
+
+/// What the settings refer to on this system, `None` when it cannot be
+/// listed, in which case the settings are not checked against it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SystemFacts {
+    pub domains: Option<Vec<String>>,
+    pub labels: Option<Vec<String>>,
+}
+impl SystemFacts {
+    /// Domains of `defaults domains` and labels of the launchd plists
+    /// in the agents and daemons directories.
+    pub fn detect() -> SystemFacts {
+        SystemFacts {
+            domains: crate::list_domains().ok().filter(|domains| !domains.is_empty()),
+            labels: crate::list_agents_and_daemons()
+                .ok()
+                .map(|plists| plists.into_iter().map(|(label, _)| label).collect::<Vec<String>>())
+                .filter(|labels| !labels.is_empty()),
+        }
+    }
+
+    fn has_domain(&self, domain: &str) -> Option<bool> {
+        let domains = self.domains.as_ref()?;
+        if domain.starts_with('/') || domain.starts_with('~') {
+            let path = iocore::Path::raw(domain).expand();
+            return Some(path.exists() || iocore::Path::raw(format!("{}.plist", path)).exists());
+        }
+        Some(
+            matches!(domain, "NSGlobalDomain" | "-g" | "-globalDomain")
+                || domains.iter().any(|known| known == domain),
+        )
+    }
+
+    /// Whether a policy entry, a label or `domain/label` target that
+    /// may end in `*`, matches any label.
+    fn has_label(&self, pattern: &str) -> Option<bool> {
+        let labels = self.labels.as_ref()?;
+        let pattern = pattern.rsplit('/').next().unwrap_or(pattern);
+        Some(match pattern.strip_suffix('*') {
+            Some(prefix) => labels.iter().any(|label| label.starts_with(prefix)),
+            None => labels.iter().any(|label| label == pattern),
+        })
+    }
+}
+
+/// Reasons `path` cannot hold backups: it is a file, or its nearest
+/// existing ancestor is not a writable directory.
+pub fn backup_path_problem(path: &iocore::Path) -> Option<String> {
+    let path = path.expand();
+    if path.is_file() {
+        return Some(format!("backup path {:#?} is a file", path.to_string()));
+    }
+    let mut directory = std::path::PathBuf::from(path.to_string());
+    while !directory.exists() {
+        if !directory.pop() {
+            return None;
+        }
+    }
+    if !directory.is_dir() {
+        return Some(format!("{:#?} is not a directory", directory.display().to_string()));
+    }
+    let probe = directory.join(format!(".cdb-adm-write-check-{}", std::process::id()));
+    match std::fs::File::create(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            None
+        },
+        Err(error) => Some(format!(
+            "backup directory {:#?} is not writable: {}",
+            directory.display().to_string(),
+            error
+        )),
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Boolean,
+    Integer,
+    String,
+    Strings,
+    Integers,
+    StringLists,
+    Table(&'static [(&'static str, Kind)]),
+    Tables(&'static [(&'static str, Kind)]),
+    /// `[hosts.<hostname>]` sections, each in the shape of the file.
+    Hosts,
+}
+impl Kind {
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Boolean => "a boolean",
+            Kind::Integer => "an integer",
+            Kind::String => "a string",
+            Kind::Strings => "an array of strings",
+            Kind::Integers => "an array of integers",
+            Kind::StringLists => "an array of arrays of strings",
+            Kind::Table(_) | Kind::Hosts => "a table",
+            Kind::Tables(_) => "an array of tables",
+        }
+    }
+
+    fn accepts(&self, value: &toml::Value) -> bool {
+        let all = |value: &toml::Value, f: fn(&toml::Value) -> bool| match value {
+            toml::Value::Array(values) => values.iter().all(f),
+            _ => false,
+        };
+        match self {
+            Kind::Boolean => value.is_bool(),
+            Kind::Integer => value.is_integer(),
+            Kind::String => value.is_str(),
+            Kind::Strings => all(value, toml::Value::is_str),
+            Kind::Integers => all(value, toml::Value::is_integer),
+            Kind::StringLists => all(value, |value| match value {
+                toml::Value::Array(values) => values.iter().all(toml::Value::is_str),
+                _ => false,
+            }),
+            Kind::Table(_) | Kind::Hosts => value.is_table(),
+            Kind::Tables(_) => all(value, toml::Value::is_table),
+        }
+    }
+}
+
+const EXECUTION: &[(&str, Kind)] = &[
+    ("timeout_secs", Kind::Integer),
+    ("retries", Kind::Integer),
+    ("retry_delay_millis", Kind::Integer),
+    ("retry_exit_codes", Kind::Integers),
+    ("concurrency", Kind::Integer),
+];
+const POLICY: &[(&str, Kind)] = &[
+    ("disabled", Kind::Strings),
+    ("bootout", Kind::Strings),
+    ("ignore", Kind::Strings),
+    ("interval", Kind::Integer),
+];
+const CHECK: &[(&str, Kind)] = &[
+    ("type", Kind::String),
+    ("app", Kind::String),
+    ("process", Kind::String),
+    ("timeout", Kind::Integer),
+    ("target", Kind::String),
+    ("command", Kind::Strings),
+];
+const DELETE: &[(&str, Kind)] = &[("domains", Kind::Strings), ("keys", Kind::StringLists)];
+const CDB: &[(&str, Kind)] =
+    &[("encrypt_backups", Kind::Boolean), ("delete", Kind::Table(DELETE))];
+const ADM: &[(&str, Kind)] = &[
+    ("display_warnings", Kind::Boolean),
+    ("include_non_needed", Kind::Boolean),
+    ("include_system_uids", Kind::Boolean),
+    ("checks", Kind::Tables(CHECK)),
+    ("policy", Kind::Table(POLICY)),
+    ("execution", Kind::Table(EXECUTION)),
+];
+const SETTINGS_FILE: &[(&str, Kind)] = &[
+    ("include", Kind::Strings),
+    ("hosts", Kind::Hosts),
+    ("backup-path", Kind::String),
+    ("backup_path", Kind::String),
+    ("cdb", Kind::Table(CDB)),
+    ("adm", Kind::Table(ADM)),
+];
+const CDB_FILE: &[(&str, Kind)] = &[
+    ("include", Kind::Strings),
+    ("hosts", Kind::Hosts),
+    ("encrypt_backups", Kind::Boolean),
+    ("delete", Kind::Table(DELETE)),
+];
+const ADM_FILE: &[(&str, Kind)] = &[
+    ("include", Kind::Strings),
+    ("hosts", Kind::Hosts),
+    ("display_warnings", Kind::Boolean),
+    ("include_non_needed", Kind::Boolean),
+    ("include_system_uids", Kind::Boolean),
+    ("checks", Kind::Tables(CHECK)),
+    ("policy", Kind::Table(POLICY)),
+    ("execution", Kind::Table(EXECUTION)),
+];
+
+struct Validator<'a> {
+    path: &'a iocore::Path,
+    text: &'a str,
+    root: &'static [(&'static str, Kind)],
+    facts: &'a SystemFacts,
+    diagnostics: Vec<Diagnostic>,
+    includes: Vec<iocore::Path>,
+}
+impl Validator<'_> {
+    fn push(&mut self, span: Option<Range<usize>>, severity: Severity, key: &str, message: String) {
+        let (line, column) = span.map(|span| position(self.text, span.start)).unwrap_or((1, 1));
+        self.diagnostics.push(Diagnostic {
+            file: self.path.to_string(),
+            line,
+            column,
+            severity,
+            key: key.to_string(),
+            message,
+        });
+    }
+
+    /// Checks the keys of `edit`, the spanned counterpart of `value`.
+    /// `logical` is the prefix of the keys once merged into the
+    /// cdb-adm settings, without the file section or host section.
+    fn table(
+        &mut self,
+        edit: &dyn TableLike,
+        value: &toml::Table,
+        schema: &'static [(&'static str, Kind)],
+        prefix: &str,
+        logical: &str,
+    ) {
+        for (name, item) in edit.iter() {
+            let key = format!("{}{}", prefix, name);
+            let key_span = edit.key(name).and_then(|key| key.span());
+            let Some((_, kind)) = schema.iter().find(|(known, _)| *known == name) else {
+                let message = match suggestion(name, schema.iter().map(|(known, _)| *known)) {
+                    Some(known) => format!("unknown key {:#?}, did you mean {:#?}?", key, known),
+                    None => format!("unknown key {:#?}", key),
+                };
+                self.push(key_span, Severity::Error, &key, message);
+                continue;
+            };
+            let Some(current) = value.get(name) else {
+                continue;
+            };
+            let span = item.span().or(key_span.clone());
+            if !kind.accepts(current) {
+                let message =
+                    format!("{:#?} must be {}, not {}", key, kind.name(), current.type_str());
+                self.push(span, Severity::Error, &key, message);
+                continue;
+            }
+            let logical_key = format!("{}{}", logical, name);
+            match (kind, current) {
+                (Kind::Table(schema), toml::Value::Table(current)) =>
+                    if let Some(edit) = item.as_table_like() {
+                        let prefix = format!("{}.", key);
+                        self.table(edit, current, schema, &prefix, &format!("{}.", logical_key));
+                    },
+                (Kind::Tables(schema), toml::Value::Array(current)) =>
+                    for (index, ((edit, edit_span), current)) in
+                        tables(item).into_iter().zip(current).enumerate()
+                    {
+                        let key = format!("{}[{}]", key, index);
+                        let span = edit_span.or(span.clone());
+                        if let toml::Value::Table(current) = current {
+                            let prefix = format!("{}.", key);
+                            let logical = format!("{}.", logical_key);
+                            self.table(edit, current, schema, &prefix, &logical);
+                        }
+                        self.element(&logical_key, &key, span, current);
+                    },
+                (Kind::Hosts, toml::Value::Table(current)) =>
+                    if let Some(edit) = item.as_table_like() {
+                        for (host, item) in edit.iter() {
+                            let key = format!("{}.{}", key, host);
+                            match (item.as_table_like(), current.get(host)) {
+                                (Some(edit), Some(toml::Value::Table(current))) => {
+                                    let prefix = format!("{}.", key);
+                                    self.table(edit, current, self.root, &prefix, logical);
+                                },
+                                _ => self.push(
+                                    item.span(),
+                                    Severity::Error,
+                                    &key,
+                                    format!("{:#?} must be a table", key),
+                                ),
+                            }
+                        }
+                    },
+                _ => self.leaf(&logical_key, &key, item, current, span),
+            }
+        }
+    }
+
+    fn leaf(
+        &mut self,
+        logical: &str,
+        key: &str,
+        item: &Item,
+        value: &toml::Value,
+        span: Option<Range<usize>>,
+    ) {
+        let spans = match item.as_array() {
+            Some(array) => array.iter().map(|value| value.span()).collect::<Vec<_>>(),
+            None => Vec::new(),
+        };
+        let values = match value {
+            toml::Value::Array(values) => values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| (spans.get(index).cloned().flatten().or(span.clone()), value))
+                .collect::<Vec<_>>(),
+            _ => Vec::new(),
+        };
+        match logical {
+            "include" | "cdb.include" | "adm.include" if !key.starts_with("hosts.") =>
+                for (span, include) in values {
+                    let include = include.as_str().unwrap_or_default();
+                    let included = crate::layers::include_path(self.path, include);
+                    if included.is_file() {
+                        self.includes.push(included);
+                    } else {
+                        let message = format!("included file {:#?} does not exist", include);
+                        self.push(span, Severity::Error, key, message);
+                    }
+                },
+            "backup-path" | "backup_path" => {
+                let path = iocore::Path::raw(value.as_str().unwrap_or_default());
+                if let Some(problem) = backup_path_problem(&path) {
+                    self.push(span, Severity::Error, key, problem);
+                }
+            },
+            "cdb.delete.domains" =>
+                for (span, domain) in values {
+                    let domain = domain.as_str().unwrap_or_default();
+                    if self.facts.has_domain(domain) == Some(false) {
+                        let message = format!("domain {:#?} does not exist on this system", domain);
+                        self.push(span, Severity::Warning, key, message);
+                    }
+                },
+            "cdb.delete.keys" =>
+                for (span, path) in values {
+                    let path = path
+                        .as_array()
+                        .map(|path| path.iter().filter_map(toml::Value::as_str).collect::<Vec<_>>())
+                        .unwrap_or_default();
+                    match path.first() {
+                        None => self.push(
+                            span,
+                            Severity::Error,
+                            key,
+                            "key path must start with a domain".to_string(),
+                        ),
+                        Some(domain) if self.facts.has_domain(domain) == Some(false) => {
+                            let message = format!(
+                                "key path {:#?} is in domain {:#?} which does not exist on this \
+                                 system",
+                                path.join(" "),
+                                domain
+                            );
+                            self.push(span, Severity::Warning, key, message);
+                        },
+                        Some(_) => {},
+                    }
+                },
+            "adm.policy.disabled" | "adm.policy.bootout" | "adm.policy.ignore" =>
+                for (span, pattern) in values {
+                    let pattern = pattern.as_str().unwrap_or_default();
+                    if self.facts.has_label(pattern) == Some(false) {
+                        let message =
+                            format!("{:#?} does not match the label of any launchd plist", pattern);
+                        self.push(span, Severity::Warning, key, message);
+                    }
+                },
+            _ => {},
+        }
+    }
+
+    fn element(
+        &mut self,
+        logical: &str,
+        key: &str,
+        span: Option<Range<usize>>,
+        value: &toml::Value,
+    ) {
+        if logical != "adm.checks" {
+            return;
+        }
+        if let Err(error) = value.clone().try_into::<HealthCheckSettings>() {
+            let message = format!("invalid health check: {}", error.message());
+            self.push(span, Severity::Error, key, message);
+        }
+    }
+}
+
+/// Tables of an array of tables or of an array of inline tables, with
+/// their spans.
+fn tables(item: &Item) -> Vec<(&dyn TableLike, Option<Range<usize>>)> {
+    match item {
+        Item::ArrayOfTables(tables) => tables
+            .iter()
+            .map(|table| (table as &dyn TableLike, table.span()))
+            .collect(),
+        Item::Value(toml_edit::Value::Array(values)) => values
+            .iter()
+            .filter_map(|value| match value {
+                toml_edit::Value::InlineTable(table) =>
+                    Some((table as &dyn TableLike, table.span())),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+    (line, column)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn suggestion<'a>(name: &str, known: impl Iterator<Item = &'a str>) -> Option<&'a str> {
+    known
+        .map(|known| (edit_distance(name, known), known))
+        .filter(|(distance, known)| *distance <= (name.len().max(known.len()) / 3).max(1))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, known)| known)
+}
+
+fn validate(
+    path: &iocore::Path,
+    text: &str,
+    section: Option<&str>,
+    facts: &SystemFacts,
+) -> (Vec<Diagnostic>, Vec<iocore::Path>) {
+    let root = match section {
+        Some("cdb") => CDB_FILE,
+        Some("adm") => ADM_FILE,
+        _ => SETTINGS_FILE,
+    };
+    let mut validator =
+        Validator { path, text, root, facts, diagnostics: Vec::new(), includes: Vec::new() };
+    let parsed = toml_edit::ImDocument::parse(text)
+        .map_err(|error| (error.span(), error.message().trim().to_string()))
+        .and_then(|document| match toml::from_str::<toml::Table>(text) {
+            Ok(value) => Ok((document, value)),
+            Err(error) => Err((error.span(), error.message().trim().to_string())),
+        });
+    match parsed {
+        Ok((document, value)) => {
+            let logical = section.map(|section| format!("{}.", section)).unwrap_or_default();
+            validator.table(document.as_table(), &value, root, "", &logical);
+        },
+        Err((span, message)) => validator.push(span, Severity::Error, "", message),
+    }
+    (validator.diagnostics, validator.includes)
+}
+
+/// Diagnostics of the settings `text` read from `path`, whose keys are
+/// merged under `section`, e.g.: `adm` for `~/.config/adm.toml`.
+/// Included files are not read.
+pub fn validate_settings(
+    path: &iocore::Path,
+    text: &str,
+    section: Option<&str>,
+    facts: &SystemFacts,
+) -> Vec<Diagnostic> {
+    validate(path, text, section, facts).0
+}
+
+/// Diagnostics of `files` and of the files they include, each checked
+/// once.
+pub fn check_settings_files(
+    files: &[(iocore::Path, Option<&str>)],
+    facts: &SystemFacts,
+) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::<Diagnostic>::new();
+    let mut checked = Vec::<String>::new();
+    let mut pending = files.iter().rev().cloned().collect::<Vec<_>>();
+    while let Some((path, section)) = pending.pop() {
+        let path = path.expand().try_canonicalize();
+        if checked.contains(&path.to_string()) {
+            continue;
+        }
+        checked.push(path.to_string());
+        match path.read() {
+            Ok(text) => {
+                let (found, includes) = validate(&path, &text, section, facts);
+                diagnostics.extend(found);
+                pending.extend(includes.into_iter().rev().map(|include| (include, section)));
+            },
+            Err(error) => diagnostics.push(Diagnostic {
+                file: path.to_string(),
+                line: 1,
+                column: 1,
+                severity: Severity::Error,
+                key: String::new(),
+                message: error.to_string(),
+            }),
+        }
+    }
+    diagnostics
+}
+
+/// Diagnostics of the settings files [`settings_layers`](crate::settings_layers)
+/// reads.
+pub fn check_settings(facts: &SystemFacts) -> Vec<Diagnostic> {
+    let mut files = crate::user_settings_files();
+    files.extend(crate::project_settings_paths().into_iter().map(|path| (path, None)));
+    check_settings_files(&files, facts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{edit_distance, position, suggestion};
+
+    #[test]
+    fn test_suggestions_are_close_keys() {
+        assert_eq!(edit_distance("include_non_neded", "include_non_needed"), 1);
+        assert_eq!(edit_distance("", "adm"), 3);
+        let known = ["display_warnings", "include_non_needed", "include_system_uids"];
+        assert_eq!(
+            suggestion("include-system-uids", known.into_iter()),
+            Some("include_system_uids")
+        );
+        assert_eq!(suggestion("verbose", known.into_iter()), None);
+        assert_eq!(position("a = 1\nbé = 2\n", 10), (2, 4));
+    }
+}
@@ -0,0 +1,94 @@
+use std::sync::Arc;
+
+use cdb_adm::{
+    boot_up_agents_and_daemons, defaults_delete, launchctl_ok, override_command_runner, spctl,
+    turn_off_agents_and_daemons, CommandRunnerOverride, FakeCommandRunner, Uid, DEFAULTS_PATH,
+    LAUNCHCTL_PATH, SPCTL_PATH,
+};
+
+const LABEL: &str = "com.example.agent 'quoted' \"double\" $(touch /tmp/pwned); `id`";
+const PLIST_PATH: &str =
+    "/Users/me/Library/Application Support/Example; rm -rf ~/$(whoami)/com.example.plist";
+
+/// Fake runner answering every command successfully, current until
+/// the guard drops.
+fn runner() -> (Arc<FakeCommandRunner>, CommandRunnerOverride) {
+    let runner = Arc::new(FakeCommandRunner::new().respond("", 0, ""));
+    let guard = override_command_runner(runner.clone());
+    (runner, guard)
+}
+
+fn argv(args: &[&str]) -> Vec<String> {
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_launchctl_arguments_are_not_split_or_interpreted() {
+    let (runner, _guard) = runner();
+    let target = format!("gui/501/{}", LABEL);
+    launchctl_ok(&["kickstart", "-k", &target], true).unwrap();
+    assert_eq!(
+        runner.argvs(),
+        vec![argv(&["sudo", "-n", LAUNCHCTL_PATH, "kickstart", "-k", &target])]
+    );
+}
+
+#[test]
+fn test_boot_up_passes_plist_path_with_spaces_as_one_argument() {
+    let (runner, _guard) = runner();
+    let results = boot_up_agents_and_daemons(
+        true,
+        vec![(
+            "gui/501".to_string(),
+            LABEL.to_string(),
+            0,
+            (iocore::Path::raw(PLIST_PATH), plist::Dictionary::new()),
+        )],
+    );
+    assert!(results.iter().all(|(_, _, _, result)| result.is_ok()));
+    let target = format!("gui/501/{}", LABEL);
+    assert_eq!(
+        runner.argvs(),
+        vec![
+            argv(&["sudo", "-n", LAUNCHCTL_PATH, "bootstrap", "gui/501", PLIST_PATH]),
+            argv(&["sudo", "-n", LAUNCHCTL_PATH, "enable", &target]),
+            argv(&["sudo", "-n", LAUNCHCTL_PATH, "kickstart", "-k", &target]),
+        ]
+    );
+}
+
+#[test]
+fn test_turn_off_passes_label_as_one_argument() {
+    let (runner, _guard) = runner();
+    let results = turn_off_agents_and_daemons(
+        &Uid(501),
+        true,
+        vec![("system".to_string(), LABEL.to_string(), 0)],
+        false,
+    );
+    assert!(results.iter().all(|(_, _, _, result)| result.is_ok()));
+    let target = format!("system/{}", LABEL);
+    let calls = runner.argvs();
+    assert!(!calls.is_empty());
+    for call in &calls {
+        assert_eq!(&call[..3], &argv(&["sudo", "-n", LAUNCHCTL_PATH])[..]);
+        assert_eq!(call.last(), Some(&target));
+    }
+}
+
+#[test]
+fn test_spctl_and_defaults_arguments_are_not_interpreted() {
+    let (runner, _guard) = runner();
+    let option = "--status; touch /tmp/pwned";
+    spctl(&[option]).unwrap();
+    let domain = "/Users/me/Library/Preferences/com.example $(id).plist";
+    let key = "Key 'with' \"quotes\"; `id`";
+    defaults_delete(&[domain, key]).unwrap();
+    assert_eq!(
+        runner.argvs(),
+        vec![
+            argv(&["sudo", SPCTL_PATH, option]),
+            argv(&[DEFAULTS_PATH, "delete", domain, key]),
+        ]
+    );
+}
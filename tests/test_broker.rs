@@ -0,0 +1,134 @@
+use std::sync::{Arc, Mutex};
+
+use cdb_adm::{
+    Broker, BrokerAuditEntry, BrokerClient, BrokerRequest, CommandRunner, Error,
+    FakeCommandRunner, PrivilegedCommand, Result,
+};
+
+const TOKEN: &str = "3f1c9a0e5b7d42e8a6c1f0b9d8e7a6c5";
+
+fn temp_path(name: &str) -> iocore::Path {
+    let path = iocore::Path::raw(std::env::temp_dir().display().to_string())
+        .join(format!("cdb-adm-{}-{}", std::process::id(), name));
+    if path.exists() {
+        path.delete().unwrap();
+    }
+    path
+}
+
+fn launchctl(args: &[&str]) -> PrivilegedCommand {
+    PrivilegedCommand::Launchctl { args: args.iter().map(|arg| arg.to_string()).collect() }
+}
+
+fn read_audit_log(path: &iocore::Path) -> Vec<BrokerAuditEntry> {
+    path.read()
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str::<BrokerAuditEntry>(line).unwrap())
+        .collect()
+}
+
+/// Records each argv as given so that tests can tell apart arguments
+/// containing spaces.
+#[derive(Default)]
+struct ArgvRecorder {
+    calls: Mutex<Vec<Vec<String>>>,
+}
+impl CommandRunner for ArgvRecorder {
+    fn run(&self, program: &str, args: &[&str]) -> Result<(i64, String, String)> {
+        let argv = std::iter::once(program)
+            .chain(args.iter().copied())
+            .map(|arg| arg.to_string())
+            .collect::<Vec<String>>();
+        self.calls.lock().unwrap().push(argv);
+        Ok((0, String::new(), String::new()))
+    }
+}
+
+#[test]
+fn test_authorized_request_runs_through_runner() {
+    let runner = Arc::new(
+        FakeCommandRunner::new().respond("/bin/launchctl print-disabled system", 0, "disabled"),
+    );
+    let audit_log = temp_path("broker-authorized.jsonl");
+    let broker = Broker::new(TOKEN, runner.clone(), Some(audit_log.clone()));
+    let response = broker.handle(&BrokerRequest {
+        id: 7,
+        token: TOKEN.to_string(),
+        command: launchctl(&["print-disabled", "system"]),
+    });
+    assert_eq!(response.id, 7);
+    assert_eq!(response.exit_code, 0);
+    assert_eq!(response.stdout, "disabled");
+    assert_eq!(response.error, None);
+    assert_eq!(runner.calls(), vec!["/bin/launchctl print-disabled system".to_string()]);
+
+    let entries = read_audit_log(&audit_log);
+    assert_eq!(entries.len(), 1);
+    assert!(entries[0].authorized);
+    assert_eq!(entries[0].exit_code, Some(0));
+    assert!(!audit_log.read().unwrap().contains(TOKEN));
+}
+
+#[test]
+fn test_invalid_token_is_denied_and_audited() {
+    let runner = Arc::new(FakeCommandRunner::new());
+    let audit_log = temp_path("broker-denied.jsonl");
+    let broker = Broker::new(TOKEN, runner.clone(), Some(audit_log.clone()));
+    let response = broker.handle(&BrokerRequest {
+        id: 1,
+        token: "not-the-token".to_string(),
+        command: launchctl(&["bootout", "system/com.apple.bluetoothd"]),
+    });
+    assert!(matches!(response.error, Some(Error::AuthorizationError(_))));
+    assert!(runner.calls().is_empty());
+
+    let entries = read_audit_log(&audit_log);
+    assert_eq!(entries.len(), 1);
+    assert!(!entries[0].authorized);
+    assert_eq!(entries[0].exit_code, None);
+    assert!(!audit_log.read().unwrap().contains("not-the-token"));
+}
+
+#[test]
+fn test_commands_outside_allowlist_are_denied() {
+    let runner = Arc::new(FakeCommandRunner::new());
+    let broker = Broker::new(TOKEN, runner.clone(), None);
+    for command in [
+        launchctl(&["asuser", "0", "/bin/sh"]),
+        launchctl(&[]),
+        PrivilegedCommand::Spctl { args: vec!["--add".to_string(), "/tmp/x.app".to_string()] },
+    ] {
+        let response =
+            broker.handle(&BrokerRequest { id: 1, token: TOKEN.to_string(), command });
+        assert!(matches!(response.error, Some(Error::AuthorizationError(_))));
+    }
+    assert!(runner.calls().is_empty());
+}
+
+#[test]
+fn test_client_and_server_pass_argv_intact() {
+    let runner = Arc::new(ArgvRecorder::default());
+    let socket_path = temp_path("broker.sock");
+    let server = {
+        let runner = runner.clone();
+        let socket_path = socket_path.clone();
+        std::thread::spawn(move || Broker::new(TOKEN, runner, None).serve(&socket_path, None))
+    };
+    cdb_adm::wait_until(&std::time::Duration::from_secs(10), || socket_path.exists()).unwrap();
+
+    let client = BrokerClient::connect(&socket_path, TOKEN).unwrap();
+    let label = "gui/501/com.example.agent with spaces; $(touch /tmp/pwned) 'quoted'";
+    let (exit_code, _, _) = client.request(&launchctl(&["bootout", label])).unwrap();
+    assert_eq!(exit_code, 0);
+    let error = client.request(&launchctl(&["asuser", "0", "/bin/sh"])).unwrap_err();
+    assert!(matches!(error, Error::AuthorizationError(_)));
+    drop(client);
+    server.join().unwrap().unwrap();
+
+    assert_eq!(
+        *runner.calls.lock().unwrap(),
+        vec![vec!["/bin/launchctl".to_string(), "bootout".to_string(), label.to_string()]]
+    );
+    assert!(!socket_path.exists());
+}
@@ -0,0 +1,114 @@
+use std::sync::{Mutex, MutexGuard};
+
+use cdb_adm::{resolve_adm_config, ConfigSource, Settings, SettingsEnvPath};
+
+/// Settings are located through the environment, which is process-wide,
+/// so the tests of this file run one at a time in a directory of their
+/// own.
+fn exclusive(name: &str) -> (MutexGuard<'static, ()>, std::path::PathBuf) {
+    static LOCK: Mutex<()> = Mutex::new(());
+    let guard = LOCK.lock().unwrap_or_else(|error| error.into_inner());
+    let directory =
+        std::env::temp_dir().join(format!("adm-config-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    unsafe {
+        for name in
+            ["CDB_ADM_SYSTEM_SETTINGS", "CDB_ADM_SETTINGS", "CDM_SETTINGS", "ADM_SETTINGS"]
+        {
+            std::env::set_var(name, directory.join("missing.toml"));
+        }
+        for name in ["ADM_INCLUDE_SYSTEM_UIDS", "ADM_DISPLAY_WARNINGS"] {
+            std::env::remove_var(name);
+        }
+        std::env::set_var("CDB_ADM_HOSTNAME", "studio");
+    }
+    (guard, directory)
+}
+
+#[test]
+fn test_flags_override_env_which_overrides_the_settings_file() {
+    let (_guard, directory) = exclusive("precedence");
+    let adm = directory.join("adm.toml");
+    std::fs::write(
+        &adm,
+        "display_warnings = false\ninclude_system_uids = false\n[execution]\nretries = 5\n",
+    )
+    .unwrap();
+    unsafe {
+        std::env::set_var("ADM_SETTINGS", &adm);
+        std::env::set_var("ADM_INCLUDE_SYSTEM_UIDS", "yes");
+        std::env::set_var("ADM_DISPLAY_WARNINGS", "no");
+    }
+
+    let config = resolve_adm_config(&[(
+        "display_warnings",
+        "--display-warnings",
+        toml::Value::Boolean(true),
+    )])
+    .unwrap();
+    let settings = config.adm_settings().unwrap();
+    assert!(settings.display_warnings());
+    assert!(settings.include_system_uids());
+    assert!(!settings.include_non_needed());
+    assert_eq!(settings.execution().retries, 5);
+    assert_eq!(
+        config.source("display_warnings"),
+        Some(&ConfigSource::Flag("--display-warnings".to_string()))
+    );
+    assert_eq!(
+        config.source("include_system_uids"),
+        Some(&ConfigSource::Env("ADM_INCLUDE_SYSTEM_UIDS".to_string()))
+    );
+    assert!(matches!(config.source("execution.retries"), Some(ConfigSource::File(_))));
+    assert_eq!(config.source("include_non_needed"), Some(&ConfigSource::Default));
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn test_layers_merge_system_user_and_host_settings() {
+    let (_guard, directory) = exclusive("layers");
+    let system = directory.join("system.toml");
+    std::fs::write(
+        &system,
+        "backup-path = \"/var/backups/cdb-adm\"\n[cdb.delete]\ndomains = [\"com.apple.a\"]\n\
+         [adm.execution]\nretry_exit_codes = [35]\n",
+    )
+    .unwrap();
+    std::fs::write(directory.join("shared.toml"), "[adm]\ninclude_non_needed = true\n").unwrap();
+    let user = directory.join("cdb-adm.toml");
+    std::fs::write(
+        &user,
+        "include = [\"shared.toml\"]\n[cdb.delete]\ndomains = [\"com.apple.b\"]\n\
+         [adm.execution]\nretry_exit_codes = [37]\n\
+         [hosts.studio.cdb.delete]\ndomains = [\"com.apple.studio\"]\n\
+         [hosts.studio.adm]\ninclude_non_needed = false\n\
+         [hosts.laptop.adm]\ndisplay_warnings = false\n",
+    )
+    .unwrap();
+    unsafe {
+        std::env::set_var("CDB_ADM_SYSTEM_SETTINGS", &system);
+        std::env::set_var("CDB_ADM_SETTINGS", &user);
+    }
+
+    let settings = Settings::detect().unwrap();
+    assert_eq!(settings.backup_path().to_string(), "/var/backups/cdb-adm");
+    assert_eq!(
+        settings.cdb().defaults_exec_args(),
+        ["com.apple.a", "com.apple.b", "com.apple.studio"]
+            .iter()
+            .map(|domain| vec!["delete".to_string(), domain.to_string()])
+            .collect::<Vec<Vec<String>>>()
+    );
+    let adm = settings.adm();
+    assert!(!adm.include_non_needed());
+    assert!(adm.display_warnings());
+    assert_eq!(adm.execution().retry_exit_codes, vec![35, 37]);
+
+    let config = resolve_adm_config(&[]).unwrap();
+    assert_eq!(config.get("execution.retry_exit_codes"), Some(&toml::Value::from(vec![35, 37])));
+    assert_eq!(
+        config.source("include_non_needed"),
+        Some(&ConfigSource::File(format!("{} [hosts.studio]", user.display())))
+    );
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,116 @@
+use cdb_adm::{
+    render_initial_settings, validate_settings, write_initial_settings, Settings,
+    SettingsInventory, SystemFacts,
+};
+
+const EXPECTED: &str = r#"# cdb-adm settings generated by `cdb config init` from the preferences
+# domains and the disabled services of this system.
+# Check them with `cdb config check` and `adm config show`.
+
+# Where backups and the journal of `adm tui` are kept
+backup-path = "~/cdb-adm-backup"
+
+[cdb]
+# Encrypt export, delete and snapshot files with a keychain key
+encrypt_backups = false
+
+[cdb.delete]
+# 2 of the 13 candidate domains are present on this system
+domains = [
+    "com.apple.HIToolbox",
+    "org.mozilla.firefox",
+]
+# Key paths, each starting with its domain
+keys = [
+    ["NSGlobalDomain", "com.apple.gms.availability.useCasesWhoseAssetsNotReady"],
+    ["NSGlobalDomain", "com.apple.gms.availability.disallowedUseCases"],
+]
+
+[adm]
+# Print warnings about services whose plist is not found
+display_warnings = true
+# Also turn off the services of the non-needed catalog
+include_non_needed = false
+# List the services of the system uids besides the given one
+include_system_uids = true
+
+[adm.policy]
+# The 2 services disabled when this file was generated
+disabled = [
+    "gui/501/com.apple.Siri.agent",
+    "system/com.apple.mdmclient.daemon",
+]
+# Services to boot out as well as disable
+bootout = []
+# Services `adm enforce` leaves alone
+ignore = []
+# Seconds between runs of the enforce launch agent
+interval = 3600
+
+[adm.execution]
+# Seconds before a launchctl command is killed, 0 for no timeout
+timeout_secs = 30
+# Attempts after the first for timeouts and the exit codes below
+retries = 1
+retry_delay_millis = 500
+retry_exit_codes = [35, 37]
+# Services turned off or booted up at once
+concurrency = 4
+"#;
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|value| value.to_string()).collect()
+}
+
+fn inventory() -> SettingsInventory {
+    SettingsInventory::new(
+        strings(&["org.mozilla.firefox", "com.apple.finder", "com.apple.HIToolbox"]),
+        strings(&["system/com.apple.mdmclient.daemon", "gui/501/com.apple.Siri.agent"]),
+    )
+}
+
+#[test]
+fn test_settings_are_proposed_from_the_inventory() {
+    assert_eq!(render_initial_settings(&inventory()), EXPECTED);
+}
+
+#[test]
+fn test_generation_does_not_depend_on_the_order_of_the_inventory() {
+    let shuffled = SettingsInventory {
+        domains: strings(&[
+            "com.apple.HIToolbox",
+            "com.apple.finder",
+            "org.mozilla.firefox",
+            "com.apple.HIToolbox",
+        ]),
+        disabled: strings(&["gui/501/com.apple.Siri.agent", "system/com.apple.mdmclient.daemon"]),
+    };
+    assert_eq!(render_initial_settings(&shuffled), render_initial_settings(&inventory()));
+}
+
+#[test]
+fn test_generated_settings_are_valid() {
+    let facts = SystemFacts {
+        domains: Some(inventory().domains),
+        labels: Some(strings(&["com.apple.Siri.agent", "com.apple.mdmclient.daemon"])),
+    };
+    let text = render_initial_settings(&inventory());
+    let path = iocore::Path::raw("cdb-adm.toml");
+    assert_eq!(validate_settings(&path, &text, None, &facts), Vec::new());
+    let settings = toml::from_str::<Settings>(&text.replace("backup-path", "backup_path")).unwrap();
+    assert_eq!(settings.cdb().defaults_exec_args().len(), 4);
+    assert_eq!(settings.adm().policy().disabled.len(), 2);
+}
+
+#[test]
+fn test_existing_settings_are_only_overwritten_when_forced() {
+    let directory = std::env::temp_dir().join(format!("cdb-adm-init-{}", std::process::id()));
+    let path = iocore::Path::raw(directory.join("config/cdb-adm.toml").display());
+    write_initial_settings(&path, "backup-path = \"~/a\"\n", false).unwrap();
+    let error = write_initial_settings(&path, EXPECTED, false).unwrap_err();
+    assert!(error.to_string().contains("use --force"));
+    assert_eq!(path.read().unwrap(), "backup-path = \"~/a\"\n");
+    write_initial_settings(&path, EXPECTED, true).unwrap();
+    assert_eq!(path.read().unwrap(), EXPECTED);
+    std::fs::remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,104 @@
+use std::sync::Arc;
+
+use cdb_adm::{
+    open_backup, set_secret_store, write_backup_with_key, BackupKey, EncryptedBundle, Error,
+    MemorySecretStore, Result, SecretStore, ENCRYPTED_BUNDLE_FORMAT,
+};
+
+/// Keychain that is locked: every access fails.
+struct LockedSecretStore;
+impl SecretStore for LockedSecretStore {
+    fn set(&self, _service: &str, _account: &str, _data: &[u8]) -> Result<()> {
+        panic!("nothing is written to a locked keychain")
+    }
+
+    fn get(&self, _service: &str, _account: &str) -> Result<Vec<u8>> {
+        Err(Error::KeychainError("User interaction is not allowed.".to_string()))
+    }
+
+    fn delete(&self, _service: &str, _account: &str) -> Result<()> {
+        panic!("nothing is deleted from a locked keychain")
+    }
+}
+
+fn backup_key(id: &str, byte: u8) -> BackupKey {
+    BackupKey::new(id, &[byte; 32]).unwrap()
+}
+
+#[test]
+fn test_seal_and_open_bundle() -> Result<()> {
+    let key = backup_key("0011223344556677", 7);
+    let bundle = EncryptedBundle::seal(&key, b"{\"com.apple.dock\": {}}")?;
+    assert_eq!(bundle.format, ENCRYPTED_BUNDLE_FORMAT);
+    assert_eq!(bundle.key_id, "0011223344556677");
+    assert_eq!(bundle.nonce.len(), 24);
+    assert!(!bundle.ciphertext.contains("dock"));
+    assert_eq!(bundle.open(&key)?, b"{\"com.apple.dock\": {}}".to_vec());
+    let first = EncryptedBundle::seal(&key, b"{}")?;
+    assert_ne!(first.nonce, EncryptedBundle::seal(&key, b"{}")?.nonce);
+    Ok(())
+}
+
+#[test]
+fn test_open_bundle_rejects_tampering_and_wrong_key() -> Result<()> {
+    let key = backup_key("0011223344556677", 7);
+    let bundle = EncryptedBundle::seal(&key, b"secret token")?;
+
+    let mut tampered = bundle.clone();
+    let last = if tampered.ciphertext.ends_with('0') { "1" } else { "0" };
+    tampered.ciphertext.replace_range(tampered.ciphertext.len() - 1.., last);
+    assert!(matches!(tampered.open(&key), Err(Error::EncryptionError(_))));
+
+    let mut relabelled = bundle.clone();
+    relabelled.key_id = "8899aabbccddeeff".to_string();
+    assert!(relabelled.open(&backup_key("8899aabbccddeeff", 7)).is_err());
+
+    assert!(bundle.open(&backup_key("0011223344556677", 8)).is_err());
+    assert!(bundle.open(&backup_key("8899aabbccddeeff", 7)).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_backup_key_length() {
+    assert!(BackupKey::new("short", &[0u8; 16]).is_err());
+    let generated = BackupKey::generate().unwrap();
+    assert_eq!(generated.id().len(), 16);
+    assert_eq!(format!("{:?}", generated), format!("BackupKey[id: {:#?}]", generated.id()));
+}
+
+#[test]
+fn test_open_backup() -> Result<()> {
+    let key = backup_key("0011223344556677", 7);
+    let path = iocore::Path::raw(std::env::temp_dir().display().to_string())
+        .join(format!("cdb-adm-test-encrypted-{}.json", std::process::id()));
+    write_backup_with_key(&path, "{\"NSGlobalDomain\": {}}", &key)?;
+    let data = path.read()?;
+    assert!(EncryptedBundle::parse(&data).is_some());
+    assert_eq!(
+        open_backup(&data, |id| {
+            assert_eq!(id, "0011223344556677");
+            Ok(key.clone())
+        })?,
+        "{\"NSGlobalDomain\": {}}"
+    );
+    assert_eq!(
+        open_backup("{\"NSGlobalDomain\": {}}", |_| panic!("plaintext needs no key"))?,
+        "{\"NSGlobalDomain\": {}}"
+    );
+    assert!(open_backup(&data, |id| Err(Error::EncryptionError(id.to_string()))).is_err());
+    path.delete()?;
+    Ok(())
+}
+
+#[test]
+fn test_current_backup_key_is_only_generated_when_missing() -> Result<()> {
+    set_secret_store(Arc::new(LockedSecretStore));
+    assert_eq!(
+        BackupKey::current(),
+        Err(Error::KeychainError("User interaction is not allowed.".to_string()))
+    );
+    set_secret_store(Arc::new(MemorySecretStore::default()));
+    let generated = BackupKey::current()?;
+    assert_eq!(BackupKey::current()?, generated);
+    Ok(())
+}
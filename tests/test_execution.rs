@@ -0,0 +1,163 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use cdb_adm::{
+    launchctl_ok, override_command_runner, set_execution_settings, turn_off_agents_and_daemons,
+    CommandRunner, Error, ExecutionSettings, FakeCommandRunner, Result, Uid,
+};
+
+/// Exits with `exit_code` on the first `failures` calls and with 0 on
+/// the following ones.
+struct FlakyRunner {
+    exit_code: i64,
+    failures: usize,
+    calls: AtomicUsize,
+}
+impl CommandRunner for FlakyRunner {
+    fn run(&self, _program: &str, _args: &[&str]) -> Result<(i64, String, String)> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            Ok((self.exit_code, String::new(), "Resource temporarily unavailable".to_string()))
+        } else {
+            Ok((0, String::new(), String::new()))
+        }
+    }
+}
+
+/// Refuses `sudo -n` with a password prompt until `sudo -v` was run,
+/// counting how many times the password was asked for.
+#[derive(Default)]
+struct SudoRunner {
+    authenticated: AtomicBool,
+    prompts: AtomicUsize,
+}
+impl CommandRunner for SudoRunner {
+    fn run(&self, _program: &str, args: &[&str]) -> Result<(i64, String, String)> {
+        if args == ["-v"] {
+            std::thread::sleep(Duration::from_millis(100));
+            self.prompts.fetch_add(1, Ordering::SeqCst);
+            self.authenticated.store(true, Ordering::SeqCst);
+        } else if !self.authenticated.load(Ordering::SeqCst) {
+            return Ok((1, String::new(), "sudo: a password is required\n".to_string()));
+        }
+        Ok((0, String::new(), String::new()))
+    }
+}
+
+#[test]
+fn test_hung_command_times_out() {
+    let runner = Arc::new(FakeCommandRunner::new().sleep("bootout", Duration::from_secs(30)));
+    let settings = ExecutionSettings {
+        timeout_secs: 1,
+        retries: 1,
+        retry_delay_millis: 0,
+        ..Default::default()
+    };
+    let _guard = override_command_runner(runner.clone());
+    set_execution_settings(settings);
+    let started = Instant::now();
+    let error = launchctl_ok(&["bootout", "system/com.example.hung"], true).unwrap_err();
+    assert!(matches!(error, Error::TimeoutError(_)));
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(runner.calls().len(), 2);
+}
+
+#[test]
+fn test_retryable_exit_codes_are_retried() {
+    let runner = Arc::new(FlakyRunner { exit_code: 35, failures: 2, calls: AtomicUsize::new(0) });
+    let settings = ExecutionSettings { retries: 2, retry_delay_millis: 0, ..Default::default() };
+    let _guard = override_command_runner(runner.clone());
+    set_execution_settings(settings);
+    let (exit_code, _, _) = launchctl_ok(&["enable", "system/com.example.flaky"], true).unwrap();
+    assert_eq!(exit_code, 0);
+    assert_eq!(runner.calls.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn test_other_exit_codes_are_not_retried() {
+    let runner = Arc::new(FlakyRunner { exit_code: 113, failures: 2, calls: AtomicUsize::new(0) });
+    let settings = ExecutionSettings { retries: 2, retry_delay_millis: 0, ..Default::default() };
+    let _guard = override_command_runner(runner.clone());
+    set_execution_settings(settings);
+    let (exit_code, _, _) = launchctl_ok(&["enable", "system/com.example.missing"], true).unwrap();
+    assert_eq!(exit_code, 113);
+    assert_eq!(runner.calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_turn_off_runs_services_in_parallel_in_order() {
+    let runner = Arc::new(
+        FakeCommandRunner::new()
+            .sleep("bootout system/com.example.slow", Duration::from_millis(600))
+            .sleep("bootout", Duration::from_millis(300))
+            .respond("com.example.broken", 1, "")
+            .respond("", 0, ""),
+    );
+    let settings = ExecutionSettings { concurrency: 4, ..Default::default() };
+    let _guard = override_command_runner(runner.clone());
+    set_execution_settings(settings);
+    let services = ["com.example.slow", "com.example.a", "com.example.broken", "com.example.b"]
+        .iter()
+        .map(|service| ("system".to_string(), service.to_string(), 0))
+        .collect::<Vec<(String, String, i64)>>();
+    let started = Instant::now();
+    let results = turn_off_agents_and_daemons(&Uid(501), true, services, false);
+    assert!(started.elapsed() < Duration::from_millis(1200));
+    assert_eq!(
+        results
+            .iter()
+            .map(|(_, service, _, result)| (service.as_str(), result.is_ok()))
+            .collect::<Vec<(&str, bool)>>(),
+        vec![
+            ("com.example.slow", true),
+            ("com.example.a", true),
+            ("com.example.broken", false),
+            ("com.example.b", true),
+        ]
+    );
+}
+
+#[test]
+fn test_concurrency_of_one_is_sequential() {
+    let runner = Arc::new(
+        FakeCommandRunner::new().sleep("bootout", Duration::from_millis(200)).respond("", 0, ""),
+    );
+    let settings = ExecutionSettings { concurrency: 1, ..Default::default() };
+    let _guard = override_command_runner(runner.clone());
+    set_execution_settings(settings);
+    let services = ["com.example.a", "com.example.b", "com.example.c"]
+        .iter()
+        .map(|service| ("system".to_string(), service.to_string(), 0))
+        .collect::<Vec<(String, String, i64)>>();
+    let started = Instant::now();
+    turn_off_agents_and_daemons(&Uid(501), true, services, false);
+    assert!(started.elapsed() >= Duration::from_millis(600));
+    let bootouts = runner
+        .calls()
+        .into_iter()
+        .filter(|call| call.contains("bootout"))
+        .collect::<Vec<String>>();
+    assert_eq!(
+        bootouts,
+        vec![
+            "sudo -n /bin/launchctl bootout system/com.example.a",
+            "sudo -n /bin/launchctl bootout system/com.example.b",
+            "sudo -n /bin/launchctl bootout system/com.example.c",
+        ]
+    );
+}
+
+#[test]
+fn test_sudo_password_is_asked_for_once() {
+    let runner = Arc::new(SudoRunner::default());
+    let settings = ExecutionSettings { concurrency: 4, ..Default::default() };
+    let _guard = override_command_runner(runner.clone());
+    set_execution_settings(settings);
+    let services = ["com.example.a", "com.example.b", "com.example.c"]
+        .iter()
+        .map(|service| ("system".to_string(), service.to_string(), 0))
+        .collect::<Vec<(String, String, i64)>>();
+    let results = turn_off_agents_and_daemons(&Uid(501), true, services, false);
+    assert!(results.iter().all(|(_, _, _, result)| result.is_ok()));
+    assert_eq!(runner.prompts.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,112 @@
+use std::collections::BTreeMap;
+
+use cdb_adm::{
+    analyse_impact, launchd_log_findings, mach_services_from_plist, parse_endpoints,
+    parse_launchd_log, ImpactHint, LaunchdEndpoint, Result,
+};
+use iocore_test::folder_path;
+
+fn load_fixture(name: &str) -> String {
+    let path = folder_path!().join(name);
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+fn mach_services_plist(label: &str, mach_services: &[&str]) -> plist::Dictionary {
+    let mut services = plist::Dictionary::new();
+    for name in mach_services {
+        services.insert(name.to_string(), plist::Value::Boolean(true));
+    }
+    let mut dictionary = plist::Dictionary::new();
+    dictionary.insert("Label".to_string(), plist::Value::String(label.to_string()));
+    dictionary.insert("MachServices".to_string(), plist::Value::Dictionary(services));
+    dictionary
+}
+#[test]
+fn test_parse_endpoints() -> Result<()> {
+    let endpoints = parse_endpoints(&load_fixture("launchctl-print-system"))?;
+    assert_eq!(endpoints.len(), 615);
+    assert_eq!(
+        endpoints[0..2].to_vec(),
+        vec![
+            LaunchdEndpoint {
+                port: "0".to_string(),
+                kind: "M".to_string(),
+                active: false,
+                name: "com.apple.dt.RemotePairingDataVaultHelper".to_string(),
+            },
+            LaunchdEndpoint {
+                port: "0x28303".to_string(),
+                kind: "M".to_string(),
+                active: true,
+                name: "com.apple.backgroundtaskmanagement.sfl".to_string(),
+            },
+        ]
+    );
+    let endpoints = parse_endpoints(&load_fixture("launchctl-print-gui-501"))?;
+    assert_eq!(endpoints.len(), 1213);
+    assert!(endpoints.iter().any(|endpoint| endpoint.name == "cs (Apple)_OpenStep"));
+    assert_eq!(parse_endpoints(&load_fixture("launchctl-print-user-501"))?.len(), 1213);
+    Ok(())
+}
+#[test]
+fn test_mach_services_from_plist() {
+    let dictionary =
+        mach_services_plist("com.apple.colorsyncd", &["com.apple.colorsyncd", "com.apple.colorsync"]);
+    assert_eq!(
+        mach_services_from_plist(&dictionary),
+        vec!["com.apple.colorsyncd".to_string(), "com.apple.colorsync".to_string()]
+    );
+    assert_eq!(mach_services_from_plist(&plist::Dictionary::new()), Vec::<String>::new());
+}
+#[test]
+fn test_analyse_impact() -> Result<()> {
+    let mut endpoints = BTreeMap::<String, Vec<LaunchdEndpoint>>::new();
+    endpoints.insert("system".to_string(), parse_endpoints(&load_fixture("launchctl-print-system"))?);
+    let findings = launchd_log_findings(&parse_launchd_log(&load_fixture("launchd.log")));
+    let targets = vec![
+        (
+            "system".to_string(),
+            "com.apple.modelmanagerd".to_string(),
+            Some(mach_services_plist("com.apple.modelmanagerd", &["com.apple.modelmanager"])),
+        ),
+        (
+            "system".to_string(),
+            "com.apple.colorsyncd".to_string(),
+            Some(mach_services_plist("com.apple.colorsyncd", &["com.apple.colorsyncd"])),
+        ),
+        (
+            "system".to_string(),
+            "com.example.unknown".to_string(),
+            Some(mach_services_plist("com.example.unknown", &["com.example.unknown"])),
+        ),
+        ("system".to_string(), "com.example.noplist".to_string(), None),
+    ];
+    let hints = analyse_impact(&targets, &endpoints, &findings);
+    assert_eq!(
+        hints,
+        vec![
+            ImpactHint {
+                domain: "system".to_string(),
+                service: "com.apple.modelmanagerd".to_string(),
+                endpoint: "com.apple.modelmanager".to_string(),
+                active: true,
+                consumers: vec!["coreaudiod".to_string()],
+            },
+            ImpactHint {
+                domain: "system".to_string(),
+                service: "com.apple.colorsyncd".to_string(),
+                endpoint: "com.apple.colorsyncd".to_string(),
+                active: true,
+                consumers: Vec::new(),
+            },
+        ]
+    );
+    assert_eq!(
+        hints[0].to_string(),
+        "service system/com.apple.modelmanagerd provides endpoint com.apple.modelmanager used by coreaudiod"
+    );
+    assert_eq!(
+        hints[1].to_string(),
+        "service system/com.apple.colorsyncd provides endpoint com.apple.colorsyncd (active)"
+    );
+    Ok(())
+}
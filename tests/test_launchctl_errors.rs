@@ -0,0 +1,125 @@
+use std::sync::Arc;
+
+use cdb_adm::{
+    boot_up_agents_and_daemons, launchctl, override_command_runner, turn_off_agent_or_daemon,
+    turn_off_agents_and_daemons, Error, FakeCommandRunner, LaunchctlOutcome, Uid,
+};
+
+#[test]
+fn test_errors_carry_target_command_code_and_stderr() {
+    let stderr = "Boot-out failed: 150: Operation not permitted while System Integrity Protection \
+                  is engaged\n";
+    let _guard =
+        override_command_runner(Arc::new(FakeCommandRunner::new().fail("bootout", 150, stderr)));
+    let error = launchctl(&["bootout", "system/com.apple.mds"], true).unwrap_err();
+    assert_eq!(
+        error,
+        Error::LaunchctlError {
+            outcome: LaunchctlOutcome::SipProtected,
+            target: Some("system/com.apple.mds".to_string()),
+            command: vec!["bootout".to_string(), "system/com.apple.mds".to_string()],
+            code: 150,
+            stderr: stderr.to_string(),
+        }
+    );
+    assert_eq!(error.launchctl_outcome(), Some(LaunchctlOutcome::SipProtected));
+    assert_eq!(error.variant(), "LaunchctlError");
+}
+
+#[test]
+fn test_turn_off_reports_outcome_of_failed_service() {
+    let _guard = override_command_runner(Arc::new(
+        FakeCommandRunner::new()
+            .fail("com.apple.protected", 1, "Warning: Operation not permitted")
+            .respond("", 0, ""),
+    ));
+    let services = vec![
+        ("system".to_string(), "com.apple.protected".to_string(), 0),
+        ("system".to_string(), "com.example.a".to_string(), 0),
+    ];
+    let results = turn_off_agents_and_daemons(&Uid(501), true, services, false);
+    assert_eq!(
+        results[0].3.as_ref().unwrap_err().launchctl_outcome(),
+        Some(LaunchctlOutcome::NotPermitted)
+    );
+    assert!(results[1].3.is_ok());
+}
+
+#[test]
+fn test_not_running_services_are_turned_off() {
+    let _guard = override_command_runner(Arc::new(
+        FakeCommandRunner::new()
+            .fail("bootout", 3, "Boot-out failed: 3: No such process")
+            .respond("disable", 0, ""),
+    ));
+    turn_off_agent_or_daemon("com.example.stopped", None, false, true).unwrap();
+}
+
+#[test]
+fn test_already_loaded_services_boot_up() {
+    let _guard = override_command_runner(Arc::new(
+        FakeCommandRunner::new()
+            .fail("bootstrap", 5, "Bootstrap failed: 5: Input/output error\nservice already loaded")
+            .respond("", 0, ""),
+    ));
+    let results = boot_up_agents_and_daemons(
+        true,
+        vec![(
+            "system".to_string(),
+            "com.example.loaded".to_string(),
+            0,
+            (
+                iocore::Path::raw("/Library/LaunchDaemons/com.example.loaded.plist"),
+                plist::Dictionary::new(),
+            ),
+        )],
+    );
+    assert!(results[0].3.is_ok());
+}
+
+#[test]
+fn test_bootstrap_io_errors_fail_boot_up() {
+    let _guard = override_command_runner(Arc::new(
+        FakeCommandRunner::new().fail("bootstrap", 5, "Bootstrap failed: 5"),
+    ));
+    let results = boot_up_agents_and_daemons(
+        true,
+        vec![(
+            "system".to_string(),
+            "com.example.broken".to_string(),
+            0,
+            (
+                iocore::Path::raw("/Library/LaunchDaemons/com.example.broken.plist"),
+                plist::Dictionary::new(),
+            ),
+        )],
+    );
+    assert_eq!(
+        results[0].3.as_ref().unwrap_err().launchctl_outcome(),
+        Some(LaunchctlOutcome::IOError)
+    );
+}
+
+#[test]
+fn test_errors_serialize_as_kind_and_message() {
+    let error = Error::ConfigurationError("enforce requires a policy".to_string());
+    let json = serde_json::to_string(&error).unwrap();
+    assert_eq!(json, "{\"kind\":\"ConfigurationError\",\"message\":\"enforce requires a policy\"}");
+    assert_eq!(serde_json::from_str::<Error>(&json).unwrap(), error);
+    let error = Error::launchctl(&["bootout", "gui/501/com.example.a"], 3, "No such process");
+    let value = serde_json::to_value(&error).unwrap();
+    assert_eq!(value["kind"], "LaunchctlError");
+    assert_eq!(value["message"], error.message());
+    assert_eq!(value["target"], "gui/501/com.example.a");
+    assert_eq!(value["code"], 3);
+    assert_eq!(serde_json::from_value::<Error>(value).unwrap(), error);
+}
+
+#[test]
+fn test_errors_deserialize_from_variant_maps() {
+    assert_eq!(
+        serde_json::from_str::<Error>("{\"IOError\":\"defaults delete failed\"}").unwrap(),
+        Error::IOError("defaults delete failed".to_string())
+    );
+    assert!(serde_json::from_str::<Error>("{\"kind\":\"Nonsense\",\"message\":\"\"}").is_err());
+}
@@ -0,0 +1,132 @@
+use cdb_adm::{CalendarInterval, Error, LaunchdKind, LaunchdPlistSpec, PlistFormat, Result, Uid};
+
+const SPEC: &str = r#"
+label = "com.example.backup"
+kind = "daemon"
+program_arguments = ["/usr/local/bin/backup", "--quiet"]
+run_at_load = true
+standard_out_path = "/var/log/backup.log"
+
+[environment_variables]
+PATH = "/usr/bin:/bin"
+
+[[start_calendar_interval]]
+hour = 3
+minute = 30
+
+[[start_calendar_interval]]
+weekday = 0
+"#;
+
+#[test]
+fn test_spec_from_toml() -> Result<()> {
+    let spec = LaunchdPlistSpec::from_toml(SPEC)?;
+    assert_eq!(spec.label, "com.example.backup");
+    assert_eq!(spec.kind, LaunchdKind::Daemon);
+    assert_eq!(spec.program_arguments, vec!["/usr/local/bin/backup", "--quiet"]);
+    assert_eq!(
+        spec.start_calendar_interval,
+        vec![
+            CalendarInterval { minute: Some(30), hour: Some(3), ..CalendarInterval::default() },
+            CalendarInterval { weekday: Some(0), ..CalendarInterval::default() },
+        ]
+    );
+    assert_eq!(spec.keep_alive, None);
+    assert_eq!(spec.validate(), Ok(()));
+    Ok(())
+}
+
+#[test]
+fn test_spec_to_dictionary() -> Result<()> {
+    let dictionary = LaunchdPlistSpec::from_toml(SPEC)?.to_dictionary();
+    assert_eq!(
+        dictionary.keys().map(|key| key.as_str()).collect::<Vec<&str>>(),
+        vec![
+            "Label",
+            "ProgramArguments",
+            "StartCalendarInterval",
+            "RunAtLoad",
+            "StandardOutPath",
+            "EnvironmentVariables",
+        ]
+    );
+    let intervals = dictionary
+        .get("StartCalendarInterval")
+        .and_then(|intervals| intervals.as_array())
+        .expect("StartCalendarInterval array");
+    assert_eq!(
+        intervals[0]
+            .as_dictionary()
+            .and_then(|interval| interval.get("Hour"))
+            .and_then(|hour| hour.as_unsigned_integer()),
+        Some(3)
+    );
+    assert_eq!(dictionary.get("RunAtLoad").and_then(|h| h.as_boolean()), Some(true));
+    Ok(())
+}
+
+#[test]
+fn test_spec_to_bytes_roundtrip() -> Result<()> {
+    let spec = LaunchdPlistSpec::from_toml(SPEC)?;
+    let xml = spec.to_bytes(PlistFormat::Xml)?;
+    assert!(String::from_utf8_lossy(&xml).contains("<key>Label</key>"));
+    let binary = spec.to_bytes(PlistFormat::Binary)?;
+    assert!(binary.starts_with(b"bplist00"));
+    for bytes in [xml, binary] {
+        assert_eq!(plist::from_bytes::<plist::Dictionary>(&bytes)?, spec.to_dictionary());
+    }
+    Ok(())
+}
+
+#[test]
+fn test_spec_validation() {
+    let spec = LaunchdPlistSpec {
+        label: "com.example.nothing".to_string(),
+        ..LaunchdPlistSpec::default()
+    };
+    assert_eq!(
+        spec.validate(),
+        Err(Error::ConfigurationError(
+            "launchd plist \"com.example.nothing\" requires program arguments".to_string()
+        ))
+    );
+    assert!(LaunchdPlistSpec::default().validate().is_err());
+    assert!(spec.to_bytes(PlistFormat::Xml).is_err());
+}
+
+#[test]
+fn test_spec_install_path_and_domain() -> Result<()> {
+    let daemon = LaunchdPlistSpec::from_toml(SPEC)?;
+    assert_eq!(
+        daemon.install_path(false).to_string(),
+        "/Library/LaunchDaemons/com.example.backup.plist"
+    );
+    assert!(daemon.owned_by_root(false));
+    assert_eq!(daemon.domain(&Uid(501)), "system");
+
+    let agent = LaunchdPlistSpec { kind: LaunchdKind::Agent, ..daemon };
+    assert_eq!(
+        agent.install_path(true).to_string(),
+        "/Library/LaunchAgents/com.example.backup.plist"
+    );
+    assert!(
+        agent
+            .install_path(false)
+            .to_string()
+            .ends_with("/Library/LaunchAgents/com.example.backup.plist")
+    );
+    assert!(!agent.owned_by_root(false));
+    assert_eq!(agent.domain(&Uid(501)), "gui/501");
+    Ok(())
+}
+
+#[test]
+fn test_calendar_interval_from_str() {
+    assert_eq!(
+        "hour=3, minute=30".parse::<CalendarInterval>(),
+        Ok(CalendarInterval { minute: Some(30), hour: Some(3), ..CalendarInterval::default() })
+    );
+    assert!("hour".parse::<CalendarInterval>().is_err());
+    assert!("second=1".parse::<CalendarInterval>().is_err());
+    assert!("".parse::<CalendarInterval>().is_err());
+}
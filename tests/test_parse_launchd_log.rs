@@ -0,0 +1,143 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use cdb_adm::{
+    launchd_log_findings, override_command_runner, parse_launchd_log, set_execution_settings,
+    turn_off_capturing_launchd_log, CommandRunner, ExecutionSettings, LaunchdLogCapture,
+    LaunchdLogCursor, LaunchdLogEntry, LaunchdLogFinding, Result, Uid,
+};
+use iocore_test::{folder_path, path_to_test_file};
+
+/// Appends a line about the target of every `launchctl` call to `log`
+/// as launchd would, slowly enough for concurrent calls to overlap.
+struct LoggingRunner {
+    log: iocore::Path,
+}
+impl CommandRunner for LoggingRunner {
+    fn run(&self, _program: &str, args: &[&str]) -> Result<(i64, String, String)> {
+        std::thread::sleep(Duration::from_millis(50));
+        let mut launchctl_args = args.iter().skip_while(|arg| !arg.ends_with("launchctl")).skip(1);
+        let subcommand = launchctl_args.next().unwrap_or(&"");
+        let target = args[args.len() - 1];
+        self.log.append(
+            format!("2025-04-20 10:12:33.102113 ({}) <Notice>: {}\n", target, subcommand)
+                .as_bytes(),
+        )?;
+        Ok((0, String::new(), String::new()))
+    }
+}
+
+fn load_launchd_log() -> String {
+    let path = folder_path!().join("launchd.log");
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+#[test]
+fn test_parse_launchd_log() {
+    let entries = parse_launchd_log(&load_launchd_log());
+    assert_eq!(entries.len(), 13);
+    assert_eq!(
+        entries[0],
+        LaunchdLogEntry {
+            timestamp: "2025-04-20 10:12:33.102113".to_string(),
+            domain: Some("system".to_string()),
+            service: Some("com.apple.modelmanager".to_string()),
+            pid: Some(1207),
+            level: "Notice".to_string(),
+            message: "service state: running".to_string(),
+            requestor_pid: None,
+        }
+    );
+    assert_eq!(entries[2].domain, Some("gui/501".to_string()));
+    assert_eq!(entries[2].service, None);
+    assert_eq!(entries[2].pid, Some(100017));
+    assert_eq!(entries[3].target(), Some("gui/501/com.apple.Siri.agent".to_string()));
+    assert_eq!(entries[4].domain, None);
+    assert_eq!(entries[4].message, "Last log repeated 1 times");
+    assert_eq!(entries[5].level, "Warning");
+    assert_eq!(entries[5].requestor_pid, Some(418));
+    assert_eq!(entries[12].domain, Some("pid/12260".to_string()));
+    assert_eq!(entries[12].pid, None);
+    assert_eq!(
+        entries[12].message,
+        "Could not find and/or execute program specified by service: 2: No such file or directory\n\tpath = /System/Library/Frameworks/CoreServices.framework/mdworker_shared"
+    );
+}
+#[test]
+fn test_launchd_log_findings() {
+    let findings = launchd_log_findings(&parse_launchd_log(&load_launchd_log()));
+    assert_eq!(
+        findings,
+        vec![
+            LaunchdLogFinding::FailedLookup {
+                name: "com.apple.modelmanager".to_string(),
+                requestor: "coreaudiod".to_string(),
+                requestor_pid: Some(418),
+                error: "3: No such process".to_string(),
+            },
+            LaunchdLogFinding::FailedLookup {
+                name: "com.apple.BTAudioHALPlugin.xpc".to_string(),
+                requestor: "coreaudiod".to_string(),
+                requestor_pid: Some(418),
+                error: "3: No such process".to_string(),
+            },
+            LaunchdLogFinding::CrashLoop {
+                target: "gui/501/com.apple.assistantd".to_string(),
+                exits: 3,
+            },
+        ]
+    );
+}
+#[test]
+fn test_launchd_log_cursor_reads_appended_lines_only() -> Result<()> {
+    let path = path_to_test_file!("launchd.log");
+    let log = load_launchd_log();
+    let lines = log.lines().collect::<Vec<&str>>();
+    path.write(format!("{}\n", lines[0..5].join("\n")).as_bytes())?;
+
+    let cursor = LaunchdLogCursor::open(&path)?;
+    path.append(format!("{}\n", lines[5..8].join("\n")).as_bytes())?;
+
+    let (data, report) = cursor.report("system", "com.apple.modelmanager")?;
+    assert_eq!(data, format!("{}\n", lines[5..8].join("\n")));
+    assert_eq!(report.entries.len(), 3);
+    assert_eq!(report.findings.len(), 2);
+    assert_eq!(report.error, None);
+    Ok(())
+}
+#[test]
+fn test_turn_off_captures_the_launchd_log_lines_of_each_service() -> Result<()> {
+    let log = path_to_test_file!("launchd.log");
+    log.write(b"2025-04-20 10:12:33.102113 (system/com.example.before) <Notice>: before\n")?;
+    let capture = LaunchdLogCapture {
+        log_path: log.clone(),
+        reports_path: path_to_test_file!("logs"),
+    };
+    let _guard = override_command_runner(Arc::new(LoggingRunner { log: log.clone() }));
+    set_execution_settings(ExecutionSettings {
+        concurrency: 4,
+        ..Default::default()
+    });
+    let services = ["com.example.a", "com.example.b", "com.example.c"]
+        .iter()
+        .map(|service| ("system".to_string(), service.to_string(), 0))
+        .collect::<Vec<(String, String, i64)>>();
+    let results = turn_off_capturing_launchd_log(&Uid(501), true, services, Some(&capture));
+    assert!(results.iter().all(|(_, _, _, result)| result.is_ok()));
+    for service in ["com.example.a", "com.example.b", "com.example.c"] {
+        let report = capture.report_path("system", service);
+        assert_eq!(
+            report.join("launchd.log").read()?,
+            ["bootout", "disable", "kill"]
+                .iter()
+                .map(|subcommand| {
+                    format!(
+                        "2025-04-20 10:12:33.102113 (system/{}) <Notice>: {}\n",
+                        service, subcommand
+                    )
+                })
+                .collect::<String>()
+        );
+        assert!(report.join("report.json").read()?.contains("\"entries\""));
+    }
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use cdb_adm::{parse_launchctl_blame, parse_launchctl_list, BlameReason, LaunchctlListEntry, Result};
+use iocore_test::folder_path;
+
+fn load_fixture(name: &str) -> String {
+    let path = folder_path!().join(name);
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+#[test]
+fn test_parse_launchctl_list() -> Result<()> {
+    let entries = parse_launchctl_list(&load_fixture("launchctl-list-gui-501"))?;
+    assert_eq!(entries.len(), 62);
+    assert_eq!(
+        entries[0..4].to_vec(),
+        vec![
+            LaunchctlListEntry {
+                pid: Some(599),
+                status: 0,
+                label: "com.apple.syncdefaultsd".to_string(),
+            },
+            LaunchctlListEntry {
+                pid: Some(602),
+                status: 0,
+                label: "com.apple.assistantd".to_string(),
+            },
+            LaunchctlListEntry {
+                pid: None,
+                status: 0,
+                label: "com.apple.DataDetectorsLocalSources".to_string(),
+            },
+            LaunchctlListEntry {
+                pid: None,
+                status: 0,
+                label: "com.apple.unmountassistant.useragent".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        entries
+            .iter()
+            .filter(|entry| entry.status != 0)
+            .map(|entry| (entry.label.to_string(), entry.status))
+            .collect::<Vec<(String, i64)>>(),
+        vec![
+            ("com.apple.ReportCrash".to_string(), -9),
+            ("com.apple.mdworker.shared".to_string(), 78),
+        ]
+    );
+    Ok(())
+}
+#[test]
+fn test_parse_launchctl_list_invalid_line() {
+    assert!(parse_launchctl_list("PID\tStatus\tLabel\nnot a service\n").is_err());
+}
+#[test]
+fn test_parse_launchctl_blame() -> Result<()> {
+    let blame = parse_launchctl_blame(&load_fixture("launchctl-blame-speculative"))?;
+    assert_eq!(blame.reason, BlameReason::Speculative);
+    assert_eq!(blame.detail, None);
+
+    let blame = parse_launchctl_blame(&load_fixture("launchctl-blame-demand"))?;
+    assert_eq!(blame.reason, BlameReason::Demand);
+    assert_eq!(blame.to_string(), "demand");
+
+    let blame = parse_launchctl_blame(&load_fixture("launchctl-blame-non-ipc-demand"))?;
+    assert_eq!(blame.reason, BlameReason::NonIpcDemand);
+
+    let blame = parse_launchctl_blame(&load_fixture("launchctl-blame-ipc"))?;
+    assert_eq!(blame.reason, BlameReason::Ipc);
+    assert_eq!(blame.detail, Some("mach message".to_string()));
+    assert_eq!(blame.to_string(), "ipc (mach message)");
+
+    let blame = parse_launchctl_blame(&load_fixture("launchctl-blame-event"))?;
+    assert_eq!(blame.reason, BlameReason::Event);
+    assert_eq!(blame.detail, Some("com.apple.notifyd.matching".to_string()));
+    Ok(())
+}
+#[test]
+fn test_parse_launchctl_blame_empty() {
+    assert!(parse_launchctl_blame("\n").is_err());
+}
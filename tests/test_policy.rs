@@ -0,0 +1,141 @@
+use std::collections::BTreeMap;
+
+use cdb_adm::{
+    enforce_launch_agent_plist, evaluate_policy, parse_services, service_states, Deviation,
+    Policy, PolicyDeviation, PolicyRule, Result, Uid, ENFORCE_LAUNCH_AGENT_LABEL,
+};
+use iocore_test::folder_path;
+
+fn load_fixture(name: &str) -> String {
+    let path = folder_path!().join(name);
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+
+fn system_states() -> Result<Vec<cdb_adm::ServiceState>> {
+    let loaded = parse_services(&load_fixture("launchctl-print-system"), false)?
+        .iter()
+        .map(|(pid, _, service, _)| ("system".to_string(), service.to_string(), *pid))
+        .collect::<Vec<(String, String, i64)>>();
+    let overrides = parse_services(&load_fixture("launchctl-print-system-disabled"), true)?
+        .iter()
+        .map(|(_, _, service, enabled)| ("system".to_string(), service.to_string(), *enabled))
+        .collect::<Vec<(String, String, bool)>>();
+    Ok(service_states(&loaded, &overrides))
+}
+
+#[test]
+fn test_service_states() -> Result<()> {
+    let states = system_states()?;
+    let dnsmasq = states.iter().find(|state| state.service == "homebrew.mxcl.dnsmasq").unwrap();
+    assert_eq!((dnsmasq.pid, dnsmasq.loaded, dnsmasq.enabled), (515, true, true));
+    let commcenter = states.iter().find(|state| state.service == "com.apple.CommCenter").unwrap();
+    assert_eq!((commcenter.pid, commcenter.loaded, commcenter.enabled), (0, false, false));
+    let cron = states.iter().find(|state| state.service == "com.vix.cron").unwrap();
+    assert_eq!((cron.pid, cron.loaded, cron.enabled), (0, true, true));
+    Ok(())
+}
+
+#[test]
+fn test_evaluate_policy() -> Result<()> {
+    let policy = toml::from_str::<Policy>(
+        r#"
+        disabled = [
+            "homebrew.mxcl.dnsmasq",
+            "com.apple.CommCenter",
+            "com.apple.tmp_cleaner",
+            "com.apple.MRT*",
+        ]
+        bootout = ["system/com.vix.cron"]
+        ignore = ["com.apple.MRTd"]
+        interval = 600
+        "#,
+    )
+    .unwrap();
+    assert_eq!(policy.interval(), 600);
+    assert_eq!(
+        evaluate_policy(&policy, &system_states()?),
+        vec![
+            PolicyDeviation {
+                domain: "system".to_string(),
+                service: "com.apple.tmp_cleaner".to_string(),
+                pid: 0,
+                rule: PolicyRule::Disabled,
+                deviations: vec![Deviation::Enabled],
+            },
+            PolicyDeviation {
+                domain: "system".to_string(),
+                service: "com.vix.cron".to_string(),
+                pid: 0,
+                rule: PolicyRule::Bootout,
+                deviations: vec![Deviation::Enabled, Deviation::Loaded],
+            },
+            PolicyDeviation {
+                domain: "system".to_string(),
+                service: "homebrew.mxcl.dnsmasq".to_string(),
+                pid: 515,
+                rule: PolicyRule::Disabled,
+                deviations: vec![Deviation::Enabled, Deviation::Running],
+            },
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_evaluate_empty_policy() -> Result<()> {
+    assert_eq!(evaluate_policy(&Policy::default(), &system_states()?), vec![]);
+    Ok(())
+}
+
+#[test]
+fn test_policy_deviation_display() {
+    let deviation = PolicyDeviation {
+        domain: "system".to_string(),
+        service: "com.vix.cron".to_string(),
+        pid: 0,
+        rule: PolicyRule::Bootout,
+        deviations: vec![Deviation::Enabled, Deviation::Loaded],
+    };
+    assert_eq!(
+        deviation.to_string(),
+        "system/com.vix.cron should be booted out but is enabled, loaded"
+    );
+}
+
+#[test]
+fn test_enforce_launch_agent_plist() {
+    let environment = BTreeMap::from([(
+        "CDB_ADM_SETTINGS".to_string(),
+        "/Users/me/.config/cdb-adm.toml".to_string(),
+    )]);
+    let dictionary = enforce_launch_agent_plist(
+        "/usr/local/bin/adm",
+        &Uid(501),
+        environment,
+        900,
+        "/tmp/adm-enforce.log",
+    );
+    assert_eq!(
+        dictionary.get("Label").and_then(|label| label.as_string()),
+        Some(ENFORCE_LAUNCH_AGENT_LABEL)
+    );
+    assert_eq!(
+        dictionary.get("ProgramArguments").and_then(|args| args.as_array()).map(|args| args
+            .iter()
+            .filter_map(|arg| arg.as_string())
+            .collect::<Vec<&str>>()),
+        Some(vec!["/usr/local/bin/adm", "enforce", "--once", "--uid", "501"])
+    );
+    assert_eq!(
+        dictionary
+            .get("EnvironmentVariables")
+            .and_then(|environment| environment.as_dictionary())
+            .and_then(|environment| environment.get("CDB_ADM_SETTINGS"))
+            .and_then(|path| path.as_string()),
+        Some("/Users/me/.config/cdb-adm.toml")
+    );
+    assert_eq!(
+        dictionary.get("StartInterval").and_then(|interval| interval.as_unsigned_integer()),
+        Some(900)
+    );
+}
@@ -0,0 +1,77 @@
+use std::collections::BTreeMap;
+
+use cdb_adm::{diff_preferences, read_exported_domains, PreferenceChange, Result};
+
+fn dictionary(entries: &[(&str, plist::Value)]) -> plist::Value {
+    plist::Value::Dictionary(
+        entries.iter().map(|(key, value)| (key.to_string(), value.clone())).collect(),
+    )
+}
+
+fn export(
+    domains: &[(&str, plist::Value)],
+) -> BTreeMap<String, (plist::Value, Option<iocore::Path>)> {
+    domains.iter().map(|(domain, value)| (domain.to_string(), (value.clone(), None))).collect()
+}
+
+#[test]
+fn test_diff_preferences() {
+    let before = export(&[
+        (
+            "com.apple.dock",
+            dictionary(&[
+                ("autohide", plist::Value::Boolean(false)),
+                ("tilesize", plist::Value::Integer(48.into())),
+                ("wvous", dictionary(&[("tl", plist::Value::Integer(2.into()))])),
+            ]),
+        ),
+        ("com.apple.gone", dictionary(&[])),
+    ]);
+    let after = export(&[(
+        "com.apple.dock",
+        dictionary(&[
+            ("autohide", plist::Value::Boolean(true)),
+            ("tilesize", plist::Value::Integer(48.into())),
+            ("wvous", dictionary(&[("br", plist::Value::Integer(4.into()))])),
+        ]),
+    )]);
+    let changes = diff_preferences(&before, &after);
+    assert_eq!(
+        changes
+            .iter()
+            .map(|change| (change.domain.as_str(), change.key.join(".")))
+            .collect::<Vec<_>>(),
+        vec![
+            ("com.apple.dock", "autohide".to_string()),
+            ("com.apple.dock", "wvous.br".to_string()),
+            ("com.apple.dock", "wvous.tl".to_string()),
+            ("com.apple.gone", String::new()),
+        ]
+    );
+    assert_eq!(
+        changes[0],
+        PreferenceChange {
+            domain: "com.apple.dock".to_string(),
+            key: vec!["autohide".to_string()],
+            before: Some(plist::Value::Boolean(false)),
+            after: Some(plist::Value::Boolean(true)),
+        }
+    );
+    assert_eq!(changes[1].before, None);
+    assert_eq!(changes[3].after, None);
+    assert!(diff_preferences(&after, &after).is_empty());
+}
+
+#[test]
+fn test_read_exported_domains() -> Result<()> {
+    let domains = export(&[("com.apple.dock", dictionary(&[("autohide", true.into())]))]);
+    let path = iocore::Path::raw(std::env::temp_dir().display().to_string())
+        .join(format!("cdb-adm-test-export-{}.json", std::process::id()));
+    path.write(serde_json::to_string_pretty(&domains)?.as_bytes())?;
+    assert_eq!(read_exported_domains(&path)?, domains);
+    let deleted = serde_json::json!({"domain_map": &domains, "errors": {}});
+    path.write(serde_json::to_string(&deleted)?.as_bytes())?;
+    assert_eq!(read_exported_domains(&path)?, domains);
+    path.delete()?;
+    Ok(())
+}
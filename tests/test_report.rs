@@ -0,0 +1,224 @@
+use std::collections::BTreeMap;
+
+use cdb_adm::{
+    fill_template, html_escape, markdown_escape, plist_program, preference_reports,
+    service_reports, AuditReport, ReportFormat, ServiceCatalog, ServiceRisk, Uid,
+};
+use iocore_test::folder_path;
+use plist::Value;
+
+fn load_fixture(name: &str) -> String {
+    let path = folder_path!().join(name);
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+
+fn dictionary(entries: Vec<(&str, Value)>) -> plist::Dictionary {
+    entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect()
+}
+
+fn strings(values: &[&str]) -> Value {
+    Value::Array(values.iter().map(|value| Value::String(value.to_string())).collect())
+}
+
+fn mach_services(names: &[&str]) -> Value {
+    Value::Dictionary(names.iter().map(|name| (name.to_string(), Value::Boolean(true))).collect())
+}
+
+fn catalog() -> ServiceCatalog {
+    ServiceCatalog::new(
+        "[\n    // <sharing>\n    \"com.apple.nearbyd\",\n    // </sharing>\n]",
+        "[\n    \"com.apple.bluetoothUIServer\",\n]",
+    )
+}
+
+type AgentOrDaemon =
+    (String, String, i64, Option<i64>, bool, Option<(iocore::Path, plist::Dictionary)>);
+
+fn service(
+    domain: &str,
+    label: &str,
+    pid: i64,
+    status: Option<i64>,
+    enabled: bool,
+    plist: Option<(&str, plist::Dictionary)>,
+) -> AgentOrDaemon {
+    (
+        domain.to_string(),
+        label.to_string(),
+        pid,
+        status,
+        enabled,
+        plist.map(|(path, dictionary)| (iocore::Path::raw(path), dictionary)),
+    )
+}
+
+fn agents_and_daemons() -> Vec<AgentOrDaemon> {
+    vec![
+        service(
+            "gui/501",
+            "com.apple.nearbyd",
+            0,
+            Some(-9),
+            false,
+            Some((
+                "/System/Library/LaunchAgents/com.apple.nearbyd.plist",
+                dictionary(vec![
+                    ("Program", Value::String("/usr/libexec/nearbyd".to_string())),
+                    (
+                        "MachServices",
+                        mach_services(&["com.apple.nearbyd", "com.apple.nearbyd.xpc"]),
+                    ),
+                ]),
+            )),
+        ),
+        service("gui/501", "com.apple.bluetoothUIServer", 733, None, true, None),
+        service(
+            "gui/501",
+            "com.example.agent",
+            0,
+            Some(0),
+            true,
+            Some((
+                "/Users/me/Library/LaunchAgents/com.example.agent.plist",
+                dictionary(vec![
+                    (
+                        "ProgramArguments",
+                        strings(&["/Applications/Tom & Jerry.app/Contents/MacOS/agent"]),
+                    ),
+                    ("RunAtLoad", Value::Boolean(false)),
+                    ("KeepAlive", Value::Dictionary(plist::Dictionary::new())),
+                ]),
+            )),
+        ),
+        service(
+            "system",
+            "com.example.updater",
+            412,
+            Some(0),
+            true,
+            Some((
+                "/Library/LaunchDaemons/com.example.updater.plist",
+                dictionary(vec![
+                    ("ProgramArguments", strings(&["/Library/Example/updater", "--daemon"])),
+                    ("RunAtLoad", Value::Boolean(true)),
+                    ("KeepAlive", Value::Boolean(true)),
+                    ("MachServices", mach_services(&["com.example.updater.xpc"])),
+                ]),
+            )),
+        ),
+    ]
+}
+
+fn audit_report() -> AuditReport {
+    let domains = BTreeMap::from([
+        (
+            "NSGlobalDomain".to_string(),
+            Value::Dictionary(dictionary(vec![
+                ("AppleLanguages", strings(&["en-US"])),
+                ("AppleLocale", Value::String("en_US".to_string())),
+                ("AppleInterfaceStyle", Value::String("Dark".to_string())),
+            ])),
+        ),
+        (
+            "com.apple.dock".to_string(),
+            Value::Dictionary(dictionary(vec![
+                ("autohide", Value::Boolean(true)),
+                ("tilesize", Value::Integer(48.into())),
+            ])),
+        ),
+        ("com.example.broken".to_string(), Value::String("not a dictionary".to_string())),
+    ]);
+    AuditReport {
+        generated: 1760000000,
+        uid: Uid(501),
+        services: service_reports(&agents_and_daemons(), &catalog()),
+        preferences: preference_reports(&domains),
+    }
+}
+
+#[test]
+fn test_service_reports() {
+    let services = service_reports(&agents_and_daemons(), &catalog());
+    assert_eq!(
+        services
+            .iter()
+            .map(|service| (service.service.as_str(), service.risk))
+            .collect::<Vec<(&str, ServiceRisk)>>(),
+        vec![
+            ("com.example.updater", ServiceRisk::High),
+            ("com.apple.bluetoothUIServer", ServiceRisk::Medium),
+            ("com.example.agent", ServiceRisk::Medium),
+            ("com.apple.nearbyd", ServiceRisk::Low),
+        ]
+    );
+    let updater = &services[0];
+    assert_eq!(updater.program.as_deref(), Some("/Library/Example/updater"));
+    assert_eq!((updater.run_at_load, updater.keep_alive), (true, true));
+    assert_eq!(updater.mach_services, vec!["com.example.updater.xpc"]);
+    assert_eq!(updater.path.as_deref(), Some("/Library/LaunchDaemons/com.example.updater.plist"));
+    assert!(!services[2].keep_alive);
+    assert_eq!(services[3].categories, vec!["apple", "non-needed", "sharing"]);
+}
+
+#[test]
+fn test_service_risk() {
+    let categories =
+        |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<String>>();
+    let third_party = categories(&["third-party"]);
+    assert_eq!(ServiceRisk::assess("system", true, &third_party, false, false), ServiceRisk::High);
+    assert_eq!(ServiceRisk::assess("gui/501", true, &third_party, true, false), ServiceRisk::High);
+    assert_eq!(
+        ServiceRisk::assess("gui/501", false, &third_party, false, false),
+        ServiceRisk::Medium
+    );
+    let non_needed = categories(&["apple", "non-needed"]);
+    assert_eq!(ServiceRisk::assess("system", true, &non_needed, true, true), ServiceRisk::Medium);
+    assert_eq!(ServiceRisk::assess("system", false, &non_needed, true, true), ServiceRisk::Low);
+    assert_eq!(
+        ServiceRisk::assess("gui/501", true, &categories(&["apple"]), true, true),
+        ServiceRisk::Low
+    );
+}
+
+#[test]
+fn test_plist_program() {
+    assert_eq!(
+        plist_program(&dictionary(vec![
+            ("Program", Value::String("/usr/libexec/a".to_string())),
+            ("ProgramArguments", strings(&["/usr/libexec/b", "-v"])),
+        ])),
+        Some("/usr/libexec/a".to_string())
+    );
+    assert_eq!(
+        plist_program(&dictionary(vec![("ProgramArguments", strings(&["/usr/libexec/b"]))])),
+        Some("/usr/libexec/b".to_string())
+    );
+    assert_eq!(plist_program(&plist::Dictionary::new()), None);
+}
+
+#[test]
+fn test_fill_template() {
+    let values = [("name", "{{value}}".to_string()), ("value", "x".to_string())];
+    assert_eq!(
+        fill_template("{{name}} {{value}} {{unknown}}", &values),
+        "{{value}} x {{unknown}}"
+    );
+    assert_eq!(fill_template("{{name", &values), "{{name");
+    assert_eq!(markdown_escape("a|b\nc"), "a\\|b c");
+    assert_eq!(
+        html_escape("<a href=\"x\">Tom & Jerry's</a>"),
+        "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+    );
+}
+
+#[test]
+fn test_render_markdown_report() {
+    let report = audit_report();
+    assert_eq!((report.running(), report.disabled()), (2, 1));
+    assert_eq!(report.render(ReportFormat::Md), load_fixture("audit-report.md"));
+}
+
+#[test]
+fn test_render_html_report() {
+    assert_eq!(audit_report().render(ReportFormat::Html), load_fixture("audit-report.html"));
+}
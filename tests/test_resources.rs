@@ -0,0 +1,149 @@
+use std::sync::Arc;
+
+use cdb_adm::{
+    format_epoch, format_kib, override_command_runner, parse_proc_boot_time, parse_ps_duration_ms,
+    parse_ps_resources, parse_ps_threads, proc_resources, ps_resources, rank_by_resources,
+    FakeCommandRunner, ProcessResources, ResourceSort, Result, ServiceStatus,
+};
+use iocore_test::folder_path;
+
+const NOW: u64 = 1760000000;
+
+fn load_fixture(name: &str) -> String {
+    let path = folder_path!().join(name);
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+
+fn status(domain: &str, service: &str, pid: i64) -> ServiceStatus {
+    ServiceStatus {
+        domain: domain.to_string(),
+        service: service.to_string(),
+        pid,
+        status: Some(0),
+        enabled: true,
+        path: None,
+        categories: Vec::new(),
+    }
+}
+
+#[test]
+fn test_parse_ps_duration_ms() -> Result<()> {
+    assert_eq!(parse_ps_duration_ms("0:03.94")?, 3940);
+    assert_eq!(parse_ps_duration_ms("12:34.56")?, 754560);
+    assert_eq!(parse_ps_duration_ms("01:02:03")?, 3723000);
+    assert_eq!(parse_ps_duration_ms("2-03:04:05")?, 183845000);
+    assert_eq!(parse_ps_duration_ms("00:05")?, 5000);
+    assert!(parse_ps_duration_ms("5").is_err());
+    assert!(parse_ps_duration_ms("a:05").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_ps_resources() -> Result<()> {
+    let resources = parse_ps_resources(&load_fixture("ps-o-pid-rss-time-etime"), NOW)?;
+    assert_eq!(resources.keys().copied().collect::<Vec<i64>>(), vec![93, 310, 4210]);
+    assert_eq!(
+        resources[&310],
+        ProcessResources {
+            pid: 310,
+            rss_kib: 524288,
+            cpu_time_ms: 754560,
+            threads: 0,
+            started: NOW - 3723,
+        }
+    );
+    assert_eq!(resources[&93].started, NOW - 183845);
+    assert!(parse_ps_resources("93 10240\n", NOW).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_ps_threads() {
+    let threads = parse_ps_threads(&load_fixture("ps-M"));
+    assert_eq!(
+        threads.into_iter().collect::<Vec<(i64, u64)>>(),
+        vec![(93, 3), (310, 2), (4210, 1)]
+    );
+}
+
+#[test]
+fn test_ps_resources() -> Result<()> {
+    let runner = Arc::new(
+        FakeCommandRunner::new()
+            .respond("/bin/ps -o", 0, &load_fixture("ps-o-pid-rss-time-etime"))
+            .respond("/bin/ps -M", 0, &load_fixture("ps-M")),
+    );
+    let _guard = override_command_runner(runner.clone());
+    let resources = ps_resources(&[93, 310, 4210], NOW)?;
+    assert_eq!(
+        runner.calls(),
+        vec![
+            "/bin/ps -o pid=,rss=,time=,etime= -p 93,310,4210",
+            "/bin/ps -M -p 93,310,4210"
+        ]
+    );
+    assert_eq!(
+        resources.values().map(|resources| resources.threads).collect::<Vec<u64>>(),
+        vec![3, 2, 1]
+    );
+    assert!(ps_resources(&[], NOW)?.is_empty());
+    assert_eq!(runner.calls().len(), 2);
+    Ok(())
+}
+
+#[test]
+fn test_proc_resources() -> Result<()> {
+    let root = folder_path!().join("proc");
+    assert_eq!(parse_proc_boot_time(&root.join("stat").read()?)?, NOW);
+    let resources = proc_resources(&root, &[93, 310, 4210])?;
+    assert_eq!(resources.keys().copied().collect::<Vec<i64>>(), vec![310, 4210]);
+    assert_eq!(
+        resources[&4210],
+        ProcessResources {
+            pid: 4210,
+            rss_kib: 8192,
+            cpu_time_ms: 3750,
+            threads: 6,
+            started: NOW + 3600,
+        }
+    );
+    assert_eq!(resources[&310].cpu_time_ms, 154560);
+    Ok(())
+}
+
+#[test]
+fn test_rank_by_resources() -> Result<()> {
+    let resources = parse_ps_resources(&load_fixture("ps-o-pid-rss-time-etime"), NOW)?;
+    let statuses = vec![
+        status("system", "com.apple.bluetoothd", 93),
+        status("gui/501", "com.apple.mobileassetd", 0),
+        status("user/501", "com.example.agent", 4210),
+        status("system", "com.apple.analyticsd", 310),
+    ];
+    let labels = |by: ResourceSort| {
+        rank_by_resources(statuses.clone(), &resources, by)
+            .into_iter()
+            .map(|(status, _)| status.service)
+            .collect::<Vec<String>>()
+    };
+    assert_eq!(
+        labels(ResourceSort::Memory),
+        vec!["com.apple.analyticsd", "com.apple.bluetoothd", "com.example.agent"]
+    );
+    assert_eq!(
+        labels(ResourceSort::Cpu),
+        vec!["com.apple.analyticsd", "com.example.agent", "com.apple.bluetoothd"]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_format_resources() {
+    assert_eq!(format_kib(812), "812K");
+    assert_eq!(format_kib(10240), "10.0M");
+    assert_eq!(format_kib(3 * 1048576 / 2), "1.5G");
+    assert_eq!(format_epoch(NOW + 3600), "2025-10-09 09:53:20");
+    assert_eq!(format_epoch(951782400), "2000-02-29 00:00:00");
+    let resources = ProcessResources { cpu_time_ms: 3723450, ..ProcessResources::default() };
+    assert_eq!(resources.cpu_time(), "1:02:03.45");
+}
@@ -0,0 +1,46 @@
+use cdb_adm::{Error, FileSecretStore, MemorySecretStore, Result, SecretStore, SECRET_NOT_FOUND};
+
+fn temp_path(name: &str) -> iocore::Path {
+    iocore::Path::raw(std::env::temp_dir().display().to_string())
+        .join(format!("cdb-adm-test-{}-{}.json", name, std::process::id()))
+}
+
+#[test]
+fn test_memory_secret_store() -> Result<()> {
+    let store = MemorySecretStore::default();
+    store.set("cdb-adm-backup-key", "cdb-adm", b"0011")?;
+    assert_eq!(store.get("cdb-adm-backup-key", "cdb-adm")?, b"0011".to_vec());
+    assert_eq!(
+        store.get("cdb-adm-backup-key", "other"),
+        Err(Error::KeychainError(SECRET_NOT_FOUND.to_string()))
+    );
+    store.delete("cdb-adm-backup-key", "cdb-adm")?;
+    assert!(store.get("cdb-adm-backup-key", "cdb-adm").is_err());
+    assert!(store.delete("cdb-adm-backup-key", "cdb-adm").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_file_secret_store() -> Result<()> {
+    let path = temp_path("secrets");
+    let store = FileSecretStore::new(&path);
+    store.set("cdb-adm-test", "cdb-adm", b"cdb adm data")?;
+    store.set("cdb-adm-test", "other", b"other data")?;
+    assert!(!path.read()?.contains(&hex::encode(b"cdb adm data")));
+    assert!(store.key_path().is_file());
+
+    let reopened = FileSecretStore::new(&path);
+    assert_eq!(reopened.get("cdb-adm-test", "cdb-adm")?, b"cdb adm data".to_vec());
+    reopened.delete("cdb-adm-test", "cdb-adm")?;
+    assert_eq!(
+        store.get("cdb-adm-test", "cdb-adm"),
+        Err(Error::KeychainError(SECRET_NOT_FOUND.to_string()))
+    );
+    assert_eq!(store.get("cdb-adm-test", "other")?, b"other data".to_vec());
+
+    store.key_path().delete()?;
+    assert!(FileSecretStore::new(&path).get("cdb-adm-test", "other").is_err());
+    path.delete()?;
+    FileSecretStore::new(&path).key_path().delete()?;
+    Ok(())
+}
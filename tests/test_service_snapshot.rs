@@ -0,0 +1,73 @@
+use cdb_adm::{diff_service_snapshots, Result, ServiceDifference, ServiceSnapshot, ServiceStatus};
+
+fn status(target: (&str, &str), pid: i64, status: Option<i64>, enabled: bool) -> ServiceStatus {
+    ServiceStatus {
+        domain: target.0.to_string(),
+        service: target.1.to_string(),
+        pid,
+        status,
+        enabled,
+        path: None,
+        categories: vec!["apple".to_string()],
+    }
+}
+
+#[test]
+fn test_diff_service_snapshots() {
+    let before = ServiceSnapshot::new(vec![
+        status(("system", "com.apple.bluetoothd"), 93, Some(0), true),
+        status(("gui/501", "com.apple.nearbyd"), 0, None, true),
+        status(("gui/501", "com.apple.Siri.agent"), 612, Some(0), true),
+        status(("system", "com.apple.unchanged"), 0, Some(0), false),
+        status(("system", "com.example.gone"), 0, None, true),
+    ]);
+    let after = ServiceSnapshot::new(vec![
+        status(("system", "com.apple.bluetoothd"), 1024, Some(0), true),
+        status(("gui/501", "com.apple.nearbyd"), 77, None, false),
+        status(("gui/501", "com.apple.Siri.agent"), 0, Some(-9), true),
+        status(("system", "com.apple.unchanged"), 0, Some(0), false),
+        status(("user/501", "com.example.new"), 0, None, true),
+    ]);
+    let changes = diff_service_snapshots(&before, &after)
+        .into_iter()
+        .map(|change| (change.target(), change.differences))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        vec![
+            (
+                "gui/501/com.apple.Siri.agent".to_string(),
+                vec![
+                    ServiceDifference::Stopped { pid: 612 },
+                    ServiceDifference::ExitStatus { before: Some(0), after: Some(-9) },
+                ]
+            ),
+            (
+                "gui/501/com.apple.nearbyd".to_string(),
+                vec![ServiceDifference::Started { pid: 77 }, ServiceDifference::Disabled]
+            ),
+            (
+                "system/com.apple.bluetoothd".to_string(),
+                vec![ServiceDifference::Restarted { before: 93, after: 1024 }]
+            ),
+            ("system/com.example.gone".to_string(), vec![ServiceDifference::Removed]),
+            ("user/501/com.example.new".to_string(), vec![ServiceDifference::Added]),
+        ]
+    );
+    assert!(diff_service_snapshots(&after, &after).is_empty());
+}
+
+#[test]
+fn test_service_snapshot_save_and_load() -> Result<()> {
+    let path = iocore::Path::raw(std::env::temp_dir().display().to_string())
+        .join(format!("cdb-adm-test-snapshot-{}.json", std::process::id()));
+    let snapshot =
+        ServiceSnapshot::new(vec![status(("system", "com.apple.bluetoothd"), 93, Some(0), true)]);
+    snapshot.save(&path)?;
+    assert_eq!(ServiceSnapshot::load(&path)?, snapshot);
+    let changes = diff_service_snapshots(&ServiceSnapshot::default(), &snapshot);
+    let json = serde_json::to_value(changes)?;
+    assert_eq!(json[0]["differences"][0]["kind"], "added");
+    path.delete()?;
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use cdb_adm::{
+    category_name, filter_and_sort_statuses, label_matches, parse_catalog, DomainKind,
+    ServiceCatalog, ServiceStatus, StatusFilter, StatusSort, Uid,
+};
+
+fn status(
+    domain: &str,
+    service: &str,
+    pid: i64,
+    status: Option<i64>,
+    enabled: bool,
+) -> ServiceStatus {
+    ServiceStatus {
+        domain: domain.to_string(),
+        service: service.to_string(),
+        pid,
+        status,
+        enabled,
+        path: None,
+        categories: ServiceCatalog::builtin().categories(service),
+    }
+}
+
+fn statuses() -> Vec<ServiceStatus> {
+    vec![
+        status("gui/501", "com.apple.mobileassetd", 0, Some(0), true),
+        status("system", "com.apple.bluetoothd", 93, Some(0), true),
+        status("gui/501", "com.apple.nearbyd", 0, Some(-9), false),
+        status("user/501", "com.example.agent", 4210, None, true),
+        status("system", "com.apple.analyticsd", 310, Some(0), false),
+    ]
+}
+
+fn labels(statuses: &[ServiceStatus]) -> Vec<&str> {
+    statuses.iter().map(|status| status.service.as_str()).collect()
+}
+
+#[test]
+fn test_parse_catalog() {
+    let catalog = parse_catalog(
+        r#"[
+    // <A.I.>
+    "com.apple.assistantd",
+    // "com.apple.siriknowledged",
+    // <social&games>
+    "com.apple.gamed", // Game Center
+    // </social&games>
+    // </A.I.>
+    "com.apple.lonely",
+]"#,
+    );
+    assert_eq!(catalog.get("com.apple.assistantd"), Some(&vec!["ai".to_string()]));
+    assert_eq!(
+        catalog.get("com.apple.gamed"),
+        Some(&vec!["ai".to_string(), "social-games".to_string()])
+    );
+    assert_eq!(catalog.get("com.apple.lonely"), Some(&Vec::new()));
+    assert_eq!(catalog.get("com.apple.siriknowledged"), None);
+    assert_eq!(category_name("_fpsd group"), "fpsd-group");
+}
+
+#[test]
+fn test_builtin_catalog_categories() {
+    let catalog = ServiceCatalog::builtin();
+    let categories = catalog.categories("com.apple.mobileassetd");
+    assert!(categories.contains(&"apple".to_string()));
+    assert!(categories.contains(&"non-needed".to_string()));
+    assert!(categories.contains(&"mobile".to_string()));
+    assert_eq!(catalog.categories("com.example.agent"), vec!["third-party".to_string()]);
+    assert!(catalog.known_categories().contains(&"bluetooth".to_string()));
+}
+
+#[test]
+fn test_label_matches() {
+    assert!(label_matches("bluetooth", "com.apple.bluetoothd"));
+    assert!(label_matches("com.apple.*d", "com.apple.bluetoothd"));
+    assert!(label_matches("*.nearby*", "com.apple.nearbyd.xpc"));
+    assert!(label_matches("*", "anything"));
+    assert!(!label_matches("com.apple.*d", "com.apple.bluetooth.xpc"));
+    assert!(!label_matches("com.*apple*", "com.example.agent"));
+    assert!(!label_matches("com.apple.*.apple.com", "com.apple.com"));
+}
+
+#[test]
+fn test_filter_statuses() {
+    let running = StatusFilter { running: Some(true), ..StatusFilter::default() };
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &running, StatusSort::Label)),
+        vec!["com.apple.analyticsd", "com.apple.bluetoothd", "com.example.agent"]
+    );
+    let filter = StatusFilter { domain: Some(DomainKind::Gui), ..StatusFilter::default() };
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &filter, StatusSort::Label)),
+        vec!["com.apple.mobileassetd", "com.apple.nearbyd"]
+    );
+    let filter =
+        StatusFilter { uid: Some(Uid(501)), enabled: Some(true), ..StatusFilter::default() };
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &filter, StatusSort::Label)),
+        vec!["com.apple.mobileassetd", "com.example.agent"]
+    );
+    let filter = StatusFilter { crashed: true, ..StatusFilter::default() };
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &filter, StatusSort::Label)),
+        vec!["com.apple.nearbyd"]
+    );
+    let filter = StatusFilter {
+        label: Some("com.apple.*d".to_string()),
+        category: Some("non-needed".to_string()),
+        ..StatusFilter::default()
+    };
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &filter, StatusSort::Label)),
+        vec![
+            "com.apple.analyticsd",
+            "com.apple.bluetoothd",
+            "com.apple.mobileassetd",
+            "com.apple.nearbyd",
+        ]
+    );
+}
+
+#[test]
+fn test_sort_statuses() {
+    let all = StatusFilter::default();
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &all, StatusSort::Pid)),
+        vec![
+            "com.apple.mobileassetd",
+            "com.apple.nearbyd",
+            "com.apple.bluetoothd",
+            "com.apple.analyticsd",
+            "com.example.agent",
+        ]
+    );
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &all, StatusSort::Status)),
+        vec![
+            "com.example.agent",
+            "com.apple.nearbyd",
+            "com.apple.analyticsd",
+            "com.apple.bluetoothd",
+            "com.apple.mobileassetd",
+        ]
+    );
+    assert_eq!(
+        labels(&filter_and_sort_statuses(statuses(), &all, StatusSort::Domain)),
+        vec![
+            "com.apple.mobileassetd",
+            "com.apple.nearbyd",
+            "com.apple.analyticsd",
+            "com.apple.bluetoothd",
+            "com.example.agent",
+        ]
+    );
+}
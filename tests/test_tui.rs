@@ -0,0 +1,163 @@
+use std::sync::Arc;
+
+use cdb_adm::{
+    apply_tui_action, override_command_runner, parse_services, read_tui_journal, Error,
+    FakeCommandRunner, Result, ServiceCatalog, TuiAction, TuiCommand, TuiKey, TuiState, Uid,
+};
+use iocore_test::folder_path;
+
+fn load_fixture(name: &str) -> String {
+    let path = folder_path!().join(name);
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+
+fn plist(label: &str) -> plist::Dictionary {
+    let mut dictionary = plist::Dictionary::new();
+    dictionary.insert("Label".to_string(), plist::Value::String(label.to_string()));
+    dictionary.insert("RunAtLoad".to_string(), plist::Value::Boolean(true));
+    dictionary
+}
+
+fn state() -> TuiState {
+    let services = vec![
+        ("system", "com.apple.bluetoothd", 93, Some(0), true),
+        ("gui/501", "com.apple.nearbyd", 0, Some(-9), false),
+        ("gui/501", "com.apple.Siri.agent", 612, Some(0), true),
+        ("user/501", "com.example.agent", 4210, None, true),
+    ]
+    .into_iter()
+    .map(|(domain, service, pid, status, enabled)| {
+        let path = iocore::Path::raw(format!("/Library/LaunchAgents/{}.plist", service));
+        let info = Some((path, plist(service)));
+        (domain.to_string(), service.to_string(), pid, status, enabled, info)
+    })
+    .collect::<Vec<_>>();
+    TuiState::new(&services, &ServiceCatalog::builtin())
+}
+
+fn labels(state: &TuiState) -> Vec<&str> {
+    state.visible().iter().map(|status| status.service.as_str()).collect()
+}
+
+#[test]
+fn test_tui_search() {
+    let mut state = state();
+    assert_eq!(
+        labels(&state),
+        vec![
+            "com.apple.Siri.agent",
+            "com.apple.bluetoothd",
+            "com.apple.nearbyd",
+            "com.example.agent",
+        ]
+    );
+    for key in [TuiKey::Char('/'), TuiKey::Char('*'), TuiKey::Char('d')] {
+        assert_eq!(state.handle_key(key), None);
+    }
+    assert_eq!(labels(&state), vec!["com.apple.bluetoothd", "com.apple.nearbyd"]);
+    for key in [TuiKey::Backspace, TuiKey::Char('y'), TuiKey::Char('d')] {
+        state.handle_key(key);
+    }
+    state.handle_key(TuiKey::Enter);
+    assert_eq!(labels(&state), vec!["com.apple.nearbyd"]);
+    assert_eq!(state.handle_key(TuiKey::Esc), None);
+    assert_eq!(labels(&state).len(), 4);
+    assert_eq!(state.handle_key(TuiKey::Esc), Some(TuiCommand::Quit));
+}
+
+#[test]
+fn test_tui_select_and_confirm() {
+    let mut state = state();
+    state.handle_key(TuiKey::Down);
+    state.handle_key(TuiKey::Char(' '));
+    state.handle_key(TuiKey::Char(' '));
+    assert_eq!(state.selected.len(), 2);
+    assert_eq!(state.handle_key(TuiKey::Char('d')), None);
+    assert_eq!(state.confirm, Some(TuiAction::TurnOff));
+    assert_eq!(state.render(80, 24).last().unwrap(), "bootout and disable 2 services? [y/N]");
+    assert_eq!(state.handle_key(TuiKey::Char('n')), None);
+    assert_eq!(state.confirm, None);
+
+    state.handle_key(TuiKey::Char('e'));
+    match state.handle_key(TuiKey::Char('y')) {
+        Some(TuiCommand::Apply(TuiAction::BootUp, targets)) => assert_eq!(
+            targets.iter().map(|status| status.target()).collect::<Vec<String>>(),
+            vec!["system/com.apple.bluetoothd", "gui/501/com.apple.nearbyd"]
+        ),
+        command => panic!("unexpected command {:?}", command),
+    }
+    assert!(state.selected.is_empty());
+    assert_eq!(state.targets().len(), 1);
+}
+
+#[test]
+fn test_tui_render() {
+    let mut state = state();
+    state.handle_key(TuiKey::End);
+    let lines = state.render(100, 20);
+    assert_eq!(lines.len(), 20);
+    assert!(lines.iter().all(|line| line.chars().count() <= 100));
+    assert!(lines[0].starts_with("adm tui: 4 of 4 services, 0 selected"));
+    assert!(lines[1].contains("SERVICE"));
+    assert!(lines[5].starts_with(">[ ] com.example.agent"));
+    assert!(lines.contains(&"user/501/com.example.agent".to_string()));
+    assert!(lines.contains(&"RunAtLoad: true".to_string()));
+    assert_eq!(state.render(40, 4).len(), 4);
+}
+
+#[test]
+fn test_tui_actions_with_fake_command_runner() -> Result<()> {
+    let runner = Arc::new(
+        FakeCommandRunner::new()
+            .respond("launchctl bootout system/", 0, "")
+            .respond("launchctl disable system/", 0, "")
+            .respond("launchctl kill 9 system/", 0, ""),
+    );
+    let _guard = override_command_runner(runner.clone());
+
+    let uid = Uid(501);
+    let services = parse_services(&load_fixture("launchctl-print-system"), false)?
+        .into_iter()
+        .map(|(pid, status, service, enabled)| {
+            ("system".to_string(), service, pid, status, enabled, None)
+        })
+        .collect::<Vec<_>>();
+    let mut state = TuiState::new(&services, &ServiceCatalog::builtin());
+    state.handle_key(TuiKey::Char('/'));
+    for c in "dnsmasq".chars() {
+        state.handle_key(TuiKey::Char(c));
+    }
+    state.handle_key(TuiKey::Enter);
+    assert_eq!(labels(&state), vec!["homebrew.mxcl.dnsmasq"]);
+    assert_eq!(state.current().map(|status| status.pid), Some(515));
+
+    let journal = iocore::Path::raw(std::env::temp_dir().display().to_string())
+        .join(format!("cdb-adm-test-tui-{}", std::process::id()))
+        .join("adm-journal.jsonl");
+    let targets = state.targets();
+    let entries = apply_tui_action(&uid, TuiAction::TurnOff, &targets, &state.plists, &journal)?;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].target, "system/homebrew.mxcl.dnsmasq");
+    assert!(entries[0].ok);
+    let calls = runner.calls();
+    for subcommand in ["bootout", "disable", "kill 9"] {
+        let command = format!("launchctl {} system/homebrew.mxcl.dnsmasq", subcommand);
+        assert!(calls.iter().any(|call| call.ends_with(&command)), "{} in {:#?}", command, calls);
+    }
+
+    let entries = apply_tui_action(&uid, TuiAction::BootUp, &targets, &state.plists, &journal)?;
+    assert!(!entries[0].ok);
+    state.show_outcome(TuiAction::BootUp, &Ok(entries));
+    assert!(state.message.as_deref().is_some_and(|message| message.contains("0 ok, 1 failed")));
+    state.show_outcome(TuiAction::BootUp, &Err(Error::LaunchdError("gone".to_string())));
+    assert_eq!(state.message.as_deref(), Some("enable and kickstart failed: LaunchdError: gone"));
+    let journal_entries = read_tui_journal(&journal)?;
+    assert_eq!(
+        journal_entries.iter().map(|entry| (entry.action, entry.ok)).collect::<Vec<_>>(),
+        vec![(TuiAction::TurnOff, true), (TuiAction::BootUp, false)]
+    );
+    if let Some(parent) = journal.parent() {
+        parent.delete()?;
+    }
+    Ok(())
+}
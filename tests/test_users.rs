@@ -0,0 +1,116 @@
+use cdb_adm::{
+    launchd_system_uids, parse_dscl_list, parse_passwd, parse_user_subdomains, Result, Uid, User,
+    UserDirectory,
+};
+use iocore_test::folder_path;
+
+fn load_fixture(name: &str) -> String {
+    let path = folder_path!().join(name);
+    path.read().unwrap_or_else(|error| panic!("contents of {}: {}", &path, error))
+}
+
+fn dscl_directory() -> UserDirectory {
+    UserDirectory::from_dscl(
+        &load_fixture("dscl-list-users-UniqueID"),
+        &load_fixture("dscl-list-users-PrimaryGroupID"),
+        &load_fixture("dscl-list-users-NFSHomeDirectory"),
+        &load_fixture("dscl-list-users-UserShell"),
+    )
+}
+
+#[test]
+fn test_parse_passwd() -> Result<()> {
+    let users = parse_passwd(&load_fixture("etc-passwd"))?;
+    assert_eq!(users.len(), 11);
+    assert_eq!(
+        users[0],
+        User {
+            name: "root".to_string(),
+            uid: Uid(0),
+            gid: 0,
+            home: "/var/root".to_string(),
+            shell: "/bin/sh".to_string(),
+        }
+    );
+    assert!(users.iter().all(|user| user.is_system()));
+    assert!(users.iter().all(|user| user.name != "nobody"));
+
+    let master = parse_passwd("me:*:501:20::0:0:Me:/Users/me:/bin/zsh\n")?;
+    assert_eq!((master[0].uid, master[0].home.as_str()), (Uid(501), "/Users/me"));
+    assert!(!master[0].is_system());
+    assert!(parse_passwd("broken:*:501\n").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_system_uids_from_passwd() -> Result<()> {
+    let directory = UserDirectory::from_passwd(&load_fixture("etc-passwd"))?;
+    assert_eq!(
+        directory.system_uids(),
+        [0, 1, 4, 55, 88, 89, 200, 202, 205, 242, 262].map(Uid).to_vec()
+    );
+    assert!(directory.login_users().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_system_uids_with_a_launchd_domain() {
+    let system = load_fixture("launchctl-print-system");
+    assert_eq!(
+        parse_user_subdomains(&system),
+        [0, 55, 88, 200, 202, 205, 242, 247, 260, 262, 277, 278, 501].map(Uid).to_vec()
+    );
+    assert_eq!(
+        launchd_system_uids(&dscl_directory(), &system),
+        [0, 55, 88, 200, 202, 205, 242, 262].map(Uid).to_vec()
+    );
+}
+
+#[test]
+fn test_parse_dscl_list() {
+    let homes = parse_dscl_list(&load_fixture("dscl-list-users-NFSHomeDirectory"));
+    assert_eq!(homes.len(), 15);
+    assert_eq!(homes["root"], "/var/root");
+    assert_eq!(homes["tester"], "/Users/tester");
+}
+
+#[test]
+fn test_user_directory_from_dscl() {
+    let directory = dscl_directory();
+    assert_eq!(directory.users.len(), 14);
+    assert_eq!(
+        directory.find_by_name("gabrielfalcao"),
+        Some(&User {
+            name: "gabrielfalcao".to_string(),
+            uid: Uid(501),
+            gid: 20,
+            home: "/Users/gabrielfalcao".to_string(),
+            shell: "/bin/zsh".to_string(),
+        })
+    );
+    assert_eq!(
+        directory.login_users().iter().map(|user| user.name.as_str()).collect::<Vec<&str>>(),
+        vec!["gabrielfalcao", "tester"]
+    );
+    assert!(directory.system_uids().contains(&Uid(201)));
+    assert!(!directory.system_uids().contains(&Uid(501)));
+}
+
+#[test]
+fn test_console_user() -> Result<()> {
+    let directory = dscl_directory();
+    assert_eq!(directory.console_user(Some(Uid(502)), None)?.name, "tester");
+    assert_eq!(directory.console_user(Some(Uid(0)), Some(Uid(501)))?.name, "gabrielfalcao");
+    let error = directory.console_user(Some(Uid(0)), None).unwrap_err();
+    assert!(error.to_string().contains("gabrielfalcao, tester"));
+
+    assert_eq!(directory.resolve(None, Some("tester"))?, Uid(502));
+    assert_eq!(directory.resolve(Some(Uid(503)), None)?, Uid(503));
+    assert!(directory.resolve(None, Some("nobody")).is_err());
+
+    let single = UserDirectory::new(
+        directory.users.iter().filter(|user| user.name != "tester").cloned().collect(),
+    );
+    assert_eq!(single.console_user(None, None)?.uid, Uid(501));
+    Ok(())
+}
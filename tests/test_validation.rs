@@ -0,0 +1,152 @@
+use cdb_adm::{check_settings_files, validate_settings, Diagnostic, Severity, SystemFacts};
+
+fn facts() -> SystemFacts {
+    SystemFacts {
+        domains: Some(vec!["com.apple.finder".to_string(), "com.apple.dock".to_string()]),
+        labels: Some(vec!["com.apple.Siri.agent".to_string(), "com.apple.mds".to_string()]),
+    }
+}
+
+fn summary(diagnostics: &[Diagnostic]) -> Vec<(usize, usize, Severity, &str)> {
+    diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (diagnostic.line, diagnostic.column, diagnostic.severity, diagnostic.message.as_str())
+        })
+        .collect()
+}
+
+#[test]
+fn test_diagnostics_are_positioned_at_keys_and_values() {
+    let text = "\
+[cdb.delete]
+domains = [\"com.apple.finder\", \"com.example.gone\"]
+keys = [[\"com.example.gone\", \"Key\"], [\"com.apple.dock\", \"autohide\"]]
+
+[adm]
+include-non-needed = true
+display_warnings = \"yes\"
+
+[adm.policy]
+disabled = [\"com.apple.Siri.*\", \"gui/501/com.example.missing\"]
+
+[[adm.checks]]
+type = \"process-alive\"
+
+[hosts.studio.adm]
+verbose = true
+";
+    let diagnostics =
+        validate_settings(&iocore::Path::raw("cdb-adm.toml"), text, None, &facts());
+    assert_eq!(
+        summary(&diagnostics),
+        vec![
+            (
+                2,
+                32,
+                Severity::Warning,
+                "domain \"com.example.gone\" does not exist on this system"
+            ),
+            (
+                3,
+                9,
+                Severity::Warning,
+                "key path \"com.example.gone Key\" is in domain \"com.example.gone\" which does \
+                 not exist on this system"
+            ),
+            (
+                6,
+                1,
+                Severity::Error,
+                "unknown key \"adm.include-non-needed\", did you mean \"include_non_needed\"?"
+            ),
+            (7, 20, Severity::Error, "\"adm.display_warnings\" must be a boolean, not string"),
+            (
+                10,
+                33,
+                Severity::Warning,
+                "\"gui/501/com.example.missing\" does not match the label of any launchd plist"
+            ),
+            (12, 1, Severity::Error, "invalid health check: missing field `process`"),
+            (16, 1, Severity::Error, "unknown key \"hosts.studio.adm.verbose\""),
+        ]
+    );
+    assert_eq!(diagnostics[2].to_string().split(": ").next(), Some("cdb-adm.toml:6:1"));
+}
+
+#[test]
+fn test_sections_of_cdb_and_adm_files() {
+    let diagnostics = validate_settings(
+        &iocore::Path::raw("adm.toml"),
+        "[policy]\nbootout = [\"com.apple.mds\"]\n[execution]\nretries = 2\ndelete = 1\n",
+        Some("adm"),
+        &facts(),
+    );
+    assert_eq!(
+        summary(&diagnostics),
+        vec![(5, 1, Severity::Error, "unknown key \"execution.delete\"")]
+    );
+    let diagnostics = validate_settings(
+        &iocore::Path::raw("cdb.toml"),
+        "[delete]\ndomains = [\"com.example.gone\"]\n",
+        Some("cdb"),
+        &SystemFacts::default(),
+    );
+    assert_eq!(diagnostics, Vec::new());
+}
+
+#[test]
+fn test_syntax_errors_are_positioned() {
+    let diagnostics = validate_settings(
+        &iocore::Path::raw("cdb-adm.toml"),
+        "[adm]\ndisplay_warnings = tru\n",
+        None,
+        &facts(),
+    );
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!((diagnostics[0].line, diagnostics[0].column), (2, 20));
+    assert!(diagnostics[0].is_error());
+}
+
+#[test]
+fn test_included_files_are_checked_once() {
+    let directory = std::env::temp_dir().join(format!("cdb-adm-validation-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    std::fs::write(
+        directory.join("main.toml"),
+        "include = [\"shared.toml\", \"shared.toml\", \"missing.toml\"]\n",
+    )
+    .unwrap();
+    std::fs::write(directory.join("shared.toml"), "backup-path = \"/tmp\"\nadm = 1\n").unwrap();
+    std::fs::write(directory.join("backup"), "").unwrap();
+    std::fs::write(
+        directory.join("other.toml"),
+        format!("backup_path = {:#?}\n", directory.join("backup").display().to_string()),
+    )
+    .unwrap();
+
+    let diagnostics = check_settings_files(
+        &[
+            (iocore::Path::raw(directory.join("main.toml").display()), None),
+            (iocore::Path::raw(directory.join("other.toml").display()), None),
+        ],
+        &facts(),
+    );
+    let found = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let file = diagnostic.file.rsplit('/').next().unwrap_or_default().to_string();
+            (file, diagnostic.line, diagnostic.column, diagnostic.key.to_string())
+        })
+        .collect::<Vec<(String, usize, usize, String)>>();
+    assert_eq!(
+        found,
+        vec![
+            ("main.toml".to_string(), 1, 42, "include".to_string()),
+            ("shared.toml".to_string(), 2, 7, "adm".to_string()),
+            ("other.toml".to_string(), 1, 15, "backup_path".to_string()),
+        ]
+    );
+    assert!(diagnostics[2].message.contains("is a file"));
+    std::fs::remove_dir_all(&directory).unwrap();
+}